@@ -0,0 +1,129 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use super::{HttpConfig, into_result, validate_registry_dir};
+use crate::telemetry::init::read_env_var;
+
+/// Минимальный бюджет памяти на поток записи в tantivy
+const MIN_WRITER_MEMORY_BYTES: usize = 15_000_000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct IndexerConfig {
+    pub index_registry_dir: PathBuf,
+    pub writer_memory_bytes: usize,
+    pub autocommit_interval_secs: u64,
+    pub http: HttpConfig,
+    /// Переопределения для отдельных индексов
+    pub indexes: BTreeMap<String, IndexerIndexOverrides>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IndexerIndexOverrides {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub writer_memory_bytes: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub autocommit_interval_secs: Option<u64>,
+}
+
+/// Итоговые настройки записи конкретного индекса
+#[derive(Debug, Clone)]
+pub struct IndexWriterSettings {
+    pub writer_memory_bytes: usize,
+    pub autocommit_interval: Duration,
+}
+
+impl Default for IndexerConfig {
+    fn default() -> Self {
+        Self {
+            index_registry_dir: PathBuf::from("data/indexes"),
+            writer_memory_bytes: 2_000_000_000, // 2 GB
+            autocommit_interval_secs: 30,
+            http: HttpConfig::with_port(8090),
+            indexes: BTreeMap::new(),
+        }
+    }
+}
+
+impl Default for IndexWriterSettings {
+    fn default() -> Self {
+        IndexerConfig::default().index_settings("")
+    }
+}
+
+impl IndexerConfig {
+    pub fn index_settings(&self, index_name: &str) -> IndexWriterSettings {
+        let overrides = self.indexes.get(index_name).cloned().unwrap_or_default();
+        IndexWriterSettings {
+            writer_memory_bytes: overrides
+                .writer_memory_bytes
+                .unwrap_or(self.writer_memory_bytes),
+            autocommit_interval: Duration::from_secs(
+                overrides
+                    .autocommit_interval_secs
+                    .unwrap_or(self.autocommit_interval_secs),
+            ),
+        }
+    }
+
+    pub(super) fn apply_env(&mut self) -> Result<()> {
+        // имя переменной исторически с опечаткой
+        self.index_registry_dir = read_env_var(
+            "INDEXER_INDEX_REGISRY_DIR",
+            Some(self.index_registry_dir.clone()),
+        )?;
+        self.writer_memory_bytes = read_env_var(
+            "INDEXER_WRITER_MEMORY_BYTES",
+            Some(self.writer_memory_bytes),
+        )?;
+        self.autocommit_interval_secs = read_env_var(
+            "INDEXER_AUTOCOMMIT_INTERVAL_SECS",
+            Some(self.autocommit_interval_secs),
+        )?;
+        self.http.apply_env("INDEXER")
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        let mut errors = Vec::new();
+        validate_registry_dir("indexer", &self.index_registry_dir, &mut errors);
+        self.http.validate("indexer", &mut errors);
+        self.validate_writers(&mut errors);
+        into_result(errors)
+    }
+
+    /// Настройки writer'ов и автокоммита, общие с встроенным режимом
+    pub(super) fn validate_writers(&self, errors: &mut Vec<String>) {
+        validate_writer_memory("indexer", self.writer_memory_bytes, errors);
+        validate_autocommit("indexer", self.autocommit_interval_secs, errors);
+
+        for (name, overrides) in &self.indexes {
+            let section = format!("indexer.indexes.{name}");
+            if let Some(bytes) = overrides.writer_memory_bytes {
+                validate_writer_memory(&section, bytes, errors);
+            }
+            if let Some(secs) = overrides.autocommit_interval_secs {
+                validate_autocommit(&section, secs, errors);
+            }
+        }
+    }
+}
+
+fn validate_writer_memory(section: &str, bytes: usize, errors: &mut Vec<String>) {
+    if bytes < MIN_WRITER_MEMORY_BYTES {
+        errors.push(format!(
+            "{section}.writer_memory_bytes: must be at least {MIN_WRITER_MEMORY_BYTES}, got {bytes}"
+        ));
+    }
+}
+
+fn validate_autocommit(section: &str, secs: u64, errors: &mut Vec<String>) {
+    if secs == 0 {
+        errors.push(format!(
+            "{section}.autocommit_interval_secs: must be greater than 0"
+        ));
+    }
+}
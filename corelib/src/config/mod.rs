@@ -0,0 +1,366 @@
+mod indexer;
+mod searcher;
+mod telemetry;
+
+pub use indexer::*;
+pub use searcher::*;
+pub use telemetry::*;
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::telemetry::init::read_env_var;
+
+/// Переменная окружения с путём до конфига, если не передан `--config`
+pub const CONFIG_PATH_ENV: &str = "LISTTECH_CONFIG";
+
+pub const DEFAULT_MAX_BODY_SIZE: usize = 5 * 1024 * 1024; // 5MB лимит
+
+/// Конфигурация всех сервисов. Слои: значения по умолчанию → TOML-файл → переменные окружения.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub indexer: IndexerConfig,
+    pub searcher: SearcherConfig,
+    /// Встроенный режим (индексатор и поиск в одном процессе)
+    pub listtech: EmbeddedConfig,
+    pub telemetry: TelemetryConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HttpConfig {
+    pub bind_address: String,
+    pub port: u16,
+    pub max_body_size: usize,
+    /// Пауза между снятием готовности и остановкой приёма запросов
+    pub shutdown_delay_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EmbeddedConfig {
+    pub index_registry_dir: PathBuf,
+    pub http: HttpConfig,
+}
+
+impl Default for EmbeddedConfig {
+    fn default() -> Self {
+        Self {
+            index_registry_dir: PathBuf::from("data/indexes"),
+            http: HttpConfig::with_port(8092),
+        }
+    }
+}
+
+impl HttpConfig {
+    pub fn with_port(port: u16) -> Self {
+        Self {
+            bind_address: "0.0.0.0".to_string(),
+            port,
+            max_body_size: DEFAULT_MAX_BODY_SIZE,
+            shutdown_delay_ms: 5_000,
+        }
+    }
+
+    pub fn shutdown_delay(&self) -> Duration {
+        Duration::from_millis(self.shutdown_delay_ms)
+    }
+
+    pub fn addr(&self) -> String {
+        format!("{}:{}", self.bind_address, self.port)
+    }
+
+    fn apply_env(&mut self, prefix: &str) -> Result<()> {
+        self.bind_address = read_env_var(
+            &format!("{prefix}_BIND_ADDRESS"),
+            Some(self.bind_address.clone()),
+        )?;
+        self.port = read_env_var(&format!("{prefix}_HTTP_PORT"), Some(self.port))?;
+        self.max_body_size =
+            read_env_var(&format!("{prefix}_MAX_BODY_SIZE"), Some(self.max_body_size))?;
+        self.shutdown_delay_ms = read_env_var(
+            &format!("{prefix}_SHUTDOWN_DELAY_MS"),
+            Some(self.shutdown_delay_ms),
+        )?;
+        Ok(())
+    }
+
+    fn validate(&self, section: &str, errors: &mut Vec<String>) {
+        if self.bind_address.parse::<IpAddr>().is_err() {
+            errors.push(format!(
+                "{section}.http.bind_address: '{}' is not a valid IP address",
+                self.bind_address
+            ));
+        }
+        if self.port == 0 {
+            errors.push(format!("{section}.http.port: must be non-zero"));
+        }
+        if self.max_body_size == 0 {
+            errors.push(format!(
+                "{section}.http.max_body_size: must be greater than 0"
+            ));
+        }
+    }
+}
+
+impl EmbeddedConfig {
+    fn apply_env(&mut self) -> Result<()> {
+        self.index_registry_dir = read_env_var(
+            "LISTTECH_INDEX_REGISTRY_DIR",
+            Some(self.index_registry_dir.clone()),
+        )?;
+        self.http.apply_env("LISTTECH")
+    }
+}
+
+impl Config {
+    /// Читает конфиг из файла (если указан) и накладывает переменные окружения
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let mut config = match path {
+            Some(path) => Self::from_toml_file(path)?,
+            None => Self::default(),
+        };
+        config.apply_env()?;
+        Ok(config)
+    }
+
+    /// Проверка для встроенного режима: кроме секции `listtech` он берёт настройки
+    /// writer'ов из `indexer` и лимиты поиска из `searcher`, но не их каталоги и HTTP
+    pub fn validate_embedded(&self) -> Result<()> {
+        let mut errors = Vec::new();
+        validate_registry_dir("listtech", &self.listtech.index_registry_dir, &mut errors);
+        self.listtech.http.validate("listtech", &mut errors);
+        self.indexer.validate_writers(&mut errors);
+        self.searcher.validate_limits(&mut errors);
+        into_result(errors)
+    }
+
+    pub fn from_toml_file(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file: {:?}", path))?;
+        Self::from_toml_str(&content)
+            .with_context(|| format!("Failed to parse config file: {:?}", path))
+    }
+
+    /// Значения из файла накладываются поверх значений по умолчанию,
+    /// поэтому в файле достаточно указать только отличия
+    pub fn from_toml_str(content: &str) -> Result<Self> {
+        let mut merged = toml::Value::try_from(Self::default())?;
+        merge_toml(&mut merged, toml::from_str(content)?);
+        Ok(merged.try_into()?)
+    }
+
+    pub fn to_toml(&self) -> Result<String> {
+        toml::to_string_pretty(self).context("Failed to serialize config")
+    }
+
+    fn apply_env(&mut self) -> Result<()> {
+        self.indexer.apply_env()?;
+        self.searcher.apply_env()?;
+        self.listtech.apply_env()?;
+        self.telemetry.apply_env()
+    }
+}
+
+/// Аргументы командной строки, общие для всех бинарников
+#[derive(Debug, Default)]
+pub struct CliArgs {
+    pub config_path: Option<PathBuf>,
+    pub print_config: bool,
+}
+
+impl CliArgs {
+    pub fn from_env() -> Result<Self> {
+        Self::parse(std::env::args().skip(1))
+    }
+
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self> {
+        let mut cli = CliArgs::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--print-config" => cli.print_config = true,
+                "--config" => {
+                    let path = args.next().context("--config requires a path")?;
+                    cli.config_path = Some(PathBuf::from(path));
+                }
+                other => match other.strip_prefix("--config=") {
+                    Some(path) => cli.config_path = Some(PathBuf::from(path)),
+                    None => bail!("Unknown argument: {other}"),
+                },
+            }
+        }
+        if cli.config_path.is_none() {
+            cli.config_path = std::env::var_os(CONFIG_PATH_ENV).map(PathBuf::from);
+        }
+        Ok(cli)
+    }
+
+    /// Загружает конфиг; с `--print-config` печатает его и возвращает `None`
+    pub fn load_config(&self) -> Result<Option<Config>> {
+        let config = Config::load(self.config_path.as_deref())?;
+        if self.print_config {
+            println!("{}", config.to_toml()?);
+            return Ok(None);
+        }
+        Ok(Some(config))
+    }
+}
+
+fn merge_toml(base: &mut toml::Value, overlay: toml::Value) {
+    match (base, overlay) {
+        (toml::Value::Table(base), toml::Value::Table(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_toml(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+fn validate_registry_dir(section: &str, dir: &Path, errors: &mut Vec<String>) {
+    if !dir.is_dir() {
+        errors.push(format!(
+            "{section}.index_registry_dir: directory {:?} does not exist",
+            dir
+        ));
+    }
+}
+
+fn into_result(errors: Vec<String>) -> Result<()> {
+    if errors.is_empty() {
+        return Ok(());
+    }
+    bail!("Invalid configuration:\n  {}", errors.join("\n  "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partial_file_keeps_defaults() {
+        let config = Config::from_toml_str(
+            r#"
+            [indexer]
+            autocommit_interval_secs = 5
+
+            [indexer.indexes.electronics]
+            writer_memory_bytes = 50000000
+
+            [searcher.http]
+            port = 9000
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.indexer.http.port, 8090);
+        assert_eq!(config.searcher.http.port, 9000);
+        assert_eq!(config.searcher.http.max_body_size, DEFAULT_MAX_BODY_SIZE);
+        assert_eq!(config.searcher.http.shutdown_delay().as_secs(), 5);
+
+        let electronics = config.indexer.index_settings("electronics");
+        assert_eq!(electronics.writer_memory_bytes, 50_000_000);
+        assert_eq!(electronics.autocommit_interval.as_secs(), 5);
+        let other = config.indexer.index_settings("other");
+        assert_eq!(
+            other.writer_memory_bytes,
+            config.indexer.writer_memory_bytes
+        );
+    }
+
+    #[test]
+    fn test_unknown_keys_are_rejected() {
+        let err = Config::from_toml_str("[indexer]\nwriter_memory = 1\n").unwrap_err();
+        assert!(err.to_string().contains("writer_memory"), "{err}");
+    }
+
+    #[test]
+    fn test_validation_reports_every_problem() {
+        let config = Config::from_toml_str(
+            r#"
+            [indexer]
+            index_registry_dir = "/definitely/missing"
+            autocommit_interval_secs = 0
+
+            [indexer.http]
+            bind_address = "localhost:80"
+
+            [indexer.indexes.electronics]
+            writer_memory_bytes = 1024
+            "#,
+        )
+        .unwrap();
+
+        let err = config.indexer.validate().unwrap_err().to_string();
+        assert!(err.contains("indexer.index_registry_dir"), "{err}");
+        assert!(err.contains("indexer.autocommit_interval_secs"), "{err}");
+        assert!(err.contains("indexer.http.bind_address"), "{err}");
+        assert!(
+            err.contains("indexer.indexes.electronics.writer_memory_bytes"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_embedded_validation_checks_borrowed_sections() {
+        let dir = std::env::temp_dir();
+        let config = Config::from_toml_str(&format!(
+            r#"
+            [listtech]
+            index_registry_dir = {dir:?}
+
+            [indexer]
+            index_registry_dir = "/definitely/missing"
+            autocommit_interval_secs = 0
+
+            [searcher]
+            max_open_pits = 0
+            "#
+        ))
+        .unwrap();
+
+        let err = config.validate_embedded().unwrap_err().to_string();
+        assert!(err.contains("indexer.autocommit_interval_secs"), "{err}");
+        assert!(err.contains("searcher.max_open_pits"), "{err}");
+        // каталоги и HTTP отдельных сервисов встроенный режим не использует
+        assert!(!err.contains("indexer.index_registry_dir"), "{err}");
+        assert!(!err.contains("listtech"), "{err}");
+    }
+
+    #[test]
+    fn test_cli_args() {
+        let args = ["--config", "listtech.toml", "--print-config"].map(String::from);
+        let cli = CliArgs::parse(args).unwrap();
+        assert_eq!(cli.config_path, Some(PathBuf::from("listtech.toml")));
+        assert!(cli.print_config);
+
+        assert!(CliArgs::parse(["--verbose".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_telemetry_section() {
+        let config = Config::from_toml_str(
+            r#"
+            [telemetry]
+            log_format = "json"
+            otlp_endpoint = "localhost:4318"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.telemetry.log_format, LogFormat::Json);
+        let err = config.telemetry.validate().unwrap_err().to_string();
+        assert!(err.contains("telemetry.otlp_endpoint"), "{err}");
+        assert!(Config::default().to_toml().unwrap().contains("[telemetry]"));
+    }
+}
@@ -0,0 +1,145 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use super::{HttpConfig, into_result, validate_registry_dir};
+use crate::telemetry::init::read_env_var;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SearcherConfig {
+    pub index_registry_dir: PathBuf,
+    pub reload_policy: ReloadPolicy,
+    /// Сколько ждать, пока ридер догонит `min_opstamp` из запроса
+    pub min_opstamp_timeout_ms: u64,
+    /// Верхняя граница `keep_alive_ms` для point-in-time контекстов
+    pub max_pit_keep_alive_ms: u64,
+    /// Сколько point-in-time контекстов может быть открыто в одном индексе
+    pub max_open_pits: usize,
+    pub http: HttpConfig,
+    /// Переопределения для отдельных индексов
+    pub indexes: BTreeMap<String, SearcherIndexOverrides>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SearcherIndexOverrides {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reload_policy: Option<ReloadPolicy>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReloadPolicy {
+    Manual,
+    OnCommitWithDelay,
+}
+
+impl Default for SearcherConfig {
+    fn default() -> Self {
+        Self {
+            index_registry_dir: PathBuf::from("data/indexes"),
+            reload_policy: ReloadPolicy::OnCommitWithDelay,
+            min_opstamp_timeout_ms: 5_000,
+            max_pit_keep_alive_ms: 3_600_000,
+            max_open_pits: 100,
+            http: HttpConfig::with_port(8091),
+            indexes: BTreeMap::new(),
+        }
+    }
+}
+
+impl SearcherConfig {
+    pub fn reload_policy(&self, index_name: &str) -> ReloadPolicy {
+        self.indexes
+            .get(index_name)
+            .and_then(|overrides| overrides.reload_policy)
+            .unwrap_or(self.reload_policy)
+    }
+
+    pub fn min_opstamp_timeout(&self) -> Duration {
+        Duration::from_millis(self.min_opstamp_timeout_ms)
+    }
+
+    pub fn max_pit_keep_alive(&self) -> Duration {
+        Duration::from_millis(self.max_pit_keep_alive_ms)
+    }
+
+    pub(super) fn apply_env(&mut self) -> Result<()> {
+        self.index_registry_dir = read_env_var(
+            "SEARCHER_INDEX_REGISTRY_DIR",
+            Some(self.index_registry_dir.clone()),
+        )?;
+        self.reload_policy = read_env_var("SEARCHER_RELOAD_POLICY", Some(self.reload_policy))?;
+        self.min_opstamp_timeout_ms = read_env_var(
+            "SEARCHER_MIN_OPSTAMP_TIMEOUT_MS",
+            Some(self.min_opstamp_timeout_ms),
+        )?;
+        self.max_pit_keep_alive_ms = read_env_var(
+            "SEARCHER_MAX_PIT_KEEP_ALIVE_MS",
+            Some(self.max_pit_keep_alive_ms),
+        )?;
+        self.max_open_pits = read_env_var("SEARCHER_MAX_OPEN_PITS", Some(self.max_open_pits))?;
+        self.http.apply_env("SEARCHER")
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        let mut errors = Vec::new();
+        validate_registry_dir("searcher", &self.index_registry_dir, &mut errors);
+        self.http.validate("searcher", &mut errors);
+        self.validate_limits(&mut errors);
+        into_result(errors)
+    }
+
+    /// Таймауты и лимиты поиска, общие с встроенным режимом
+    pub(super) fn validate_limits(&self, errors: &mut Vec<String>) {
+        if self.min_opstamp_timeout_ms == 0 {
+            errors.push("searcher.min_opstamp_timeout_ms: must be greater than 0".to_string());
+        }
+        if self.max_pit_keep_alive_ms == 0 {
+            errors.push("searcher.max_pit_keep_alive_ms: must be greater than 0".to_string());
+        }
+        if self.max_open_pits == 0 {
+            errors.push("searcher.max_open_pits: must be greater than 0".to_string());
+        }
+    }
+}
+
+impl From<ReloadPolicy> for tantivy::ReloadPolicy {
+    fn from(policy: ReloadPolicy) -> Self {
+        match policy {
+            ReloadPolicy::Manual => tantivy::ReloadPolicy::Manual,
+            ReloadPolicy::OnCommitWithDelay => tantivy::ReloadPolicy::OnCommitWithDelay,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct UnknownReloadPolicy(String);
+
+impl std::fmt::Display for UnknownReloadPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "unknown reload policy '{}', expected manual or on_commit_with_delay",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for UnknownReloadPolicy {}
+
+impl FromStr for ReloadPolicy {
+    type Err = UnknownReloadPolicy;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "manual" => Ok(ReloadPolicy::Manual),
+            "on_commit_with_delay" => Ok(ReloadPolicy::OnCommitWithDelay),
+            other => Err(UnknownReloadPolicy(other.to_string())),
+        }
+    }
+}
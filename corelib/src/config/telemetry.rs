@@ -0,0 +1,71 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+use super::into_result;
+
+/// Логи и экспорт трейсов, общие для всех бинарников
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TelemetryConfig {
+    pub log_format: LogFormat,
+    /// OTLP/HTTP коллектор, например `http://localhost:4318`. Без него трейсы не экспортируются
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub otlp_endpoint: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+impl TelemetryConfig {
+    pub(super) fn apply_env(&mut self) -> Result<()> {
+        if let Ok(format) = std::env::var("LOG_FORMAT") {
+            self.log_format = format.parse()?;
+        }
+        if let Ok(endpoint) = std::env::var("OTEL_EXPORTER_OTLP_ENDPOINT") {
+            self.otlp_endpoint = Some(endpoint).filter(|e| !e.is_empty());
+        }
+        Ok(())
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        let mut errors = Vec::new();
+        if let Some(endpoint) = &self.otlp_endpoint
+            && !endpoint.starts_with("http://")
+            && !endpoint.starts_with("https://")
+        {
+            errors.push(format!(
+                "telemetry.otlp_endpoint: '{endpoint}' must start with http:// or https://"
+            ));
+        }
+        into_result(errors)
+    }
+}
+
+#[derive(Debug)]
+pub struct UnknownLogFormat(String);
+
+impl std::fmt::Display for UnknownLogFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unknown log format '{}', expected text or json", self.0)
+    }
+}
+
+impl std::error::Error for UnknownLogFormat {}
+
+impl FromStr for LogFormat {
+    type Err = UnknownLogFormat;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            other => Err(UnknownLogFormat(other.to_string())),
+        }
+    }
+}
@@ -27,6 +27,8 @@ pub struct MetaColumn {
     pub is_eq: bool,
     pub is_sort_range: bool,
     pub is_full_text: bool,
+    /// Токенизированная копия `{name}_en` для полнотекстового поиска
+    pub full_text_idx: Option<Idx>,
 }
 
 impl MetaSchema {
@@ -41,8 +43,7 @@ impl MetaSchema {
     pub fn get_full_text_col_idx(&self) -> Vec<Idx> {
         self.columns
             .iter()
-            .filter(|col| col.is_full_text)
-            .map(|mc| mc.idx)
+            .filter_map(|col| col.full_text_idx)
             .collect()
     }
 
@@ -81,6 +82,9 @@ impl MetaColumn {
     fn from_api(tantivy_schema: &TantivySchema, api_column: api::MetaColumn) -> Result<Self> {
         let idx = tantivy_schema.get_field(&api_column.name)?;
         let filed_entry = tantivy_schema.get_field_entry(idx);
+        let full_text_idx = tantivy_schema
+            .get_field(&format!("{}_en", api_column.name))
+            .ok();
 
         Ok(Self {
             name: api_column.name,
@@ -100,6 +104,7 @@ impl MetaColumn {
             is_full_text: api_column
                 .modifiers
                 .contains(&api::MetaColumnModifier::FullText),
+            full_text_idx,
         })
     }
 }
@@ -128,39 +133,39 @@ impl MetaColumn {
 //     }
 // }
 
-impl Into<api::MetaSchema> for MetaSchema {
-    fn into(self) -> api::MetaSchema {
+impl From<MetaSchema> for api::MetaSchema {
+    fn from(schema: MetaSchema) -> Self {
         api::MetaSchema {
-            name: self.name,
-            columns: self.columns.into_iter().map(MetaColumn::into).collect(),
+            name: schema.name,
+            columns: schema.columns.into_iter().map(MetaColumn::into).collect(),
         }
     }
 }
 
-impl Into<api::MetaColumn> for MetaColumn {
-    fn into(self) -> api::MetaColumn {
+impl From<MetaColumn> for api::MetaColumn {
+    fn from(column: MetaColumn) -> Self {
         let modifiers = vec![
-            if self.is_id {
+            if column.is_id {
                 Some(api::MetaColumnModifier::Id)
             } else {
                 None
             },
-            if self.is_eq {
+            if column.is_eq {
                 Some(api::MetaColumnModifier::Equals)
             } else {
                 None
             },
-            if self.is_sort_range {
+            if column.is_sort_range {
                 Some(api::MetaColumnModifier::FastSortable)
             } else {
                 None
             },
-            if self.is_full_text {
+            if column.is_full_text {
                 Some(api::MetaColumnModifier::FullText)
             } else {
                 None
             },
-            if self.is_nullable {
+            if column.is_nullable {
                 Some(api::MetaColumnModifier::Nullable)
             } else {
                 None
@@ -172,8 +177,8 @@ impl Into<api::MetaColumn> for MetaColumn {
 
         api::MetaColumn {
             // idx: self.idx,
-            name: self.name,
-            column_type: self.column_type,
+            name: column.name,
+            column_type: column.column_type,
             modifiers,
         }
     }
@@ -7,8 +7,11 @@ use axum::{
 use serde::de::DeserializeOwned;
 
 use super::typed_response::ErrorResponse;
+use crate::config::DEFAULT_MAX_BODY_SIZE;
 
-const MAX_BODY_SIZE: usize = 5 * 1024 * 1024; // 5MB лимит
+/// Лимит размера тела запроса; кладётся в extensions слоем `Extension(BodyLimit(..))`
+#[derive(Debug, Clone, Copy)]
+pub struct BodyLimit(pub usize);
 
 /// Универсальный десериализуемый запрос (JSON или CBOR)
 pub struct TypedRequest<T>(pub T);
@@ -29,14 +32,18 @@ where
     async fn from_request(req: Request, _state: &S) -> Result<Self, Self::Rejection> {
         let (parts, body) = req.into_parts();
         let headers = parts.headers.clone();
+        let max_body_size = parts
+            .extensions
+            .get::<BodyLimit>()
+            .map_or(DEFAULT_MAX_BODY_SIZE, |limit| limit.0);
 
-        let bytes = axum::body::to_bytes(body, MAX_BODY_SIZE)
+        let bytes = axum::body::to_bytes(body, max_body_size)
             .await
             .map_err(|e| {
                 ErrorResponse::bad_request("invalid_body", format!("Failed to read body: {e}"))
             })?;
 
-        if bytes.len() > MAX_BODY_SIZE {
+        if bytes.len() > max_body_size {
             return Err(ErrorResponse::bad_request("too_large", "Payload too large"));
         }
 
@@ -67,6 +67,10 @@ impl<T> TypedResponse<T> {
     pub fn internal_error(message: impl Into<String>, accept: Option<String>) -> Self {
         Self::new(Err(ErrorResponse::internal_error(message)), accept)
     }
+
+    pub fn timeout(code: &str, message: impl Into<String>, accept: Option<String>) -> Self {
+        Self::new(Err(ErrorResponse::timeout(code, message)), accept)
+    }
 }
 
 /// Ошибка, возвращаемая API. Не сериализуется статус.
@@ -109,6 +113,14 @@ impl ErrorResponse {
     pub fn internal_error(message: impl Into<String>) -> Self {
         Self::new("internal_error", message, StatusCode::INTERNAL_SERVER_ERROR)
     }
+
+    pub fn timeout(code: &str, message: impl Into<String>) -> Self {
+        Self::new(code, message, StatusCode::GATEWAY_TIMEOUT)
+    }
+
+    pub fn unavailable(code: &str, message: impl Into<String>) -> Self {
+        Self::new(code, message, StatusCode::SERVICE_UNAVAILABLE)
+    }
 }
 
 fn serialize_payload<T: Serialize>(
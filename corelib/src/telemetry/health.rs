@@ -0,0 +1,123 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::time::Duration;
+
+use axum::Router;
+use axum::extract::{Request, State};
+use axum::http::StatusCode;
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+
+use crate::model::typed_response::ErrorResponse;
+
+const STARTING: u8 = 0;
+const READY: u8 = 1;
+/// Остановка после готовности: начатые и запоздавшие запросы ещё обслуживаются
+const DRAINING: u8 = 2;
+/// Остановка до готовности: индексы так и не загрузились
+const STOPPING: u8 = 3;
+
+/// Состояние сервиса для `/readyz`: готов только после загрузки индексов и до начала остановки
+#[derive(Clone, Default)]
+pub struct Readiness(Arc<AtomicU8>);
+
+impl Readiness {
+    pub fn set_ready(&self) {
+        // после начала остановки обратно в ready не возвращаемся
+        let _ = self
+            .0
+            .compare_exchange(STARTING, READY, Ordering::SeqCst, Ordering::SeqCst);
+    }
+
+    pub fn set_shutting_down(&self) {
+        if self
+            .0
+            .compare_exchange(READY, DRAINING, Ordering::SeqCst, Ordering::SeqCst)
+            .is_err()
+        {
+            self.0.store(STOPPING, Ordering::SeqCst);
+        }
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.0.load(Ordering::SeqCst) == READY
+    }
+
+    /// Индексы загружены, API-ручки можно обслуживать (в том числе во время остановки)
+    pub fn is_serving(&self) -> bool {
+        matches!(self.0.load(Ordering::SeqCst), READY | DRAINING)
+    }
+}
+
+/// Ручки GET /healthz (процесс жив) и GET /readyz (можно слать трафик)
+pub fn router(readiness: Readiness) -> Router {
+    Router::new()
+        .route("/healthz", get(|| async { "ok" }))
+        .route("/readyz", get(handle_readyz))
+        .with_state(readiness)
+}
+
+/// Оборачивает API-ручки: пока индексы не загружены, они отвечают 503
+pub fn gate_until_ready(api: Router, readiness: Readiness) -> Router {
+    api.route_layer(middleware::from_fn_with_state(readiness, require_ready))
+}
+
+async fn require_ready(
+    State(readiness): State<Readiness>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if readiness.is_serving() {
+        next.run(request).await
+    } else {
+        ErrorResponse::unavailable("not_ready", "Indexes are not loaded yet").into_response()
+    }
+}
+
+async fn handle_readyz(State(readiness): State<Readiness>) -> (StatusCode, &'static str) {
+    if readiness.is_ready() {
+        (StatusCode::OK, "ready")
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, "not ready")
+    }
+}
+
+/// Ждёт SIGTERM или Ctrl+C, снимает готовность и через `delay` начинает остановку.
+/// Для `with_graceful_shutdown`: за время задержки балансировщик успевает увидеть not ready
+pub async fn shutdown_signal(readiness: Readiness, delay: Duration) {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!(error = %e, "Failed to listen for Ctrl+C");
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                tracing::error!(error = %e, "Failed to listen for SIGTERM");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+
+    readiness.set_shutting_down();
+    tracing::info!(
+        delay_ms = delay.as_millis() as u64,
+        "Shutdown signal received, waiting before draining"
+    );
+    tokio::time::sleep(delay).await;
+    tracing::info!("Draining in-flight requests");
+}
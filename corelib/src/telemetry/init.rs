@@ -1,24 +1,111 @@
 use std::str::FromStr;
 
 use anyhow::{Context, Result};
+use opentelemetry::global;
+use opentelemetry::trace::TracerProvider;
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use tracing::Subscriber;
 use tracing_error::ErrorLayer;
-use tracing_subscriber::{EnvFilter, fmt, fmt::format::FmtSpan, prelude::*, registry::Registry};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::{
+    EnvFilter, Layer, fmt, fmt::format::FmtSpan, prelude::*, registry::Registry,
+};
 
+use crate::config::{LogFormat, TelemetryConfig};
+
+/// Держит экспорт трейсов; при drop дописывает накопленные спаны в коллектор
+#[must_use = "spans are flushed when the guard is dropped"]
+pub struct TelemetryGuard {
+    tracer_provider: Option<SdkTracerProvider>,
+}
+
+impl Drop for TelemetryGuard {
+    fn drop(&mut self) {
+        if let Some(provider) = self.tracer_provider.take()
+            && let Err(e) = provider.shutdown()
+        {
+            eprintln!("Failed to shut down tracer provider: {e}");
+        }
+    }
+}
+
+/// Человекочитаемые логи без экспорта трейсов (для скриптов)
 pub fn init_logging() {
-    let env_filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| "info".into());
+    let guard = init_telemetry("listtech", &TelemetryConfig::default()).unwrap();
+    drop(guard);
+}
+
+/// Логи в stdout (text или json) и, если задан `otlp_endpoint`, экспорт спанов по OTLP/HTTP
+pub fn init_telemetry(service_name: &str, config: &TelemetryConfig) -> Result<TelemetryGuard> {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    let tracer_provider = config
+        .otlp_endpoint
+        .as_deref()
+        .map(|endpoint| build_tracer_provider(service_name, endpoint))
+        .transpose()?;
+
+    let otel_layer = tracer_provider.as_ref().map(|provider| {
+        tracing_opentelemetry::layer()
+            .with_tracer(provider.tracer(service_name.to_string()))
+            .with_filter(env_filter())
+    });
+
+    let subscriber = Registry::default()
+        .with(ErrorLayer::default())
+        .with(fmt_layer(config.log_format))
+        .with(otel_layer);
+    tracing::subscriber::set_global_default(subscriber)
+        .context("Failed to install tracing subscriber")?;
+
+    Ok(TelemetryGuard { tracer_provider })
+}
+
+/// Провайдер с батчевым OTLP/HTTP экспортом в `{endpoint}/v1/traces`
+pub fn build_tracer_provider(service_name: &str, endpoint: &str) -> Result<SdkTracerProvider> {
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(format!("{}/v1/traces", endpoint.trim_end_matches('/')))
+        .build()
+        .context("Failed to build OTLP span exporter")?;
 
-    let fmt_layer = fmt::layer()
+    Ok(SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(
+            Resource::builder()
+                .with_service_name(service_name.to_string())
+                .build(),
+        )
+        .build())
+}
+
+fn env_filter() -> EnvFilter {
+    EnvFilter::try_from_default_env().unwrap_or_else(|_| "info".into())
+}
+
+fn fmt_layer<S>(format: LogFormat) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    let layer = fmt::layer()
         .with_span_events(FmtSpan::CLOSE)
         .with_target(true)
         .with_level(true)
         .with_file(true)
-        .with_line_number(true)
-        .with_filter(env_filter);
+        .with_line_number(true);
 
-    let subscriber = Registry::default()
-        .with(ErrorLayer::default())
-        .with(fmt_layer);
-    tracing::subscriber::set_global_default(subscriber).unwrap();
+    match format {
+        LogFormat::Text => layer.with_filter(env_filter()).boxed(),
+        LogFormat::Json => layer
+            .json()
+            .with_current_span(true)
+            .with_span_list(true)
+            .with_filter(env_filter())
+            .boxed(),
+    }
 }
 
 pub fn read_env_var<T>(key: &str, default: Option<T>) -> Result<T>
@@ -0,0 +1,135 @@
+use std::sync::LazyLock;
+use std::time::Instant;
+
+use axum::Router;
+use axum::extract::{MatchedPath, Request};
+use axum::http::{StatusCode, header};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use prometheus::{
+    Encoder, HistogramVec, IntCounterVec, IntGaugeVec, TextEncoder, exponential_buckets,
+    register_histogram_vec, register_int_counter_vec, register_int_gauge_vec,
+};
+
+// Все метрики регистрируются в глобальном реестре prometheus: во встроенном режиме
+// индексатор и поиск живут в одном процессе и отдаются одной ручкой /metrics
+
+pub static HTTP_REQUESTS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "listtech_http_requests_total",
+        "HTTP requests by route, method and status",
+        &["route", "method", "status"]
+    )
+    .unwrap()
+});
+
+pub static HTTP_REQUEST_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "listtech_http_request_duration_seconds",
+        "HTTP request latency by route, method and status",
+        &["route", "method", "status"]
+    )
+    .unwrap()
+});
+
+pub static DOCS_INDEXED: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "listtech_documents_indexed_total",
+        "Documents accepted by the index writer",
+        &["index"]
+    )
+    .unwrap()
+});
+
+pub static DOCS_REJECTED: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "listtech_documents_rejected_total",
+        "Documents rejected during mapping or indexing",
+        &["index"]
+    )
+    .unwrap()
+});
+
+pub static COMMIT_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "listtech_commit_duration_seconds",
+        "Index commit duration, including autocommits",
+        &["index"]
+    )
+    .unwrap()
+});
+
+pub static SEARCH_ROWS_RETURNED: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "listtech_search_rows_returned",
+        "Rows returned in a search page, capped by the request limit",
+        &["index"],
+        exponential_buckets(1.0, 2.0, 12).unwrap()
+    )
+    .unwrap()
+});
+
+pub static SEARCH_COLLECT_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "listtech_search_collect_duration_seconds",
+        "Time spent in query parsing and collectors",
+        &["index"]
+    )
+    .unwrap()
+});
+
+pub static SEARCH_RESPONSE_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "listtech_search_response_duration_seconds",
+        "Time spent building the search response from stored documents",
+        &["index"]
+    )
+    .unwrap()
+});
+
+pub static INDEX_SEGMENTS: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    register_int_gauge_vec!(
+        "listtech_index_segments",
+        "Searchable segments in the index",
+        &["index"]
+    )
+    .unwrap()
+});
+
+/// Ручка GET /metrics в текстовом формате prometheus
+pub fn router() -> Router {
+    Router::new().route("/metrics", get(handle_metrics))
+}
+
+async fn handle_metrics() -> Response {
+    let mut buf = Vec::new();
+    let encoder = TextEncoder::new();
+    match encoder.encode(&prometheus::gather(), &mut buf) {
+        Ok(()) => ([(header::CONTENT_TYPE, encoder.format_type())], buf).into_response(),
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()).into_response(),
+    }
+}
+
+/// Middleware для `route_layer`: считает запросы и латентность по шаблону маршрута.
+/// Вешается на роутер после маршрутов, иначе `MatchedPath` ещё не известен.
+pub async fn track_http(req: Request, next: Next) -> Response {
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or("unmatched", |p| p.as_str())
+        .to_string();
+    let method = req.method().to_string();
+    let started = Instant::now();
+
+    let response = next.run(req).await;
+
+    let status = response.status().as_u16().to_string();
+    let labels = [route.as_str(), method.as_str(), status.as_str()];
+    HTTP_REQUESTS.with_label_values(&labels).inc();
+    HTTP_REQUEST_DURATION
+        .with_label_values(&labels)
+        .observe(started.elapsed().as_secs_f64());
+
+    response
+}
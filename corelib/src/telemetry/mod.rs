@@ -1,4 +1,7 @@
+pub mod health;
 pub mod init;
+pub mod metrics;
+pub mod trace;
 
 #[macro_export]
 macro_rules! trace_err {
@@ -0,0 +1,121 @@
+use http::{HeaderMap, Request};
+use opentelemetry::global;
+use opentelemetry::propagation::Extractor;
+use tower_http::classify::{ServerErrorsAsFailures, SharedClassifier};
+use tower_http::trace::{MakeSpan, TraceLayer};
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+/// `TraceLayer` для HTTP серверов: корневой спан запроса продолжает трейс
+/// из входящего заголовка `traceparent`
+pub fn http_trace_layer()
+-> TraceLayer<SharedClassifier<ServerErrorsAsFailures>, TraceparentMakeSpan> {
+    TraceLayer::new_for_http().make_span_with(TraceparentMakeSpan)
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TraceparentMakeSpan;
+
+impl<B> MakeSpan<B> for TraceparentMakeSpan {
+    fn make_span(&mut self, request: &Request<B>) -> Span {
+        let span = tracing::info_span!(
+            "request",
+            method = %request.method(),
+            uri = %request.uri(),
+            version = ?request.version(),
+        );
+        let parent = global::get_text_map_propagator(|propagator| {
+            propagator.extract(&HeaderExtractor(request.headers()))
+        });
+        span.set_parent(parent);
+        span
+    }
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|key| key.as_str()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::telemetry::init::build_tracer_provider;
+    use axum::{Router, body::Bytes, routing::post};
+    use opentelemetry::trace::{TraceContextExt, TracerProvider};
+    use opentelemetry_sdk::propagation::TraceContextPropagator;
+    use std::sync::mpsc;
+    use std::time::Duration;
+    use tracing_subscriber::{prelude::*, registry::Registry};
+
+    /// Коллектор-заглушка: принимает OTLP/HTTP запросы и отдаёт их тела в канал
+    fn spawn_collector() -> (String, mpsc::Receiver<Vec<u8>>) {
+        let (tx, rx) = mpsc::channel();
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        listener.set_nonblocking(true).unwrap();
+
+        std::thread::spawn(move || {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+            runtime.block_on(async move {
+                let app = Router::new().route(
+                    "/v1/traces",
+                    post(move |body: Bytes| {
+                        let tx = tx.clone();
+                        async move {
+                            let _ = tx.send(body.to_vec());
+                        }
+                    }),
+                );
+                let listener = tokio::net::TcpListener::from_std(listener).unwrap();
+                axum::serve(listener, app).await.unwrap();
+            });
+        });
+
+        (endpoint, rx)
+    }
+
+    fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+        haystack.windows(needle.len()).any(|w| w == needle)
+    }
+
+    #[test]
+    fn test_spans_continue_incoming_trace_and_reach_collector() {
+        let (endpoint, received) = spawn_collector();
+        let provider = build_tracer_provider("test", &endpoint).unwrap();
+        global::set_text_map_propagator(TraceContextPropagator::new());
+        let subscriber = Registry::default()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+
+        let trace_id = "4bf92f3577b34da6a3ce929d0e0e4736";
+        tracing::subscriber::with_default(subscriber, || {
+            let request = Request::get("/v1/select")
+                .header("traceparent", format!("00-{trace_id}-00f067aa0ba902b7-01"))
+                .body(())
+                .unwrap();
+            let span = TraceparentMakeSpan.make_span(&request);
+            let span_trace_id = span.context().span().span_context().trace_id();
+            assert_eq!(span_trace_id.to_string(), trace_id);
+            span.in_scope(|| tracing::info_span!("query_parsing").in_scope(|| {}));
+        });
+        provider.force_flush().unwrap();
+
+        let body = received.recv_timeout(Duration::from_secs(10)).unwrap();
+        let raw_trace_id: Vec<u8> = (0..trace_id.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&trace_id[i..i + 2], 16).unwrap())
+            .collect();
+        assert!(contains(&body, &raw_trace_id));
+        assert!(contains(&body, b"query_parsing"));
+    }
+}
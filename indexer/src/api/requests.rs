@@ -8,8 +8,27 @@ pub struct AddDocumentRequest {
     pub document: Document,
 }
 
-// #[derive(Debug, Clone, Serialize, Deserialize)]
-// pub struct AddDocumentResponse;
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddDocumentResponse {
+    /// Opstamp операции; документ виден поиску после коммита с opstamp >= этого
+    pub opstamp: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeleteDocumentRequest {
+    pub index_name: String,
+    pub id: FieldValue,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeleteDocumentResponse {
+    pub opstamp: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommitResponse {
+    pub opstamp: u64,
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AddSchemaRequest {
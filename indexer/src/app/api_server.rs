@@ -1,29 +1,28 @@
 use anyhow::Result;
-use axum::Router;
 use axum::extract::Path;
 use axum::extract::State;
 use axum::response::IntoResponse;
 use axum::routing::{get, post};
+use axum::{Extension, Router, middleware};
+use corelib::config::IndexerConfig;
 use corelib::model::accept::Accept;
-use std::sync::Arc;
+use corelib::model::typed_request::BodyLimit;
+use corelib::telemetry::health::{self, Readiness};
+use corelib::telemetry::{metrics, trace};
 use tokio::net::TcpListener;
-use tower_http::trace::TraceLayer;
 use tracing::{error, info, warn};
 
 use crate::api;
 use crate::api::GetSchemaResponse;
-use crate::infra::index::IndexState;
-use crate::infra::index_registry::{self, IndexRegistry};
+use crate::infra::index_registry::IndexRegistry;
 use crate::model::typed_request::TypedRequest;
 use crate::model::typed_response::TypedResponse;
 
 /// Запуск HTTP API сервера
-pub async fn run_http_server(port: u16, index_registry_dir: String) -> Result<()> {
-    let addr = format!("0.0.0.0:{port}");
-
-    // Читаем индекс
-    let index_registry =
-        index_registry::load_all_indexes(std::path::Path::new(&index_registry_dir)).await?;
+pub async fn run_http_server(config: IndexerConfig) -> Result<()> {
+    let addr = config.http.addr();
+    let readiness = Readiness::default();
+    let index_registry = IndexRegistry::new(&config);
 
     // Биндим сокет
     info!("Binding to {addr}");
@@ -34,17 +33,51 @@ pub async fn run_http_server(port: u16, index_registry_dir: String) -> Result<()
 
     info!("Starting HTTP server on {addr}");
 
-    let app = Router::new()
+    let app = health::gate_until_ready(router(index_registry.clone()), readiness.clone())
+        .merge(metrics::router())
+        .merge(health::router(readiness.clone()))
+        .layer(Extension(BodyLimit(config.http.max_body_size)))
+        .layer(trace::http_trace_layer());
+
+    let server = async {
+        axum::serve(listener, app)
+            .with_graceful_shutdown(health::shutdown_signal(
+                readiness.clone(),
+                config.http.shutdown_delay(),
+            ))
+            .await
+            .map_err(|e| {
+                error!(error = %e, "HTTP server failed");
+                anyhow::Error::from(e)
+            })
+    };
+    // Индексы читаем уже после bind, чтобы /healthz отвечал во время загрузки
+    let loading = async {
+        index_registry.load_all().await?;
+        readiness.set_ready();
+        info!("All indexes loaded");
+        Ok::<_, anyhow::Error>(())
+    };
+    // Загрузка не должна держать процесс, если сервер уже остановился
+    let served = tokio::select! {
+        served = server => served,
+        Err(err) = loading => Err(err),
+    };
+
+    // Новые запросы уже не принимаются, а начатые дослужены: фиксируем всё, что успели записать
+    index_registry.shutdown().await;
+    served
+}
+
+/// Ручки индексатора без middleware, чтобы их можно было смонтировать в общий сервер
+pub fn router(index_registry: IndexRegistry) -> Router {
+    Router::new()
         .route("/v1/doc", post(handle_add_document))
+        .route("/v1/index/{index_name}/commit", post(handle_commit))
         .route("/v1/schema/{schema_name}", get(get_schema))
         .route("/v1/schema", post(create_new_schema))
+        .route_layer(middleware::from_fn(metrics::track_http))
         .with_state(index_registry)
-        .layer(TraceLayer::new_for_http());
-
-    axum::serve(listener, app).await.map_err(|e| {
-        error!(error = %e, "HTTP server failed");
-        e.into()
-    })
 }
 
 /// Обработчик ручки POST /v1/doc
@@ -52,15 +85,15 @@ pub async fn handle_add_document(
     Accept(accept): Accept,
     State(registry): State<IndexRegistry>,
     TypedRequest(body): TypedRequest<api::AddDocumentRequest>,
-) -> TypedResponse<()> {
+) -> TypedResponse<api::AddDocumentResponse> {
     let index_name = &body.document.index_name;
 
-    let Some(index_state) = registry.inner.get(index_name) else {
+    let Some(index_state) = registry.lookup(index_name) else {
         return TypedResponse::not_found(format!("Unknown index_name: {}", index_name), accept);
     };
 
     match index_state.add_document_safely(body.document).await {
-        Ok(_) => TypedResponse::ok((), accept),
+        Ok(opstamp) => TypedResponse::ok(api::AddDocumentResponse { opstamp }, accept),
         Err(err) => {
             error!(?err, "Failed to index document");
             TypedResponse::bad_request("index_failed", format!("{err}"), accept)
@@ -68,13 +101,32 @@ pub async fn handle_add_document(
     }
 }
 
+/// Обработчик ручки POST /v1/index/{index_name}/commit
+pub async fn handle_commit(
+    Accept(accept): Accept,
+    State(registry): State<IndexRegistry>,
+    Path(index_name): Path<String>,
+) -> TypedResponse<api::CommitResponse> {
+    let Some(index_state) = registry.lookup(&index_name) else {
+        return TypedResponse::not_found(format!("Unknown index_name: {}", index_name), accept);
+    };
+
+    match index_state.commit().await {
+        Ok(opstamp) => TypedResponse::ok(api::CommitResponse { opstamp }, accept),
+        Err(err) => {
+            error!(?err, "Failed to commit index");
+            TypedResponse::internal_error(format!("Failed to commit: {err}"), accept)
+        }
+    }
+}
+
 /// Обработчик ручки GET /v1/schema
 pub async fn get_schema(
     Accept(accept): Accept,
     State(registry): State<IndexRegistry>,
     Path(schema_name): Path<String>,
 ) -> impl IntoResponse {
-    let Some(index_state) = registry.inner.get(&schema_name) else {
+    let Some(index_state) = registry.lookup(&schema_name) else {
         return TypedResponse::not_found(format!("Schema '{}' not found", schema_name), accept);
     };
 
@@ -104,16 +156,10 @@ pub async fn create_new_schema(
 
     info!("Creating new schema '{}'", schema_name);
 
-    let index_state = match IndexState::init_index_state(&registry, &schema.schema).await {
-        Ok(state) => Arc::new(state),
+    match registry.create_index(&schema.schema).await {
+        Ok(_) => TypedResponse::created(api::AddSchemaResponse, accept),
         Err(err) => {
-            return TypedResponse::internal_error(
-                format!("Failed to initialize index: {err}"),
-                accept,
-            );
+            TypedResponse::internal_error(format!("Failed to initialize index: {err}"), accept)
         }
-    };
-
-    registry.inner.insert(schema_name.clone(), index_state);
-    TypedResponse::created(api::AddSchemaResponse, accept)
+    }
 }
@@ -1,22 +1,30 @@
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, anyhow, bail};
+use corelib::config::IndexWriterSettings;
 use corelib::model::meta_schema::MetaSchema;
+use corelib::telemetry::metrics;
 use std::path::Path;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::Instant;
 use tantivy::schema::*;
-use tantivy::{Index, IndexWriter};
-use tokio::sync::Mutex;
+use tantivy::{Index, IndexWriter, Opstamp};
+use tokio::sync::{MappedMutexGuard, Mutex, MutexGuard};
+use tokio::task::AbortHandle;
+use tracing::Instrument;
 
 use crate::api;
 use crate::model::doc_mapper;
 
-use super::index_registry::IndexRegistry;
+use super::index_registry::{IndexEvent, IndexListeners, IndexRegistry};
 
 #[derive(Clone)]
 pub struct IndexState {
     pub index: Index,
     pub schema: MetaSchema,
-    pub writer: Arc<Mutex<IndexWriter>>,
+    /// `None` после [`IndexState::shutdown`]: writer закрыт, lock-файл индекса снят
+    pub writer: Arc<Mutex<Option<IndexWriter>>>,
+    pub listeners: IndexListeners,
+    /// Фоновая задача автокоммита
+    pub autocommit: AbortHandle,
 }
 
 impl IndexState {
@@ -34,7 +42,14 @@ impl IndexState {
 
         let index_dir = index_path.to_str().unwrap();
         IndexState::write_schema_file(api_schema, index_dir).await?;
-        IndexState::create_index_state(api_schema.clone(), index_dir).await
+        let settings = registry.config.index_settings(schema_name);
+        IndexState::create_index_state(
+            api_schema.clone(),
+            index_dir,
+            registry.listeners.clone(),
+            &settings,
+        )
+        .await
     }
 
     async fn write_schema_file(schema: &api::MetaSchema, dir: &str) -> Result<()> {
@@ -58,20 +73,30 @@ impl IndexState {
     pub async fn create_index_state(
         api_schema: api::MetaSchema,
         index_dir: &str,
+        listeners: IndexListeners,
+        settings: &IndexWriterSettings,
     ) -> Result<IndexState> {
         let tantivy_schema = create_tantivy_schema_from_api(&api_schema);
         let index = Index::create_in_dir(Path::new(index_dir), tantivy_schema)?;
         let meta_schema = MetaSchema::from_api(&index.schema(), api_schema)?;
-        let writer = Self::init_writer(&index).await?;
+        let (writer, autocommit) =
+            Self::init_writer(&index, &meta_schema.name, &listeners, settings).await?;
 
         Ok(IndexState {
             index,
             schema: meta_schema,
             writer,
+            listeners,
+            autocommit,
         })
     }
 
-    pub async fn read_index_state(index_dir: &Path, schema_name: &str) -> Result<IndexState> {
+    pub async fn read_index_state(
+        index_dir: &Path,
+        schema_name: &str,
+        listeners: IndexListeners,
+        settings: &IndexWriterSettings,
+    ) -> Result<IndexState> {
         let index: Index = Index::open_in_dir(index_dir)?;
 
         let delta_path = index_dir.join("delta_schema.json");
@@ -80,42 +105,95 @@ impl IndexState {
         let mut meta_schema = MetaSchema::from_api(&index.schema(), delta_schema)?;
         meta_schema.name = schema_name.to_string();
 
-        let writer = Self::init_writer(&index).await?;
+        // индексы, созданные до того, как id стал индексироваться всегда: upsert и удаление
+        // по id в них не находят документы, поэтому на запись такой индекс не открывается
+        let id_column = &meta_schema.id_column;
+        if !index.schema().get_field_entry(id_column.idx).is_indexed() {
+            bail!(
+                "Index '{schema_name}': id column '{}' is not indexed, the index was created \
+                 before id columns were always indexed; create a new schema version and reindex",
+                id_column.name
+            );
+        }
+
+        let (writer, autocommit) =
+            Self::init_writer(&index, schema_name, &listeners, settings).await?;
 
         Ok(IndexState {
             index,
             schema: meta_schema,
             writer,
+            listeners,
+            autocommit,
         })
     }
 
-    async fn init_writer(index: &Index) -> Result<Arc<Mutex<IndexWriter>>> {
-        let writer = index.writer(2_000_000_000)?; // 2 GB
-        let writer = Arc::new(Mutex::new(writer));
+    async fn init_writer(
+        index: &Index,
+        name: &str,
+        listeners: &IndexListeners,
+        settings: &IndexWriterSettings,
+    ) -> Result<(Arc<Mutex<Option<IndexWriter>>>, AbortHandle)> {
+        let writer = index.writer(settings.writer_memory_bytes)?;
+        record_segments(index, name);
+        let writer = Arc::new(Mutex::new(Some(writer)));
 
-        // автокоммит по таймеру
-        {
-            let writer_clone = writer.clone();
-            tokio::spawn(async move {
+        // автокоммит по таймеру; задача не держит writer, чтобы он закрывался вместе с индексом
+        let autocommit = {
+            let writer_weak = Arc::downgrade(&writer);
+            let index = index.clone();
+            let name = name.to_string();
+            let listeners = listeners.clone();
+            let interval = settings.autocommit_interval;
+            let task = tokio::spawn(async move {
                 loop {
-                    tokio::time::sleep(Duration::from_secs(30)).await;
-                    let mut w = writer_clone.lock().await;
-                    if let Err(e) = w.commit() {
-                        tracing::error!(error = %e, "Failed to autocommit index");
-                    } else {
-                        tracing::info!("Index autocommitted");
+                    tokio::time::sleep(interval).await;
+                    let Some(writer) = writer_weak.upgrade() else {
+                        break;
+                    };
+                    let mut w = writer
+                        .lock()
+                        .instrument(tracing::info_span!("writer_lock_wait", index = %name))
+                        .await;
+                    let Some(w) = w.as_mut() else {
+                        break;
+                    };
+                    let started = Instant::now();
+                    match w.commit() {
+                        Ok(opstamp) => {
+                            record_commit(&index, &name, started);
+                            tracing::info!(opstamp, "Index autocommitted");
+                            listeners.notify(&IndexEvent::Committed {
+                                name: name.clone(),
+                                opstamp,
+                            });
+                        }
+                        Err(e) => tracing::error!(error = %e, "Failed to autocommit index"),
                     }
                 }
             });
-        }
+            task.abort_handle()
+        };
 
-        Ok(writer)
+        Ok((writer, autocommit))
+    }
+
+    /// Добавляет документ (заменяя старый с тем же ID) и возвращает opstamp операции.
+    /// Документ станет виден поиску после коммита с opstamp не меньше этого.
+    pub async fn add_document_safely(&self, doc: api::Document) -> Result<Opstamp> {
+        let index_name = [self.schema.name.as_str()];
+        let result = self.add_document(doc).await;
+        match result {
+            Ok(_) => metrics::DOCS_INDEXED.with_label_values(&index_name).inc(),
+            Err(_) => metrics::DOCS_REJECTED.with_label_values(&index_name).inc(),
+        }
+        result
     }
 
-    pub async fn add_document_safely(&self, doc: api::Document) -> Result<()> {
+    async fn add_document(&self, doc: api::Document) -> Result<Opstamp> {
         let tantivy_doc = doc_mapper::to_tantivy_doc(&self.schema, &doc)?;
 
-        let writer = self.writer.lock().await;
+        let writer = self.lock_writer().await?;
 
         let id_col_name = self.schema.id_column.name.clone();
         let term = doc
@@ -124,26 +202,80 @@ impl IndexState {
             .find(|field| field.name == id_col_name)
             .ok_or_else(|| anyhow::anyhow!("ID not found"))
             .and_then(|field| field.value.ok_or_else(|| anyhow::anyhow!("ID is null")))
-            .and_then(|id_value| match id_value {
-                api::FieldValue::Text(id) => Ok(Term::from_field_text(
-                    self.schema.id_column.idx,
-                    id.as_str(),
-                )),
-                api::FieldValue::Long(id) => {
-                    Ok(Term::from_field_i64(self.schema.id_column.idx, id))
-                }
-                other => Err(anyhow::anyhow!("Unsupported ID type: {}", other)),
-            })?;
+            .and_then(|id_value| self.id_term(id_value))?;
         writer.delete_term(term);
 
-        loop {
-            match writer.add_document(tantivy_doc.clone()) {
-                Ok(_) => break,
-                Err(e) => return Err(e.into()),
-            }
+        let opstamp = writer.add_document(tantivy_doc)?;
+
+        Ok(opstamp)
+    }
+
+    /// Удаляет документ по ID, возвращает opstamp операции
+    pub async fn delete_document(&self, id: api::FieldValue) -> Result<Opstamp> {
+        let term = self.id_term(id)?;
+        let writer = self.lock_writer().await?;
+        Ok(writer.delete_term(term))
+    }
+
+    /// Останавливает автокоммит, делает финальный коммит и закрывает writer.
+    /// После этого индекс можно открыть на запись заново, в том числе в этом же процессе
+    pub async fn shutdown(&self) -> Result<Opstamp> {
+        self.autocommit.abort();
+        let opstamp = self.commit().await;
+        // writer закрывается и при ошибке коммита, чтобы не держать lock-файл
+        drop(self.writer.lock().await.take());
+        opstamp
+    }
+
+    async fn lock_writer(&self) -> Result<MappedMutexGuard<'_, IndexWriter>> {
+        let guard = self
+            .writer
+            .lock()
+            .instrument(tracing::info_span!("writer_lock_wait", index = %self.schema.name))
+            .await;
+        MutexGuard::try_map(guard, Option::as_mut)
+            .map_err(|_| anyhow!("Index '{}' is closed", self.schema.name))
+    }
+
+    fn id_term(&self, id_value: api::FieldValue) -> Result<Term> {
+        match id_value {
+            api::FieldValue::Text(id) => Ok(Term::from_field_text(
+                self.schema.id_column.idx,
+                id.as_str(),
+            )),
+            api::FieldValue::Long(id) => Ok(Term::from_field_i64(self.schema.id_column.idx, id)),
+            other => Err(anyhow::anyhow!("Unsupported ID type: {}", other)),
         }
+    }
 
-        Ok(())
+    /// Принудительный коммит, возвращает opstamp коммита
+    pub async fn commit(&self) -> Result<Opstamp> {
+        let mut writer = self.lock_writer().await?;
+        let started = Instant::now();
+        let opstamp = writer.commit()?;
+        record_commit(&self.index, &self.schema.name, started);
+        tracing::info!(index = %self.schema.name, opstamp, "Index committed");
+        self.listeners.notify(&IndexEvent::Committed {
+            name: self.schema.name.clone(),
+            opstamp,
+        });
+        Ok(opstamp)
+    }
+}
+
+fn record_commit(index: &Index, name: &str, started: Instant) {
+    metrics::COMMIT_DURATION
+        .with_label_values(&[name])
+        .observe(started.elapsed().as_secs_f64());
+    record_segments(index, name);
+}
+
+fn record_segments(index: &Index, name: &str) {
+    match index.searchable_segment_ids() {
+        Ok(ids) => metrics::INDEX_SEGMENTS
+            .with_label_values(&[name])
+            .set(ids.len() as i64),
+        Err(e) => tracing::warn!(error = %e, "Failed to count index segments"),
     }
 }
 
@@ -151,8 +283,9 @@ pub fn create_tantivy_schema_from_api(api_schema: &api::MetaSchema) -> tantivy::
     let mut schema_builder = tantivy::schema::Schema::builder();
 
     api_schema.columns.iter().for_each(|api_col| {
-        // let is_id = api_col.modifiers.contains(&api::MetaColumnModifier::Id);
-        let is_eq = api_col.modifiers.contains(&api::MetaColumnModifier::Equals);
+        let is_id = api_col.modifiers.contains(&api::MetaColumnModifier::Id);
+        // ID всегда индексируется: по нему делаются upsert и удаление
+        let is_eq = is_id || api_col.modifiers.contains(&api::MetaColumnModifier::Equals);
         let is_sort_range = api_col
             .modifiers
             .contains(&api::MetaColumnModifier::FastSortable);
@@ -204,7 +337,7 @@ pub fn create_tantivy_schema_from_api(api_schema: &api::MetaSchema) -> tantivy::
             api::MetaColumnType::Text => {
                 let mut opt = TextOptions::from(STORED);
                 if is_eq {
-                    opt = opt | TextOptions::from(STRING)
+                    opt = opt | STRING
                 };
                 if is_sort_range {
                     opt = opt | TextOptions::from(FAST);
@@ -212,7 +345,7 @@ pub fn create_tantivy_schema_from_api(api_schema: &api::MetaSchema) -> tantivy::
                 schema_builder.add_text_field(&api_col.name, opt);
 
                 if is_full_text {
-                    let text_opt = TextOptions::from(TEXT).set_indexing_options(
+                    let text_opt = TEXT.set_indexing_options(
                         TextFieldIndexing::default()
                             .set_tokenizer("default")
                             .set_index_option(IndexRecordOption::WithFreqsAndPositions),
@@ -243,5 +376,5 @@ pub fn create_tantivy_schema_from_api(api_schema: &api::MetaSchema) -> tantivy::
             }
         }
     });
-    return schema_builder.build();
+    schema_builder.build()
 }
@@ -1,46 +1,135 @@
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
+use corelib::config::IndexerConfig;
 use dashmap::DashMap;
 use std::fs;
-use std::path::Path;
 use std::path::PathBuf;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
+use tantivy::Opstamp;
 
 use super::index::IndexState;
+use crate::api;
 
 #[derive(Clone)]
 pub struct IndexRegistry {
     pub inner: Arc<DashMap<String, Arc<IndexState>>>,
     pub indexes_root: PathBuf,
+    pub listeners: IndexListeners,
+    pub config: Arc<IndexerConfig>,
 }
 
-pub async fn load_all_indexes(repo_path: &Path) -> Result<IndexRegistry> {
-    let registry = Arc::new(DashMap::new());
+/// События жизненного цикла индексов
+#[derive(Debug, Clone)]
+pub enum IndexEvent {
+    Created { name: String },
+    Committed { name: String, opstamp: Opstamp },
+}
+
+pub type IndexListener = Arc<dyn Fn(&IndexEvent) + Send + Sync>;
+
+/// Подписчики на события индексов. Вызываются синхронно, до ответа клиенту,
+/// поэтому в embedded-режиме коммит виден поиску сразу.
+#[derive(Clone, Default)]
+pub struct IndexListeners(Arc<RwLock<Vec<IndexListener>>>);
 
-    let entries = fs::read_dir(repo_path).context("Failed to read index repository dir")?;
+impl IndexListeners {
+    pub fn subscribe(&self, listener: IndexListener) {
+        self.0.write().unwrap().push(listener);
+    }
+
+    pub fn notify(&self, event: &IndexEvent) {
+        for listener in self.0.read().unwrap().iter() {
+            listener(event);
+        }
+    }
+}
 
-    for entry in entries {
-        let entry = entry?;
-        if !entry.file_type()?.is_dir() {
-            continue;
+impl IndexRegistry {
+    /// Пустой реестр; индексы с диска читает [`IndexRegistry::load_all`]
+    pub fn new(config: &IndexerConfig) -> Self {
+        Self {
+            inner: Arc::new(DashMap::new()),
+            indexes_root: config.index_registry_dir.clone(),
+            listeners: IndexListeners::default(),
+            config: Arc::new(config.clone()),
         }
+    }
+
+    pub async fn load_all(&self) -> Result<()> {
+        let entries =
+            fs::read_dir(&self.indexes_root).context("Failed to read index repository dir")?;
+
+        for entry in entries {
+            let entry = entry?;
+            if !entry.file_type()?.is_dir() {
+                continue;
+            }
 
-        let schema_name = entry.file_name().to_string_lossy().to_string();
-        let schema_path = entry.path();
-        let index_path = schema_path.join("index");
+            let schema_name = entry.file_name().to_string_lossy().to_string();
+            let schema_path = entry.path();
+            let index_path = schema_path.join("index");
 
-        match IndexState::read_index_state(&index_path, &schema_name).await {
-            Ok(index_state) => {
-                registry.insert(schema_name.clone(), Arc::new(index_state));
-                tracing::info!(%schema_name, "Loaded index");
+            let settings = self.config.index_settings(&schema_name);
+            match IndexState::read_index_state(
+                &index_path,
+                &schema_name,
+                self.listeners.clone(),
+                &settings,
+            )
+            .await
+            {
+                Ok(index_state) => {
+                    self.insert(schema_name.clone(), Arc::new(index_state));
+                    tracing::info!(%schema_name, "Loaded index");
+                }
+                Err(e) => {
+                    tracing::warn!(%schema_name, error = ?e, "Failed to load index");
+                }
             }
-            Err(e) => {
-                tracing::warn!(%schema_name, error = ?e, "Failed to load index");
+        }
+        Ok(())
+    }
+
+    /// Останавливает автокоммиты, коммитит все индексы, чтобы не потерять документы,
+    /// и закрывает их writer'ы
+    pub async fn shutdown(&self) {
+        let states: Vec<_> = self.inner.iter().map(|e| e.value().clone()).collect();
+        for state in states {
+            match state.shutdown().await {
+                Ok(opstamp) => {
+                    tracing::info!(index = %state.schema.name, opstamp, "Final commit done")
+                }
+                Err(err) => {
+                    tracing::error!(index = %state.schema.name, ?err, "Final commit failed")
+                }
             }
         }
     }
 
-    Ok(IndexRegistry {
-        inner: registry,
-        indexes_root: repo_path.to_path_buf(),
-    })
+    /// Создаёт индекс на диске под новую схему и регистрирует его
+    pub async fn create_index(&self, api_schema: &api::MetaSchema) -> Result<Arc<IndexState>> {
+        if self.inner.contains_key(&api_schema.name) {
+            bail!("Schema '{}' already exists", api_schema.name);
+        }
+
+        let index_state = Arc::new(IndexState::init_index_state(self, api_schema).await?);
+        self.insert(api_schema.name.clone(), index_state.clone());
+        Ok(index_state)
+    }
+
+    /// Поиск индекса по имени схемы
+    pub fn lookup(&self, name: &str) -> Option<Arc<IndexState>> {
+        let _span = tracing::info_span!("schema_lookup", index = %name).entered();
+        self.inner.get(name).map(|state| state.clone())
+    }
+
+    pub fn insert(&self, name: String, index_state: Arc<IndexState>) {
+        self.inner.insert(name.clone(), index_state);
+        self.listeners.notify(&IndexEvent::Created { name });
+    }
+}
+
+pub async fn load_all_indexes(config: &IndexerConfig) -> Result<IndexRegistry> {
+    let registry = IndexRegistry::new(config);
+    registry.load_all().await?;
+    Ok(registry)
 }
@@ -5,17 +5,21 @@ pub mod model;
 
 use anyhow::{Error, Result};
 use app::api_server;
-use corelib::telemetry::init::{init_logging, read_env_var};
+use corelib::config::CliArgs;
+use corelib::telemetry::init::init_telemetry;
 
 #[tokio::main]
 async fn main() -> Result<(), Error> {
     dotenvy::dotenv().ok();
-    init_logging();
 
-    let api_port = read_env_var("INDEXER_HTTP_PORT", None)?;
-    let index_registry_dir = read_env_var("INDEXER_INDEX_REGISRY_DIR", None)?;
+    let Some(config) = CliArgs::from_env()?.load_config()? else {
+        return Ok(());
+    };
+    config.indexer.validate()?;
+    config.telemetry.validate()?;
+    let _telemetry = init_telemetry("indexer", &config.telemetry)?;
 
-    let http_api = api_server::run_http_server(api_port, index_registry_dir);
+    let http_api = api_server::run_http_server(config.indexer);
     tokio::try_join!(http_api)?;
 
     Ok(())
@@ -8,7 +8,7 @@ use tantivy::schema::document::TantivyDocument;
 use crate::api;
 use crate::api::FieldValue::*;
 
-#[tracing::instrument(level = "debug")]
+#[tracing::instrument(name = "doc_mapping", skip_all, fields(index = %meta_schema.name))]
 pub fn to_tantivy_doc(meta_schema: &MetaSchema, doc: &api::Document) -> Result<TantivyDocument> {
     let mut compact_doc = TantivyDocument::new();
     let mut indexed_fields = HashSet::new();
@@ -29,7 +29,12 @@ pub fn to_tantivy_doc(meta_schema: &MetaSchema, doc: &api::Document) -> Result<T
             (Long(i), api::MetaColumnType::Long) => compact_doc.add_i64(idx, *i),
             (Ulong(u), api::MetaColumnType::Ulong) => compact_doc.add_u64(idx, *u),
             (Double(f), api::MetaColumnType::Double) => compact_doc.add_f64(idx, *f),
-            (Text(s), api::MetaColumnType::Text) => compact_doc.add_text(idx, s),
+            (Text(s), api::MetaColumnType::Text) => {
+                compact_doc.add_text(idx, s);
+                if let Some(full_text_idx) = meta_col.full_text_idx {
+                    compact_doc.add_text(full_text_idx, s);
+                }
+            }
             (Bytes(b), api::MetaColumnType::Bytes) => compact_doc.add_bytes(idx, b.as_slice()),
             (DateTime(iso_date), api::MetaColumnType::DateTime) => {
                 let dt: chrono::DateTime<chrono::Utc> = iso_date.parse().map_err(|_| {
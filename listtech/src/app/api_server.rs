@@ -0,0 +1,55 @@
+use anyhow::{Context, Result};
+use axum::Extension;
+use corelib::config::Config;
+use corelib::model::typed_request::BodyLimit;
+use corelib::telemetry::health::{self, Readiness};
+use corelib::telemetry::{metrics, trace};
+use tracing::info;
+
+use crate::embedded::registry::EmbeddedRegistry;
+
+/// Запуск индексатора и поиска на одном HTTP сервере
+pub async fn run_http_server(config: Config) -> Result<()> {
+    let readiness = Readiness::default();
+    let registry = EmbeddedRegistry::new(&config);
+
+    let addr = config.listtech.http.addr();
+    info!(addr = %addr, "Starting embedded HTTP server");
+
+    let app = health::gate_until_ready(router(registry.clone()), readiness.clone())
+        .merge(metrics::router())
+        .merge(health::router(readiness.clone()))
+        .layer(Extension(BodyLimit(config.listtech.http.max_body_size)))
+        .layer(trace::http_trace_layer());
+
+    let listener = tokio::net::TcpListener::bind(&addr).await?;
+
+    let server = async {
+        axum::serve(listener, app)
+            .with_graceful_shutdown(health::shutdown_signal(
+                readiness.clone(),
+                config.listtech.http.shutdown_delay(),
+            ))
+            .await
+            .context("HTTP server exited unexpectedly")
+    };
+    let loading = async {
+        registry.load_all().await?;
+        readiness.set_ready();
+        info!("All indexes loaded");
+        Ok::<_, anyhow::Error>(())
+    };
+    // Загрузка не должна держать процесс, если сервер уже остановился
+    let served = tokio::select! {
+        served = server => served,
+        Err(err) = loading => Err(err),
+    };
+
+    registry.shutdown().await;
+    served
+}
+
+pub fn router(registry: EmbeddedRegistry) -> axum::Router {
+    indexer::app::api_server::router(registry.indexer)
+        .merge(searcher::app::api_server::router(registry.searcher))
+}
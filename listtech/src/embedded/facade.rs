@@ -0,0 +1,192 @@
+use anyhow::{Context, Result, anyhow};
+use corelib::config::{Config, EmbeddedConfig};
+use indexer::api as indexer_api;
+use indexer::infra::index::IndexState;
+use searcher::api as searcher_api;
+use searcher::domain::index::SearchIndex;
+use searcher::engine::{response, search};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::registry::{self, EmbeddedRegistry};
+
+/// Библиотечный API без HTTP: индексация и поиск в одном процессе
+/// поверх общего реестра (см. [`EmbeddedRegistry`]).
+#[derive(Clone)]
+pub struct Listtech {
+    registry: EmbeddedRegistry,
+}
+
+impl Listtech {
+    /// Открывает (или создаёт пустой) каталог индексов с настройками по умолчанию
+    pub async fn open(index_registry_dir: impl AsRef<Path>) -> Result<Self> {
+        let config = Config {
+            listtech: EmbeddedConfig {
+                index_registry_dir: index_registry_dir.as_ref().to_path_buf(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        Self::open_with_config(&config).await
+    }
+
+    pub async fn open_with_config(config: &Config) -> Result<Self> {
+        let dir = &config.listtech.index_registry_dir;
+        tokio::fs::create_dir_all(dir)
+            .await
+            .with_context(|| format!("Failed to create index registry dir: {:?}", dir))?;
+
+        Ok(Self {
+            registry: registry::load_all_indexes(config).await?,
+        })
+    }
+
+    pub fn registry(&self) -> &EmbeddedRegistry {
+        &self.registry
+    }
+
+    pub async fn create_schema(
+        &self,
+        req: indexer_api::AddSchemaRequest,
+    ) -> Result<indexer_api::AddSchemaResponse> {
+        self.registry.indexer.create_index(&req.schema).await?;
+        Ok(indexer_api::AddSchemaResponse)
+    }
+
+    pub async fn index(
+        &self,
+        req: indexer_api::AddDocumentRequest,
+    ) -> Result<indexer_api::AddDocumentResponse> {
+        let index_state = self.index_state(&req.document.index_name)?;
+        let opstamp = index_state.add_document_safely(req.document).await?;
+        Ok(indexer_api::AddDocumentResponse { opstamp })
+    }
+
+    pub async fn delete(
+        &self,
+        req: indexer_api::DeleteDocumentRequest,
+    ) -> Result<indexer_api::DeleteDocumentResponse> {
+        let index_state = self.index_state(&req.index_name)?;
+        let opstamp = index_state.delete_document(req.id).await?;
+        Ok(indexer_api::DeleteDocumentResponse { opstamp })
+    }
+
+    pub async fn commit(&self, index_name: &str) -> Result<indexer_api::CommitResponse> {
+        let opstamp = self.index_state(index_name)?.commit().await?;
+        Ok(indexer_api::CommitResponse { opstamp })
+    }
+
+    /// Останавливает автокоммиты, коммитит все индексы и закрывает writer'ы: после этого
+    /// каталог можно снова открыть через [`Listtech::open`], в том числе в этом же процессе
+    pub async fn close(&self) {
+        self.registry.shutdown().await
+    }
+
+    pub async fn search(
+        &self,
+        req: &searcher_api::SearchRequest,
+    ) -> Result<searcher_api::SearchResponse> {
+        let index = self.search_index(&req.from)?;
+
+        if let Some(min_opstamp) = req.min_opstamp {
+            index
+                .wait_for_opstamp(
+                    min_opstamp,
+                    self.registry.searcher.config.min_opstamp_timeout(),
+                )
+                .await?;
+        }
+
+        let hits = search::execute_search(&index, req)?;
+        response::build_search_response(&index, &hits, req)
+    }
+
+    /// Поиск с ответом по колонкам, как у `/v1/select_columns`
+    pub async fn search_columns(
+        &self,
+        req: &searcher_api::SearchRequest,
+    ) -> Result<searcher_api::SearchMatrixResponse> {
+        let index = self.search_index(&req.from)?;
+
+        if let Some(min_opstamp) = req.min_opstamp {
+            index
+                .wait_for_opstamp(
+                    min_opstamp,
+                    self.registry.searcher.config.min_opstamp_timeout(),
+                )
+                .await?;
+        }
+
+        let hits = search::execute_search(&index, req)?;
+        response::build_matrix_response(&index, &hits, req)
+    }
+
+    pub async fn count(
+        &self,
+        req: &searcher_api::CountRequest,
+    ) -> Result<searcher_api::CountResponse> {
+        let index = self.search_index(&req.from)?;
+
+        if let Some(min_opstamp) = req.min_opstamp {
+            index
+                .wait_for_opstamp(
+                    min_opstamp,
+                    self.registry.searcher.config.min_opstamp_timeout(),
+                )
+                .await?;
+        }
+
+        let count = search::execute_count(&index, req)?;
+        Ok(searcher_api::CountResponse { count })
+    }
+
+    /// Point-in-time контекст, как у `/v1/pit`
+    pub async fn open_pit(
+        &self,
+        req: &searcher_api::PitRequest,
+    ) -> Result<searcher_api::PitResponse> {
+        let index = self.search_index(&req.from)?;
+        let config = &self.registry.searcher.config;
+
+        if let Some(min_opstamp) = req.min_opstamp {
+            index
+                .wait_for_opstamp(min_opstamp, config.min_opstamp_timeout())
+                .await?;
+        }
+
+        let pit_id = index.open_pit(
+            Duration::from_millis(req.keep_alive_ms),
+            config.max_pit_keep_alive(),
+            config.max_open_pits,
+        )?;
+        Ok(searcher_api::PitResponse {
+            pit_id,
+            keep_alive_ms: req.keep_alive_ms,
+        })
+    }
+
+    pub fn close_pit(
+        &self,
+        req: &searcher_api::ClosePitRequest,
+    ) -> Result<searcher_api::ClosePitResponse> {
+        let index = self.search_index(&req.from)?;
+        Ok(searcher_api::ClosePitResponse {
+            closed: index.pits.close(&req.pit_id),
+        })
+    }
+
+    fn index_state(&self, index_name: &str) -> Result<Arc<IndexState>> {
+        self.registry
+            .indexer
+            .lookup(index_name)
+            .ok_or_else(|| anyhow!("Unknown index_name: {}", index_name))
+    }
+
+    fn search_index(&self, index_name: &str) -> Result<Arc<SearchIndex>> {
+        self.registry
+            .searcher
+            .lookup(index_name)
+            .ok_or_else(|| anyhow!("Unknown index_name: {}", index_name))
+    }
+}
@@ -0,0 +1,2 @@
+pub mod facade;
+pub mod registry;
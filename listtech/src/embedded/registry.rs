@@ -0,0 +1,112 @@
+use anyhow::Result;
+use corelib::config::{Config, IndexerConfig};
+use indexer::infra::index::IndexState;
+use indexer::infra::index_registry::{self, IndexEvent};
+use searcher::domain::index::SearchIndex;
+use searcher::domain::registry as search_registry;
+use std::sync::{Arc, Mutex};
+use tantivy::ReloadPolicy;
+use tokio::task::AbortHandle;
+
+/// Общий реестр для запуска индексатора и поиска в одном процессе.
+/// Поисковые индексы открываются поверх тех же `tantivy::Index`, что и у индексатора,
+/// и перезагружают ридер синхронно на каждый коммит.
+#[derive(Clone)]
+pub struct EmbeddedRegistry {
+    pub indexer: index_registry::IndexRegistry,
+    pub searcher: search_registry::IndexRegistry,
+    /// Закрытие истёкших PIT-контекстов, запускается один раз в [`EmbeddedRegistry::load_all`]
+    pit_reaper: Arc<Mutex<Option<AbortHandle>>>,
+}
+
+impl EmbeddedRegistry {
+    /// Индексы читаются из `listtech.index_registry_dir`, настройки индексов берутся
+    /// из секций `indexer` и `searcher`
+    pub fn new(config: &Config) -> Self {
+        let indexer_config = IndexerConfig {
+            index_registry_dir: config.listtech.index_registry_dir.clone(),
+            ..config.indexer.clone()
+        };
+        let indexer = index_registry::IndexRegistry::new(&indexer_config);
+        let searcher = search_registry::IndexRegistry::new(&config.searcher);
+
+        // подписываемся до загрузки: каждый прочитанный или созданный индекс сразу монтируется в поиск.
+        // Подписчик хранится в самом реестре индексатора, поэтому держит его только по Weak
+        {
+            let indexes = Arc::downgrade(&indexer.inner);
+            let searcher = searcher.clone();
+            indexer
+                .listeners
+                .clone()
+                .subscribe(Arc::new(move |event| match event {
+                    IndexEvent::Created { name } => {
+                        let Some(indexes) = indexes.upgrade() else {
+                            return;
+                        };
+                        if let Some(index_state) = indexes.get(name).map(|s| s.clone()) {
+                            mirror_index(&searcher, name, &index_state);
+                        }
+                    }
+                    IndexEvent::Committed { name, opstamp } => {
+                        let Some(search_index) = searcher.inner.get(name).map(|i| i.clone())
+                        else {
+                            return;
+                        };
+                        if let Err(err) = search_index.refresh() {
+                            tracing::error!(%name, opstamp, ?err, "Failed to refresh embedded reader");
+                        }
+                    }
+                }));
+        }
+
+        Self {
+            indexer,
+            searcher,
+            pit_reaper: Arc::default(),
+        }
+    }
+
+    /// Загружает индексы и запускает закрытие истёкших PIT-контекстов
+    pub async fn load_all(&self) -> Result<()> {
+        self.pit_reaper
+            .lock()
+            .unwrap()
+            .get_or_insert_with(|| self.searcher.spawn_pit_reaper());
+        self.indexer.load_all().await
+    }
+
+    /// Останавливает закрытие PIT-контекстов, делает финальный коммит и закрывает writer'ы
+    /// всех индексов, см. [`index_registry::IndexRegistry::shutdown`]
+    pub async fn shutdown(&self) {
+        if let Some(pit_reaper) = self.pit_reaper.lock().unwrap().take() {
+            pit_reaper.abort();
+        }
+        self.indexer.shutdown().await
+    }
+}
+
+pub async fn load_all_indexes(config: &Config) -> Result<EmbeddedRegistry> {
+    let registry = EmbeddedRegistry::new(config);
+    registry.load_all().await?;
+    Ok(registry)
+}
+
+fn mirror_index(searcher: &search_registry::IndexRegistry, name: &str, index_state: &IndexState) {
+    if searcher.inner.contains_key(name) {
+        return;
+    }
+
+    match SearchIndex::from_index(
+        index_state.index.clone(),
+        index_state.schema.clone(),
+        ReloadPolicy::Manual,
+    ) {
+        Ok(search_index) => {
+            searcher
+                .inner
+                .insert(name.to_string(), Arc::new(search_index));
+            tracing::info!(%name, "Mounted embedded search index");
+        }
+        Err(err) => tracing::error!(%name, ?err, "Failed to mount embedded search index"),
+    }
+}
@@ -0,0 +1,4 @@
+pub mod app;
+pub mod embedded;
+
+pub use embedded::facade::Listtech;
@@ -0,0 +1,21 @@
+use anyhow::Result;
+use corelib::config::CliArgs;
+use corelib::telemetry::init::init_telemetry;
+use listtech::app::api_server;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    dotenvy::dotenv().ok();
+
+    let Some(config) = CliArgs::from_env()?.load_config()? else {
+        return Ok(());
+    };
+    config.validate_embedded()?;
+    config.telemetry.validate()?;
+    let _telemetry = init_telemetry("listtech", &config.telemetry)?;
+
+    let http_task = api_server::run_http_server(config);
+    tokio::try_join!(http_task)?;
+
+    Ok(())
+}
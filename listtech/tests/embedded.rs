@@ -0,0 +1,140 @@
+use axum::Router;
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use corelib::config::Config;
+use corelib::telemetry::health::{self, Readiness};
+use corelib::telemetry::metrics;
+use http_body_util::BodyExt;
+use listtech::app::api_server::router;
+use listtech::embedded::registry::load_all_indexes;
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+async fn post(app: &Router, uri: &str, body: Value) -> (StatusCode, Value) {
+    let request = Request::post(uri)
+        .header("content-type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let bytes = response.into_body().collect().await.unwrap().to_bytes();
+    (
+        status,
+        serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+    )
+}
+
+#[tokio::test]
+async fn test_commit_is_visible_to_search_immediately() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = Config::default();
+    config.listtech.index_registry_dir = dir.path().to_path_buf();
+    let app = router(load_all_indexes(&config).await.unwrap());
+
+    let schema = json!({
+        "schema": {
+            "name": "products",
+            "columns": [
+                { "name": "id", "column_type": "text", "modifiers": ["id"] },
+                { "name": "title", "column_type": "text", "modifiers": ["equals", "full_text"] }
+            ]
+        }
+    });
+    let (status, _) = post(&app, "/v1/schema", schema).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let doc = json!({
+        "document": {
+            "index_name": "products",
+            "index_version": 1,
+            "fields": [
+                { "name": "id", "value": { "text": "1" } },
+                { "name": "title", "value": { "text": "macbook pro" } }
+            ]
+        }
+    });
+    let (status, _) = post(&app, "/v1/doc", doc).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = post(&app, "/v1/index/products/commit", json!({})).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let select = json!({ "select": ["id", "title"], "from": "products", "filter": "*" });
+    let (status, body) = post(&app, "/v1/select", select).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["rows"].as_array().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_metrics_count_requests_and_documents() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = Config::default();
+    config.listtech.index_registry_dir = dir.path().to_path_buf();
+    let app = router(load_all_indexes(&config).await.unwrap()).merge(metrics::router());
+
+    let schema = json!({
+        "schema": {
+            "name": "metered",
+            "columns": [{ "name": "id", "column_type": "long", "modifiers": ["id"] }]
+        }
+    });
+    post(&app, "/v1/schema", schema).await;
+    let doc = json!({
+        "document": {
+            "index_name": "metered",
+            "index_version": 1,
+            "fields": [{ "name": "id", "value": { "text": "not a number" } }]
+        }
+    });
+    let (status, _) = post(&app, "/v1/doc", doc).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let request = Request::get("/metrics").body(Body::empty()).unwrap();
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let bytes = response.into_body().collect().await.unwrap().to_bytes();
+    let text = String::from_utf8(bytes.to_vec()).unwrap();
+
+    assert!(text.contains(r#"listtech_documents_rejected_total{index="metered"} 1"#));
+    assert!(
+        text.contains(
+            r#"listtech_http_requests_total{method="POST",route="/v1/doc",status="400"}"#
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_api_is_unavailable_until_indexes_are_loaded() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = Config::default();
+    config.listtech.index_registry_dir = dir.path().to_path_buf();
+    let readiness = Readiness::default();
+    let app = health::gate_until_ready(
+        router(load_all_indexes(&config).await.unwrap()),
+        readiness.clone(),
+    )
+    .merge(health::router(readiness.clone()));
+
+    let schema = json!({
+        "schema": {
+            "name": "gated",
+            "columns": [{ "name": "id", "column_type": "long", "modifiers": ["id"] }]
+        }
+    });
+    let (status, body) = post(&app, "/v1/schema", schema.clone()).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["code"], "not_ready");
+
+    readiness.set_ready();
+    let (status, _) = post(&app, "/v1/schema", schema).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // Во время остановки /readyz уже не готов, но начатый трафик ещё обслуживается
+    readiness.set_shutting_down();
+    let request = Request::get("/readyz").body(Body::empty()).unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let select = json!({ "select": ["id"], "from": "gated" });
+    let (status, _) = post(&app, "/v1/select", select).await;
+    assert_eq!(status, StatusCode::OK);
+}
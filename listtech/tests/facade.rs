@@ -0,0 +1,287 @@
+use std::collections::HashSet;
+
+use corelib::api::{MetaColumn, MetaColumnModifier, MetaColumnType, MetaSchema};
+use indexer::api::{
+    AddDocumentRequest, AddSchemaRequest, DeleteDocumentRequest, Document, FieldValue,
+    IndexableField,
+};
+use listtech::Listtech;
+use searcher::api::{SearchRequest, SearchValue};
+
+fn schema() -> MetaSchema {
+    let column = |name: &str, column_type, modifiers: &[MetaColumnModifier]| MetaColumn {
+        name: name.to_string(),
+        column_type,
+        modifiers: modifiers.iter().cloned().collect::<HashSet<_>>(),
+    };
+
+    MetaSchema {
+        name: "products".to_string(),
+        columns: vec![
+            column("id", MetaColumnType::Text, &[MetaColumnModifier::Id]),
+            column(
+                "title",
+                MetaColumnType::Text,
+                &[MetaColumnModifier::Equals, MetaColumnModifier::FullText],
+            ),
+        ],
+    }
+}
+
+fn add(id: &str, title: &str) -> AddDocumentRequest {
+    let field = |name: &str, value: &str| IndexableField {
+        name: name.to_string(),
+        value: Some(FieldValue::Text(value.to_string())),
+    };
+
+    AddDocumentRequest {
+        document: Document {
+            index_name: "products".to_string(),
+            index_version: 1,
+            fields: vec![field("id", id), field("title", title)],
+        },
+    }
+}
+
+#[tokio::test]
+async fn test_index_delete_commit_search() {
+    let dir = tempfile::tempdir().unwrap();
+    let listtech = Listtech::open(dir.path().join("indexes")).await.unwrap();
+
+    listtech
+        .create_schema(AddSchemaRequest { schema: schema() })
+        .await
+        .unwrap();
+    listtech.index(add("1", "macbook pro")).await.unwrap();
+    listtech.index(add("2", "iphone 12")).await.unwrap();
+    listtech
+        .delete(DeleteDocumentRequest {
+            index_name: "products".to_string(),
+            id: FieldValue::Text("2".to_string()),
+        })
+        .await
+        .unwrap();
+    listtech.commit("products").await.unwrap();
+
+    let req: SearchRequest = serde_json::from_value(serde_json::json!({
+        "select": ["id"],
+        "from": "products",
+        "filter": "*",
+    }))
+    .unwrap();
+    let response = listtech.search(&req).await.unwrap();
+
+    assert_eq!(response.rows.len(), 1);
+    assert!(matches!(
+        &response.rows[0].fields[0].value,
+        SearchValue::Str(id) if id == "1"
+    ));
+}
+
+#[tokio::test]
+async fn test_unknown_index_is_an_error() {
+    let dir = tempfile::tempdir().unwrap();
+    let listtech = Listtech::open(dir.path()).await.unwrap();
+
+    assert!(listtech.commit("missing").await.is_err());
+}
+
+#[tokio::test]
+async fn test_close_commits_pending_documents() {
+    let dir = tempfile::tempdir().unwrap();
+    let listtech = Listtech::open(dir.path()).await.unwrap();
+
+    listtech
+        .create_schema(AddSchemaRequest { schema: schema() })
+        .await
+        .unwrap();
+    listtech.index(add("1", "macbook pro")).await.unwrap();
+    listtech.close().await;
+
+    let index = tantivy::Index::open_in_dir(dir.path().join("products").join("index")).unwrap();
+    assert_eq!(index.reader().unwrap().searcher().num_docs(), 1);
+}
+
+#[tokio::test]
+async fn test_index_with_unindexed_id_is_not_loaded() {
+    let dir = tempfile::tempdir().unwrap();
+    let index_dir = dir.path().join("products").join("index");
+    std::fs::create_dir_all(&index_dir).unwrap();
+
+    // так выглядели индексы, у которых id был без `equals`, до того как id стал INDEXED всегда
+    let mut builder = tantivy::schema::Schema::builder();
+    builder.add_text_field("id", tantivy::schema::STORED);
+    builder.add_text_field("title", tantivy::schema::STRING | tantivy::schema::STORED);
+    builder.add_text_field("title_en", tantivy::schema::TEXT);
+    tantivy::Index::create_in_dir(&index_dir, builder.build()).unwrap();
+    std::fs::write(
+        index_dir.join("delta_schema.json"),
+        serde_json::to_vec(&schema()).unwrap(),
+    )
+    .unwrap();
+
+    let listtech = Listtech::open(dir.path()).await.unwrap();
+    let err = listtech.index(add("1", "macbook pro")).await.unwrap_err();
+    assert!(err.to_string().contains("Unknown index_name"), "{err}");
+
+    let err = indexer::infra::index::IndexState::read_index_state(
+        &index_dir,
+        "products",
+        Default::default(),
+        &Default::default(),
+    )
+    .await
+    .err()
+    .unwrap();
+    assert!(
+        err.to_string().contains("id column 'id' is not indexed"),
+        "{err}"
+    );
+}
+
+#[tokio::test]
+async fn test_reopen_same_dir_after_close_and_drop() {
+    let dir = tempfile::tempdir().unwrap();
+    let listtech = Listtech::open(dir.path()).await.unwrap();
+    listtech
+        .create_schema(AddSchemaRequest { schema: schema() })
+        .await
+        .unwrap();
+    listtech.index(add("1", "macbook pro")).await.unwrap();
+    listtech.close().await;
+    assert!(listtech.index(add("2", "iphone 12")).await.is_err());
+    drop(listtech);
+
+    // writer закрыт в close(), lock-файл индекса свободен
+    let listtech = Listtech::open(dir.path()).await.unwrap();
+    listtech.index(add("2", "iphone 12")).await.unwrap();
+    listtech.commit("products").await.unwrap();
+    drop(listtech);
+
+    // и без close(): writer освобождается вместе с последней копией фасада
+    let listtech = Listtech::open(dir.path()).await.unwrap();
+    listtech.index(add("3", "thinkpad")).await.unwrap();
+    let committed = listtech.commit("products").await.unwrap();
+
+    let req: SearchRequest = serde_json::from_value(serde_json::json!({
+        "select": ["id"],
+        "from": "products",
+        "min_opstamp": committed.opstamp,
+    }))
+    .unwrap();
+    assert_eq!(listtech.search(&req).await.unwrap().rows.len(), 3);
+}
+
+#[tokio::test]
+async fn test_pit_pins_snapshot_until_closed() {
+    let dir = tempfile::tempdir().unwrap();
+    let listtech = Listtech::open(dir.path()).await.unwrap();
+
+    listtech
+        .create_schema(AddSchemaRequest { schema: schema() })
+        .await
+        .unwrap();
+    listtech.index(add("1", "macbook pro")).await.unwrap();
+    listtech.index(add("2", "iphone 12")).await.unwrap();
+    listtech.commit("products").await.unwrap();
+
+    let pit = listtech
+        .open_pit(&serde_json::from_value(serde_json::json!({"from": "products"})).unwrap())
+        .await
+        .unwrap();
+
+    listtech
+        .delete(DeleteDocumentRequest {
+            index_name: "products".to_string(),
+            id: FieldValue::Text("1".to_string()),
+        })
+        .await
+        .unwrap();
+    listtech.index(add("3", "thinkpad")).await.unwrap();
+    listtech.index(add("4", "legion")).await.unwrap();
+    listtech.commit("products").await.unwrap();
+
+    let search_ids = |pit_id: Option<&str>| {
+        let req: SearchRequest = serde_json::from_value(serde_json::json!({
+            "select": ["id"],
+            "from": "products",
+            "pit_id": pit_id,
+        }))
+        .unwrap();
+        let listtech = listtech.clone();
+        async move {
+            let response = listtech.search(&req).await?;
+            let mut ids: Vec<String> = response
+                .rows
+                .iter()
+                .map(|row| match &row.fields[0].value {
+                    SearchValue::Str(id) => id.clone(),
+                    other => panic!("unexpected id value {other:?}"),
+                })
+                .collect();
+            ids.sort();
+            anyhow::Ok(ids)
+        }
+    };
+
+    assert_eq!(search_ids(Some(&pit.pit_id)).await.unwrap(), ["1", "2"]);
+    assert_eq!(search_ids(None).await.unwrap(), ["2", "3", "4"]);
+
+    // счётчик по тому же контексту совпадает со страницами, а не с текущим снимком
+    let count = |pit_id: Option<&str>| {
+        serde_json::from_value(serde_json::json!({"from": "products", "pit_id": pit_id})).unwrap()
+    };
+    assert_eq!(
+        listtech
+            .count(&count(Some(&pit.pit_id)))
+            .await
+            .unwrap()
+            .count,
+        2
+    );
+    assert_eq!(listtech.count(&count(None)).await.unwrap().count, 3);
+
+    let close = serde_json::from_value(serde_json::json!({
+        "from": "products",
+        "pit_id": pit.pit_id,
+    }))
+    .unwrap();
+    assert!(listtech.close_pit(&close).unwrap().closed);
+    assert!(!listtech.close_pit(&close).unwrap().closed);
+    assert!(search_ids(Some(&pit.pit_id)).await.is_err());
+
+    let forever = serde_json::json!({"from": "products", "keep_alive_ms": u64::MAX});
+    assert!(
+        listtech
+            .open_pit(&serde_json::from_value(forever).unwrap())
+            .await
+            .is_err()
+    );
+}
+
+#[tokio::test]
+async fn test_open_pits_are_capped_per_index() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = corelib::config::Config::default();
+    config.listtech.index_registry_dir = dir.path().to_path_buf();
+    config.searcher.max_open_pits = 2;
+    let listtech = Listtech::open_with_config(&config).await.unwrap();
+    listtech
+        .create_schema(AddSchemaRequest { schema: schema() })
+        .await
+        .unwrap();
+
+    let open = || serde_json::from_value(serde_json::json!({"from": "products"})).unwrap();
+    let first = listtech.open_pit(&open()).await.unwrap();
+    listtech.open_pit(&open()).await.unwrap();
+    let err = listtech.open_pit(&open()).await.unwrap_err();
+    assert!(err.to_string().contains("limit 2"), "{err}");
+
+    let close = serde_json::from_value(serde_json::json!({
+        "from": "products",
+        "pit_id": first.pit_id,
+    }))
+    .unwrap();
+    assert!(listtech.close_pit(&close).unwrap().closed);
+    assert!(listtech.open_pit(&open()).await.is_ok());
+}
@@ -1,4 +1,4 @@
-use chrono::{NaiveDate, NaiveDateTime};
+use chrono::{DateTime, NaiveDate};
 use indexer::api;
 use rand::Rng;
 
@@ -86,7 +86,7 @@ impl<'a> FieldsBuilder<'a> {
 
                 let mut rng = rand::rng();
                 let random_ts = rng.random_range(start..end);
-                NaiveDateTime::from_timestamp_opt(random_ts, 0).unwrap()
+                DateTime::from_timestamp(random_ts, 0).unwrap().naive_utc()
             }
         };
 
@@ -131,25 +131,25 @@ impl<'a> FieldsBuilder<'a> {
     pub fn rank(mut self) -> Self {
         if let Some(arr) = self.json.get("rank").and_then(|v| v.as_array()) {
             for item in arr {
-                if let Some(text) = item.as_str() {
-                    if let Some((rank_str, cat_str)) = text.split_once(" in ") {
-                        let rank_clean = rank_str.trim_start_matches(">#").replace(",", "");
-                        if let Ok(rank_value) = rank_clean.parse::<u64>() {
-                            self.fields.push(api::IndexableField {
-                                name: "rank_position".to_string(),
-                                value: Some(api::FieldValue::Ulong(rank_value)),
-                            });
-                        }
-
-                        let facet_path = format!(
-                            "/{}",
-                            cat_str.replace(" &gt; ", "/").replace(" > ", "/").trim()
-                        );
+                if let Some(text) = item.as_str()
+                    && let Some((rank_str, cat_str)) = text.split_once(" in ")
+                {
+                    let rank_clean = rank_str.trim_start_matches(">#").replace(",", "");
+                    if let Ok(rank_value) = rank_clean.parse::<u64>() {
                         self.fields.push(api::IndexableField {
-                            name: "rank_facet".to_string(),
-                            value: Some(api::FieldValue::Tree(vec![facet_path])),
+                            name: "rank_position".to_string(),
+                            value: Some(api::FieldValue::Ulong(rank_value)),
                         });
                     }
+
+                    let facet_path = format!(
+                        "/{}",
+                        cat_str.replace(" &gt; ", "/").replace(" > ", "/").trim()
+                    );
+                    self.fields.push(api::IndexableField {
+                        name: "rank_facet".to_string(),
+                        value: Some(api::FieldValue::Tree(vec![facet_path])),
+                    });
                 }
             }
         }
@@ -158,10 +158,10 @@ impl<'a> FieldsBuilder<'a> {
 
     pub fn build(self) -> Vec<api::IndexableField> {
         for f in &self.fields {
-            if let Some(api::FieldValue::Text(ref s)) = f.value {
-                if s.len() > 65530 {
-                    tracing::warn!(field = %f.name, len = s.len(), value = %s, "String too long");
-                }
+            if let Some(api::FieldValue::Text(ref s)) = f.value
+                && s.len() > 65530
+            {
+                tracing::warn!(field = %f.name, len = s.len(), value = %s, "String too long");
             }
             if let Some(api::FieldValue::Tree(ref paths)) = f.value {
                 for path in paths {
@@ -5,16 +5,18 @@ export $(grep -v '^#' .env | xargs)
 
 SEARCHER_URL="http://localhost:$SEARCHER_HTTP_PORT"
 
-# "-ln(price + 1) - 10 * exp(-0.01 * ((now_ms() - 202176000000 - timestamp_creation_ms) / 86400000))"
+# "ln(price + 1) + 10 * exp(-0.01 * ((now_ms() - 202176000000 - timestamp_creation_ms) / 86400000))"
 
 curl -X POST "$SEARCHER_URL/v1/select" \
   -H "Content-Type: application/json" \
   -d '{
     "select": ["title", "price", "timestamp_creation_ms"],
     "filter": "*",
-    "functions": [],
+    "functions": ["freshness(t) = exp(-0.01 * ((now_ms() - 202176000000 - t) / 86400000))"],
     "from": "electronics",
-    "sort": "-ln(price + 1) - 10 * exp(-0.01 * ((now_ms() - 202176000000 - timestamp_creation_ms) / 86400000))",
+    "sort": "ln(price + 1) + $boost * freshness(timestamp_creation_ms)",
+    "params": {"boost": 10},
+    "sort_order": "desc",
     "limit": 20,
     "offset": 0
     }' | jq
@@ -0,0 +1,105 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Агрегация по `fast_sortable` колонке, считается по всем найденным документам.
+/// Даты участвуют как миллисекунды от эпохи, bool — как 0/1.
+///
+/// ```json
+/// {"price": {"histogram": {"field": "price", "interval": 100}},
+///  "posted": {"range": {"field": "created_at", "ranges": [{"key": "day", "from": "now-1d"}]}}}
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Aggregation {
+    /// count/min/max/avg/sum
+    Stats { field: String },
+    /// Корзины фиксированной ширины `interval`, начиная от `offset`
+    Histogram {
+        field: String,
+        interval: f64,
+        #[serde(default)]
+        offset: f64,
+    },
+    /// Произвольные корзины `[from, to)`; для дат границы — ISO8601, миллисекунды
+    /// или `now`/`now-7d` (единицы `m`, `h`, `d`, `w`)
+    Range {
+        field: String,
+        ranges: Vec<RangeBucket>,
+    },
+    /// Календарные корзины по UTC
+    DateHistogram {
+        field: String,
+        interval: DateInterval,
+    },
+    /// Самые частые значения `fast_sortable` текстовой колонки
+    Terms {
+        field: String,
+        #[serde(default = "default_terms_size")]
+        size: usize,
+        #[serde(default = "default_min_doc_count")]
+        min_doc_count: u64,
+    },
+    /// Приблизительное число различных значений (HyperLogLog, ошибка ~1%)
+    Cardinality { field: String },
+}
+
+const fn default_terms_size() -> usize {
+    10
+}
+
+const fn default_min_doc_count() -> u64 {
+    1
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RangeBucket {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to: Option<Value>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DateInterval {
+    Minute,
+    Hour,
+    Day,
+    /// Неделя с понедельника
+    Week,
+    Month,
+    Year,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum AggregationResult {
+    /// Для пустой выборки `min`/`max`/`avg` равны null
+    Stats {
+        count: u64,
+        min: Option<f64>,
+        max: Option<f64>,
+        avg: Option<f64>,
+        sum: f64,
+    },
+    Buckets {
+        buckets: Vec<Bucket>,
+    },
+    Cardinality {
+        value: u64,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Bucket {
+    /// Нижняя граница для `histogram`, ISO8601 начала корзины для `date_histogram`,
+    /// `key` или `from-to` для `range`, значение колонки для `terms`
+    pub key: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to: Option<f64>,
+    pub doc_count: u64,
+}
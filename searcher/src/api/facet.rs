@@ -0,0 +1,38 @@
+use serde::{Deserialize, Serialize};
+
+/// Запрос счётчиков по `Tree` колонке: top-N прямых потомков узла `path`
+///
+/// ```json
+/// {"field": "category", "path": "/Electronics", "top": 5}
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FacetRequest {
+    pub field: String,
+    #[serde(default = "default_path")]
+    pub path: String,
+    #[serde(default = "default_top")]
+    pub top: usize,
+}
+
+fn default_path() -> String {
+    "/".to_string()
+}
+
+const fn default_top() -> usize {
+    10
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FacetResult {
+    pub field: String,
+    pub path: String,
+    /// Потомки по убыванию количества документов
+    pub counts: Vec<FacetCount>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FacetCount {
+    /// Полный путь узла, например `/Electronics/Phones`
+    pub path: String,
+    pub count: u64,
+}
@@ -0,0 +1,71 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Структурный фильтр. Значения в `term`/`terms`/`range` приводятся к типу колонки:
+/// числа и bool как есть, `date_time` — строка ISO8601, `tree` — путь `/a/b`.
+///
+/// ```json
+/// {"and": [
+///     {"term": {"field": "brand", "value": "apple"}},
+///     {"range": {"field": "price", "gte": 10, "lt": 100}},
+///     {"not": {"exists": {"field": "discontinued_at"}}}
+/// ]}
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Filter {
+    And(Vec<Filter>),
+    Or(Vec<Filter>),
+    Not(Box<Filter>),
+    /// Точное совпадение по индексированной (`equals`/`id`) колонке
+    Term {
+        field: String,
+        value: Value,
+    },
+    /// Совпадение с любым из значений
+    Terms {
+        field: String,
+        values: Vec<Value>,
+    },
+    /// Диапазон по индексированной или `fast_sortable` колонке, хотя бы одна граница обязательна
+    Range {
+        field: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        gt: Option<Value>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        gte: Option<Value>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        lt: Option<Value>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        lte: Option<Value>,
+    },
+    /// Префикс текстовой `equals` колонки
+    Prefix {
+        field: String,
+        value: String,
+    },
+    /// Документы с этим узлом дерева или его потомками
+    Facet {
+        field: String,
+        path: String,
+    },
+    /// Полнотекстовый поиск по `full_text` колонке, текст токенизируется как при индексации
+    Match {
+        field: String,
+        query: String,
+        #[serde(default)]
+        operator: MatchOperator,
+    },
+    /// Значение присутствует (только `fast_sortable` колонки)
+    Exists {
+        field: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchOperator {
+    #[default]
+    Or,
+    And,
+}
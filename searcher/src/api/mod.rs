@@ -1,7 +1,15 @@
+mod aggs;
 mod column;
+mod facet;
 mod field;
+mod filter;
 mod req_res;
+mod sort;
 
+pub use aggs::*;
 pub use column::*;
+pub use facet::*;
 pub use field::*;
+pub use filter::*;
 pub use req_res::*;
+pub use sort::*;
@@ -1,23 +1,82 @@
-use crate::api::{Column, SearchField};
+use crate::api::{
+    Aggregation, AggregationResult, Column, FacetRequest, FacetResult, Filter, InvalidSortValue,
+    OrderBy, SearchField, SortOrder,
+};
+use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SearchRequest {
     pub select: Vec<String>,
     pub from: String,
+    /// Строка запроса для tantivy `QueryParser`; пустая — без ограничений
+    #[serde(default)]
     pub filter: String,
 
+    /// Структурный фильтр, объединяется со строкой `filter` через AND
+    #[serde(default, rename = "where", skip_serializing_if = "Option::is_none")]
+    pub where_filter: Option<Filter>,
+
     #[serde(default)]
     pub sort: Option<String>,
 
+    /// Направление сортировки выражением `sort`
+    #[serde(default)]
+    pub sort_order: SortOrder,
+
+    /// Документы, для которых `sort` не дал числа, см. [`InvalidSortValue`]
+    #[serde(default)]
+    pub sort_invalid: InvalidSortValue,
+
+    /// Сортировка по колонкам без выражения; несовместима с `sort`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub order_by: Vec<OrderBy>,
+
+    /// Определения вида `name(a, b) = <выражение>`, их можно вызывать из `sort`
+    /// и друг из друга
     #[serde(default)]
     pub functions: Vec<String>,
 
+    /// Значения `$name` из `sort` и `functions`, подставляются при компиляции
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub params: HashMap<String, f64>,
+
+    /// Момент для `now_ms()` и относительных дат в агрегациях (RFC 3339);
+    /// по умолчанию текущее время. Нужен для воспроизводимого ранжирования
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub now: Option<String>,
+
+    /// Счётчики по `Tree` колонкам, считаются в том же проходе, что и выдача
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub facets: Vec<FacetRequest>,
+
+    /// Именованные агрегации по `fast_sortable` колонкам, тоже в том же проходе
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub aggs: IndexMap<String, Aggregation>,
+
     #[serde(default)]
     pub offset: usize,
 
     #[serde(default = "default_limit")]
     pub limit: usize,
+
+    /// Посчитать общее число найденных документов (точно, в том же проходе)
+    #[serde(default)]
+    pub track_total: bool,
+
+    /// `cursor` из предыдущего ответа: выдача продолжится сразу после него
+    /// (`offset` отсчитывается уже от курсора)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+
+    /// Искать в снимке point-in-time контекста из `/v1/pit`, а не в текущем
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pit_id: Option<String>,
+
+    /// Opstamp из ответа индексатора: поиск дождётся, пока ридер его увидит
+    #[serde(default)]
+    pub min_opstamp: Option<u64>,
 }
 
 const fn default_limit() -> usize {
@@ -27,6 +86,24 @@ const fn default_limit() -> usize {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SearchResponse {
     pub rows: Vec<Row>,
+
+    /// Общее число найденных документов, если запрошено `track_total`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total: Option<u64>,
+
+    /// Курсор следующей страницы; нет, если страница неполная
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+
+    /// Сколько документов получили NaN или ошибку в выражении `sort`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub invalid_sort_values: Option<u64>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub facets: Vec<FacetResult>,
+
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub aggs: IndexMap<String, AggregationResult>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -34,8 +111,95 @@ pub struct Row {
     pub fields: Vec<SearchField>,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RefreshResponse {
+    pub opstamp: u64,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SearchMatrixResponse {
     pub row_count: u32,
+    /// В порядке `select`; nullable колонки используют `Nullable*` варианты
     pub columns: Vec<Column>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total: Option<u64>,
+
+    /// Курсор следующей страницы; нет, если страница неполная
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+
+    /// Сколько документов получили NaN или ошибку в выражении `sort`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub invalid_sort_values: Option<u64>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub facets: Vec<FacetResult>,
+
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub aggs: IndexMap<String, AggregationResult>,
+}
+
+/// Запрос ручки `/v1/sql`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SqlRequest {
+    /// `SELECT cols FROM index WHERE ... ORDER BY <expr> LIMIT n OFFSET m`
+    pub query: String,
+
+    #[serde(default)]
+    pub min_opstamp: Option<u64>,
+}
+
+/// Запрос ручки `/v1/count`: те же фильтры, что у поиска, но без выборки строк
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CountRequest {
+    pub from: String,
+    #[serde(default)]
+    pub filter: String,
+    #[serde(default, rename = "where", skip_serializing_if = "Option::is_none")]
+    pub where_filter: Option<Filter>,
+    /// Считать в снимке point-in-time контекста, как и страницы поиска по нему
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pit_id: Option<String>,
+    #[serde(default)]
+    pub min_opstamp: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CountResponse {
+    pub count: u64,
+}
+
+/// Запрос ручки `/v1/pit`: закрепить текущий снимок индекса для постраничного обхода
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PitRequest {
+    pub from: String,
+    /// Сколько контекст живёт без обращений; каждый поиск по нему продлевает срок
+    #[serde(default = "default_keep_alive_ms")]
+    pub keep_alive_ms: u64,
+    #[serde(default)]
+    pub min_opstamp: Option<u64>,
+}
+
+const fn default_keep_alive_ms() -> u64 {
+    60_000
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PitResponse {
+    pub pit_id: String,
+    pub keep_alive_ms: u64,
+}
+
+/// Запрос ручки `/v1/pit/close`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ClosePitRequest {
+    pub from: String,
+    pub pit_id: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ClosePitResponse {
+    /// `false`, если контекст уже истёк или не существовал
+    pub closed: bool,
 }
@@ -0,0 +1,44 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+/// Куда ставить документы без значения, независимо от `dir`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MissingOrder {
+    First,
+    #[default]
+    Last,
+}
+
+/// Что делать с документами, для которых выражение `sort` дало NaN (в том числе из-за
+/// пустого значения колонки) или ошибку; `first`/`last` — независимо от `sort_order`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InvalidSortValue {
+    Drop,
+    First,
+    #[default]
+    Last,
+}
+
+/// Ключ сортировки по `fast_sortable` колонке; ключи сравниваются по очереди,
+/// а равные по всем ключам документы — по адресу в индексе
+///
+/// ```json
+/// [{"field": "price"}, {"field": "created_at", "dir": "desc", "missing": "first"}]
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrderBy {
+    pub field: String,
+    #[serde(default)]
+    pub dir: SortOrder,
+    #[serde(default)]
+    pub missing: MissingOrder,
+}
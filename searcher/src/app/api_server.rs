@@ -1,36 +1,82 @@
 use anyhow::{Context, Result};
-use axum::{Router, extract::State, routing::post};
-use corelib::model::{accept::Accept, typed_request::TypedRequest, typed_response::TypedResponse};
-use tower_http::trace::TraceLayer;
+use axum::{
+    Extension, Router,
+    extract::{Path, State},
+    middleware,
+    routing::post,
+};
+use corelib::config::SearcherConfig;
+use corelib::model::{
+    accept::Accept,
+    typed_request::{BodyLimit, TypedRequest},
+    typed_response::TypedResponse,
+};
+use corelib::telemetry::health::{self, Readiness};
+use corelib::telemetry::{metrics, trace};
+use std::time::Duration;
 use tracing::{error, info};
 
-use crate::engine::{response, search};
-use crate::{
-    api,
-    domain::registry::{self, IndexRegistry},
-};
+use crate::domain::index::SearchIndex;
+use crate::engine::search::SearchHits;
+use crate::engine::{query_sql, response, search};
+use crate::{api, domain::registry::IndexRegistry};
 
 /// Запуск HTTP API сервера
-pub async fn run_http_server(port: u16, index_registry_dir: String) -> Result<()> {
-    let index_registry =
-        registry::load_all_indexes(std::path::Path::new(&index_registry_dir)).await?;
+pub async fn run_http_server(config: SearcherConfig) -> Result<()> {
+    let readiness = Readiness::default();
+    let index_registry = IndexRegistry::new(&config);
 
     // let index = SearchIndex::open_from_path(&index_dir)?;
     // let search_index = Arc::new(index);
 
-    let addr = format!("0.0.0.0:{port}");
-    info!(port = %port, addr = %addr, "Starting HTTP server");
+    let addr = config.http.addr();
+    info!(addr = %addr, "Starting HTTP server");
 
-    let app = Router::new()
-        .route("/v1/select", post(handle_search))
-        .with_state(index_registry)
-        .layer(TraceLayer::new_for_http());
+    index_registry.spawn_pit_reaper();
+
+    let app = health::gate_until_ready(router(index_registry.clone()), readiness.clone())
+        .merge(metrics::router())
+        .merge(health::router(readiness.clone()))
+        .layer(Extension(BodyLimit(config.http.max_body_size)))
+        .layer(trace::http_trace_layer());
 
     let listener = tokio::net::TcpListener::bind(&addr).await?;
 
-    axum::serve(listener, app)
-        .await
-        .context("HTTP server exited unexpectedly")
+    let server = async {
+        axum::serve(listener, app)
+            .with_graceful_shutdown(health::shutdown_signal(
+                readiness.clone(),
+                config.http.shutdown_delay(),
+            ))
+            .await
+            .context("HTTP server exited unexpectedly")
+    };
+    // Индексы читаем уже после bind, чтобы /healthz отвечал во время загрузки
+    let loading = async {
+        index_registry.load_all().await?;
+        readiness.set_ready();
+        info!("All indexes loaded");
+        Ok::<_, anyhow::Error>(())
+    };
+    // Загрузка не должна держать процесс, если сервер уже остановился
+    tokio::select! {
+        served = server => served,
+        Err(err) = loading => Err(err),
+    }
+}
+
+/// Ручки поиска без middleware, чтобы их можно было смонтировать в общий сервер
+pub fn router(index_registry: IndexRegistry) -> Router {
+    Router::new()
+        .route("/v1/select", post(handle_search))
+        .route("/v1/select_columns", post(handle_select_columns))
+        .route("/v1/sql", post(handle_sql))
+        .route("/v1/count", post(handle_count))
+        .route("/v1/pit", post(handle_open_pit))
+        .route("/v1/pit/close", post(handle_close_pit))
+        .route("/v1/index/{index_name}/refresh", post(handle_refresh))
+        .route_layer(middleware::from_fn(metrics::track_http))
+        .with_state(index_registry)
 }
 
 /// Обработчик запроса поиска
@@ -40,23 +86,188 @@ pub async fn handle_search(
     TypedRequest(req): TypedRequest<api::SearchRequest>,
 ) -> TypedResponse<api::SearchResponse> {
     let index_name = &req.from;
-    let Some(index) = registry.inner.get(index_name) else {
+    let Some(index) = registry.lookup(index_name) else {
+        return TypedResponse::not_found(format!("Unknown index_name: {}", index_name), accept);
+    };
+
+    search_in_index(
+        &registry,
+        &index,
+        &req,
+        accept,
+        response::build_search_response,
+    )
+    .await
+}
+
+/// Тот же поиск, но ответ по колонкам: один типизированный вектор на выбранную колонку
+pub async fn handle_select_columns(
+    Accept(accept): Accept,
+    State(registry): State<IndexRegistry>,
+    TypedRequest(req): TypedRequest<api::SearchRequest>,
+) -> TypedResponse<api::SearchMatrixResponse> {
+    let index_name = &req.from;
+    let Some(index) = registry.lookup(index_name) else {
+        return TypedResponse::not_found(format!("Unknown index_name: {}", index_name), accept);
+    };
+
+    search_in_index(
+        &registry,
+        &index,
+        &req,
+        accept,
+        response::build_matrix_response,
+    )
+    .await
+}
+
+/// Обработчик SQL-запроса: разбирает его в `SearchRequest` и выполняет как обычный поиск
+pub async fn handle_sql(
+    Accept(accept): Accept,
+    State(registry): State<IndexRegistry>,
+    TypedRequest(req): TypedRequest<api::SqlRequest>,
+) -> TypedResponse<api::SearchResponse> {
+    let sql = match query_sql::parse_sql(&req.query) {
+        Ok(sql) => sql,
+        Err(err) => return TypedResponse::bad_request("invalid_sql", err.to_string(), accept),
+    };
+
+    let index_name = &sql.request.from;
+    let Some(index) = registry.lookup(index_name) else {
+        return TypedResponse::not_found(format!("Unknown index_name: {}", index_name), accept);
+    };
+    if let Err(err) = sql.validate(&index) {
+        return TypedResponse::bad_request("invalid_sql", err.to_string(), accept);
+    }
+
+    let search_req = api::SearchRequest {
+        min_opstamp: req.min_opstamp,
+        ..sql.request
+    };
+    search_in_index(
+        &registry,
+        &index,
+        &search_req,
+        accept,
+        response::build_search_response,
+    )
+    .await
+}
+
+/// Число документов по фильтрам без выборки строк
+pub async fn handle_count(
+    Accept(accept): Accept,
+    State(registry): State<IndexRegistry>,
+    TypedRequest(req): TypedRequest<api::CountRequest>,
+) -> TypedResponse<api::CountResponse> {
+    let index_name = &req.from;
+    let Some(index) = registry.lookup(index_name) else {
         return TypedResponse::not_found(format!("Unknown index_name: {}", index_name), accept);
     };
 
-    let top_docs = match search::execute_search(&index, &req) {
-        Ok(top_docs) => top_docs,
+    if let Some(min_opstamp) = req.min_opstamp
+        && let Err(err) = index
+            .wait_for_opstamp(min_opstamp, registry.config.min_opstamp_timeout())
+            .await
+    {
+        error!(?err, min_opstamp, "Index reader did not catch up");
+        return TypedResponse::timeout("opstamp_timeout", format!("{err}"), accept);
+    }
+
+    match search::execute_count(&index, &req) {
+        Ok(count) => TypedResponse::ok(api::CountResponse { count }, accept),
+        Err(err) => {
+            error!(?err, "Count execution failed");
+            TypedResponse::bad_request(
+                "searching_failed",
+                format!("Count execution failed: {err}"),
+                accept,
+            )
+        }
+    }
+}
+
+/// Открывает point-in-time контекст: дальнейшие поиски с его `pit_id` видят один снимок
+pub async fn handle_open_pit(
+    Accept(accept): Accept,
+    State(registry): State<IndexRegistry>,
+    TypedRequest(req): TypedRequest<api::PitRequest>,
+) -> TypedResponse<api::PitResponse> {
+    let index_name = &req.from;
+    let Some(index) = registry.lookup(index_name) else {
+        return TypedResponse::not_found(format!("Unknown index_name: {}", index_name), accept);
+    };
+
+    if let Some(min_opstamp) = req.min_opstamp
+        && let Err(err) = index
+            .wait_for_opstamp(min_opstamp, registry.config.min_opstamp_timeout())
+            .await
+    {
+        error!(?err, min_opstamp, "Index reader did not catch up");
+        return TypedResponse::timeout("opstamp_timeout", format!("{err}"), accept);
+    }
+
+    match index.open_pit(
+        Duration::from_millis(req.keep_alive_ms),
+        registry.config.max_pit_keep_alive(),
+        registry.config.max_open_pits,
+    ) {
+        Ok(pit_id) => TypedResponse::ok(
+            api::PitResponse {
+                pit_id,
+                keep_alive_ms: req.keep_alive_ms,
+            },
+            accept,
+        ),
+        Err(err) => TypedResponse::bad_request("invalid_pit", err.to_string(), accept),
+    }
+}
+
+/// Закрывает point-in-time контекст, не дожидаясь истечения keep-alive
+pub async fn handle_close_pit(
+    Accept(accept): Accept,
+    State(registry): State<IndexRegistry>,
+    TypedRequest(req): TypedRequest<api::ClosePitRequest>,
+) -> TypedResponse<api::ClosePitResponse> {
+    let index_name = &req.from;
+    let Some(index) = registry.lookup(index_name) else {
+        return TypedResponse::not_found(format!("Unknown index_name: {}", index_name), accept);
+    };
+
+    let closed = index.pits.close(&req.pit_id);
+    TypedResponse::ok(api::ClosePitResponse { closed }, accept)
+}
+
+/// Ожидание opstamp, поиск и сборка ответа через `build_response`
+async fn search_in_index<T>(
+    registry: &IndexRegistry,
+    index: &SearchIndex,
+    req: &api::SearchRequest,
+    accept: Option<String>,
+    build_response: impl FnOnce(&SearchIndex, &SearchHits, &api::SearchRequest) -> Result<T>,
+) -> TypedResponse<T> {
+    if let Some(min_opstamp) = req.min_opstamp
+        && let Err(err) = index
+            .wait_for_opstamp(min_opstamp, registry.config.min_opstamp_timeout())
+            .await
+    {
+        error!(?err, min_opstamp, "Index reader did not catch up");
+        return TypedResponse::timeout("opstamp_timeout", format!("{err}"), accept);
+    }
+
+    let hits = match search::execute_search(index, req) {
+        Ok(hits) => hits,
         Err(err) => {
             error!(?err, "Search execution failed");
             return TypedResponse::bad_request(
                 "searching_failed",
-                "Search execution failed: {err}",
+                format!("Search execution failed: {err}"),
                 accept,
             );
         }
     };
 
-    match response::build_search_response(&index, &top_docs, &req) {
+    match build_response(index, &hits, req) {
         Ok(response) => TypedResponse::ok(response, accept),
         Err(err) => {
             error!(?err, "Failed to build search response");
@@ -64,3 +275,22 @@ pub async fn handle_search(
         }
     }
 }
+
+/// Обработчик ручки POST /v1/index/{index_name}/refresh
+pub async fn handle_refresh(
+    Accept(accept): Accept,
+    State(registry): State<IndexRegistry>,
+    Path(index_name): Path<String>,
+) -> TypedResponse<api::RefreshResponse> {
+    let Some(index) = registry.lookup(&index_name) else {
+        return TypedResponse::not_found(format!("Unknown index_name: {}", index_name), accept);
+    };
+
+    match index.refresh() {
+        Ok(opstamp) => TypedResponse::ok(api::RefreshResponse { opstamp }, accept),
+        Err(err) => {
+            error!(?err, "Failed to refresh index reader");
+            TypedResponse::internal_error(format!("Failed to refresh: {err}"), accept)
+        }
+    }
+}
@@ -46,7 +46,7 @@ pub fn map_owned_value(field_name: &str, value: OwnedValue) -> api::SearchField
 //     Ok(value_enum)
 // }
 
-fn tantivy_datetime_to_iso(dt: tantivy::DateTime) -> String {
+pub(crate) fn tantivy_datetime_to_iso(dt: tantivy::DateTime) -> String {
     let micros = dt.into_timestamp_micros(); // Получаем i64 микросекунды
     let secs = micros / 1_000_000;
     let nanos = (micros % 1_000_000) * 1000; // остаток переводим в наносекунды
@@ -1,8 +1,16 @@
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
 use corelib::api;
 use corelib::model;
 use std::path::Path;
-use tantivy::{Index, IndexReader, ReloadPolicy};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tantivy::{Index, IndexReader, Opstamp, ReloadPolicy, Searcher};
+use tokio::sync::watch;
+
+use crate::domain::pit::PitStore;
+use crate::domain::text_ranks::TextRankCache;
+
+const MIN_OPSTAMP_POLL_INTERVAL: Duration = Duration::from_millis(20);
 
 // use serde_json::Value;
 // use std::fs;
@@ -13,30 +21,144 @@ pub struct SearchIndex {
     pub index: Index,
     pub reader: IndexReader,
     pub schema: model::MetaSchema,
+    /// Opstamp коммита, который ридер гарантированно видит; ожидающие запросы
+    /// подписываются на изменения
+    loaded_opstamp: watch::Sender<Opstamp>,
+    /// Время последнего опроса metas из [`SearchIndex::wait_for_opstamp`]: опрашивает
+    /// один запрос на индекс, не чаще `MIN_OPSTAMP_POLL_INTERVAL`
+    last_poll: Mutex<Option<Instant>>,
+    /// Point-in-time контексты, открытые через `/v1/pit`
+    pub pits: PitStore,
+    /// Ранги строк text-колонок для выражений `sort`
+    pub text_ranks: TextRankCache,
 }
 
 impl SearchIndex {
-    pub fn open_from_path(index_dir: &str) -> Result<Self> {
+    pub fn open_from_path(index_dir: &str, reload_policy: ReloadPolicy) -> Result<Self> {
         let index = Index::open_in_dir(Path::new(index_dir))
             .with_context(|| format!("Failed to open index in {:?}", index_dir))?;
 
         let api_schema =
-            api::MetaSchema::from_json_file(&format!("{}/delta_schema.json", index_dir))?;
+            api::MetaSchema::from_json_file(format!("{}/delta_schema.json", index_dir))?;
         let meta_schema = model::MetaSchema::from_api(&index.schema(), api_schema)?;
 
+        Self::from_index(index, meta_schema, reload_policy)
+    }
+
+    /// Поиск поверх уже открытого индекса (например, общего с индексатором)
+    pub fn from_index(
+        index: Index,
+        schema: model::MetaSchema,
+        reload_policy: ReloadPolicy,
+    ) -> Result<Self> {
+        // metas читаем до создания ридера: ридер увидит как минимум этот коммит
+        let opstamp = index.load_metas()?.opstamp;
         let reader = index
             .reader_builder()
-            .reload_policy(ReloadPolicy::OnCommitWithDelay)
+            .reload_policy(reload_policy)
             .try_into()
             .context("Failed to create IndexReader")?;
 
         Ok(Self {
             index,
             reader,
-            schema: meta_schema,
+            schema,
+            loaded_opstamp: watch::Sender::new(opstamp),
+            last_poll: Mutex::new(None),
+            pits: PitStore::default(),
+            text_ranks: TextRankCache::default(),
         })
     }
 
+    /// Закрепляет текущий снимок ридера как point-in-time контекст
+    pub fn open_pit(
+        &self,
+        keep_alive: Duration,
+        max_keep_alive: Duration,
+        max_open: usize,
+    ) -> Result<String> {
+        if keep_alive.is_zero() || keep_alive > max_keep_alive {
+            bail!(
+                "keep_alive_ms must be between 1 and {}",
+                max_keep_alive.as_millis()
+            );
+        }
+        self.pits.open(self.reader.searcher(), keep_alive, max_open)
+    }
+
+    /// Снимок для поиска: закреплённый `pit_id` или текущий снимок ридера
+    pub fn searcher(&self, pit_id: Option<&str>) -> Result<Searcher> {
+        match pit_id {
+            Some(pit_id) => self.pits.get(pit_id),
+            None => Ok(self.reader.searcher()),
+        }
+    }
+
+    /// Opstamp коммита, который ридер гарантированно видит
+    pub fn loaded_opstamp(&self) -> Opstamp {
+        *self.loaded_opstamp.borrow()
+    }
+
+    /// Принудительно перезагружает ридер, возвращает opstamp видимого коммита
+    pub fn refresh(&self) -> Result<Opstamp> {
+        let opstamp = self.index.load_metas()?.opstamp;
+        self.reload_to(opstamp)
+    }
+
+    fn reload_to(&self, opstamp: Opstamp) -> Result<Opstamp> {
+        self.reader
+            .reload()
+            .context("Failed to reload IndexReader")?;
+        self.loaded_opstamp.send_if_modified(|loaded| {
+            let advanced = opstamp > *loaded;
+            *loaded = (*loaded).max(opstamp);
+            advanced
+        });
+        Ok(self.loaded_opstamp())
+    }
+
+    /// Ждёт, пока ридер не увидит коммит с opstamp >= `min_opstamp`
+    pub async fn wait_for_opstamp(&self, min_opstamp: Opstamp, timeout: Duration) -> Result<()> {
+        let deadline = Instant::now() + timeout;
+        let mut loaded = self.loaded_opstamp.subscribe();
+        loop {
+            if *loaded.borrow_and_update() >= min_opstamp {
+                return Ok(());
+            }
+            self.poll_metas()?;
+            if *loaded.borrow_and_update() >= min_opstamp {
+                return Ok(());
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                bail!(
+                    "Index has not reached opstamp {min_opstamp} within {} ms",
+                    timeout.as_millis()
+                );
+            }
+            // будит перезагрузка ридера другим запросом или коммитом, иначе следующий опрос
+            let tick = MIN_OPSTAMP_POLL_INTERVAL.min(deadline - now);
+            let _ = tokio::time::timeout(tick, loaded.changed()).await;
+        }
+    }
+
+    /// Читает opstamp последнего коммита и перезагружает ридер, только если он вырос.
+    /// Пока metas читает другой запрос или опрос был недавно, ничего не делает
+    fn poll_metas(&self) -> Result<()> {
+        let Ok(mut last_poll) = self.last_poll.try_lock() else {
+            return Ok(());
+        };
+        if last_poll.is_some_and(|at| at.elapsed() < MIN_OPSTAMP_POLL_INTERVAL) {
+            return Ok(());
+        }
+        *last_poll = Some(Instant::now());
+        let opstamp = self.index.load_metas()?.opstamp;
+        if opstamp > self.loaded_opstamp() {
+            self.reload_to(opstamp)?;
+        }
+        Ok(())
+    }
+
     // pub fn open_from_path_to_ram(index_dir: &str) -> Result<Self> {
     //     let index = Self::load_index_into_ram(index_dir)?;
 
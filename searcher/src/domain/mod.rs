@@ -1,3 +1,5 @@
 pub mod document;
 pub mod index;
+pub mod pit;
 pub mod registry;
+pub mod text_ranks;
@@ -0,0 +1,82 @@
+use anyhow::{Result, anyhow, bail};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tantivy::Searcher;
+
+/// Закреплённый `Searcher`: пока контекст жив, сегменты его снимка не удаляются,
+/// и `DocAddress` из курсоров остаются валидными при перезагрузке ридера
+struct PitContext {
+    searcher: Searcher,
+    keep_alive: Duration,
+    expires_at: Instant,
+}
+
+/// Point-in-time контексты одного индекса
+#[derive(Default)]
+pub struct PitStore {
+    contexts: Mutex<HashMap<String, PitContext>>,
+}
+
+impl PitStore {
+    /// Закрепляет `searcher` на `keep_alive` и возвращает id контекста. Каждый контекст
+    /// держит сегменты своего снимка, поэтому живых контекстов не больше `max_open`
+    pub fn open(
+        &self,
+        searcher: Searcher,
+        keep_alive: Duration,
+        max_open: usize,
+    ) -> Result<String> {
+        let now = Instant::now();
+        let mut contexts = self.contexts.lock().unwrap();
+        if contexts.len() >= max_open {
+            contexts.retain(|_, context| context.expires_at > now);
+        }
+        if contexts.len() >= max_open {
+            bail!("Too many open point-in-time contexts (limit {max_open}), close unused ones");
+        }
+
+        let pit_id = format!("{:032x}", rand::random::<u128>());
+        let context = PitContext {
+            searcher,
+            keep_alive,
+            expires_at: now + keep_alive,
+        };
+        contexts.insert(pit_id.clone(), context);
+        Ok(pit_id)
+    }
+
+    /// Снимок контекста; каждое обращение продлевает его ещё на `keep_alive`
+    pub fn get(&self, pit_id: &str) -> Result<Searcher> {
+        let now = Instant::now();
+        let mut contexts = self.contexts.lock().unwrap();
+        match contexts.get_mut(pit_id) {
+            Some(context) if context.expires_at > now => {
+                context.expires_at = now + context.keep_alive;
+                Ok(context.searcher.clone())
+            }
+            _ => Err(anyhow!("Unknown or expired pit_id '{pit_id}'")),
+        }
+    }
+
+    /// `true`, если контекст был открыт
+    pub fn close(&self, pit_id: &str) -> bool {
+        self.contexts.lock().unwrap().remove(pit_id).is_some()
+    }
+
+    /// Удаляет истёкшие контексты, возвращает их число
+    pub fn reap(&self, now: Instant) -> usize {
+        let mut contexts = self.contexts.lock().unwrap();
+        let before = contexts.len();
+        contexts.retain(|_, context| context.expires_at > now);
+        before - contexts.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.contexts.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
@@ -1,46 +1,92 @@
 use anyhow::{Context, Result};
+use corelib::config::SearcherConfig;
 use dashmap::DashMap;
 use std::fs;
-use std::path::Path;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::task::AbortHandle;
 
 use crate::domain::index::SearchIndex;
 
-#[derive(Clone)]
+const PIT_REAP_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Default)]
 pub struct IndexRegistry {
     pub inner: Arc<DashMap<String, Arc<SearchIndex>>>,
     // pub indexes_root: PathBuf,
+    pub config: Arc<SearcherConfig>,
 }
 
-pub async fn load_all_indexes(repo_path: &Path) -> Result<IndexRegistry> {
-    let registry = Arc::new(DashMap::new());
-
-    let entries = fs::read_dir(repo_path).context("Failed to read index repository dir")?;
-
-    for entry in entries {
-        let entry = entry?;
-        if !entry.file_type()?.is_dir() {
-            continue;
+impl IndexRegistry {
+    /// Пустой реестр; индексы с диска читает [`IndexRegistry::load_all`]
+    pub fn new(config: &SearcherConfig) -> Self {
+        Self {
+            inner: Arc::new(DashMap::new()),
+            config: Arc::new(config.clone()),
         }
+    }
 
-        let schema_name = entry.file_name().to_string_lossy().to_string();
-        let schema_path = entry.path();
-        let index_path = schema_path.join("index");
+    pub async fn load_all(&self) -> Result<()> {
+        let repo_path = self.config.index_registry_dir.as_path();
+        let entries = fs::read_dir(repo_path).context("Failed to read index repository dir")?;
 
-        match SearchIndex::open_from_path(index_path.to_str().unwrap()) {
-            // match SearchIndex::open_from_path_to_ram(index_path.to_str().unwrap()) {
-            Ok(search_index) => {
-                registry.insert(schema_name.clone(), Arc::new(search_index));
-                tracing::info!(%schema_name, "Loaded search index");
+        for entry in entries {
+            let entry = entry?;
+            if !entry.file_type()?.is_dir() {
+                continue;
             }
-            Err(e) => {
-                tracing::error!(%schema_name, error = ?e, "Failed to load search index");
+
+            let schema_name = entry.file_name().to_string_lossy().to_string();
+            let schema_path = entry.path();
+            let index_path = schema_path.join("index");
+
+            let reload_policy = self.config.reload_policy(&schema_name).into();
+            match SearchIndex::open_from_path(index_path.to_str().unwrap(), reload_policy) {
+                // match SearchIndex::open_from_path_to_ram(index_path.to_str().unwrap()) {
+                Ok(search_index) => {
+                    self.inner
+                        .insert(schema_name.clone(), Arc::new(search_index));
+                    tracing::info!(%schema_name, "Loaded search index");
+                }
+                Err(e) => {
+                    tracing::error!(%schema_name, error = ?e, "Failed to load search index");
+                }
             }
         }
+        Ok(())
     }
 
-    Ok(IndexRegistry {
-        inner: registry,
-        // indexes_root: repo_path.to_path_buf(),
-    })
+    /// Поиск индекса по имени схемы
+    pub fn lookup(&self, name: &str) -> Option<Arc<SearchIndex>> {
+        let _span = tracing::info_span!("schema_lookup", index = %name).entered();
+        self.inner.get(name).map(|index| index.clone())
+    }
+
+    /// Фоновая задача, закрывающая истёкшие point-in-time контексты всех индексов.
+    /// Реестр она не держит и завершается сама, когда он удалён
+    pub fn spawn_pit_reaper(&self) -> AbortHandle {
+        let inner = Arc::downgrade(&self.inner);
+        let task = tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(PIT_REAP_INTERVAL).await;
+                let Some(inner) = inner.upgrade() else {
+                    break;
+                };
+                let now = Instant::now();
+                for index in inner.iter() {
+                    let reaped = index.pits.reap(now);
+                    if reaped > 0 {
+                        tracing::info!(index = %index.key(), reaped, "Expired PIT contexts closed");
+                    }
+                }
+            }
+        });
+        task.abort_handle()
+    }
+}
+
+pub async fn load_all_indexes(config: &SearcherConfig) -> Result<IndexRegistry> {
+    let registry = IndexRegistry::new(config);
+    registry.load_all().await?;
+    Ok(registry)
 }
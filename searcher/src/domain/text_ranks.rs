@@ -0,0 +1,45 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tantivy::Searcher;
+use tantivy::index::SegmentId;
+
+/// Ранги строк text-колонки для каждого сегмента снимка, по порядку сегментов
+pub type TextRanks = Vec<Arc<[u32]>>;
+
+/// Ранги text-колонок для последнего набора сегментов индекса. Словарь сегмента
+/// не меняется, поэтому ранги пересчитываются только после коммита или слияния
+#[derive(Default)]
+pub struct TextRankCache {
+    columns: Mutex<HashMap<String, (Vec<SegmentId>, TextRanks)>>,
+}
+
+impl TextRankCache {
+    /// Ранги `column` для снимка `searcher`; `compute` вызывается, только если
+    /// сегменты снимка отличаются от тех, для которых ранги уже посчитаны
+    pub fn get_or_compute(
+        &self,
+        searcher: &Searcher,
+        column: &str,
+        compute: impl FnOnce() -> Result<TextRanks>,
+    ) -> Result<TextRanks> {
+        let segments: Vec<SegmentId> = searcher
+            .segment_readers()
+            .iter()
+            .map(|segment| segment.segment_id())
+            .collect();
+        if let Some((cached, ranks)) = self.columns.lock().unwrap().get(column)
+            && *cached == segments
+        {
+            return Ok(ranks.clone());
+        }
+
+        // слияние словарей идёт без блокировки, чтобы не задерживать другие колонки
+        let ranks = compute()?;
+        self.columns
+            .lock()
+            .unwrap()
+            .insert(column.to_string(), (segments, ranks.clone()));
+        Ok(ranks)
+    }
+}
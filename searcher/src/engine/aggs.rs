@@ -0,0 +1,784 @@
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::{Result, anyhow, bail};
+use chrono::{DateTime, Datelike, Months, NaiveDate, Utc};
+use corelib::api::MetaColumnType;
+use corelib::model::{MetaColumn, MetaSchema};
+use indexmap::IndexMap;
+use serde_json::Value;
+use tantivy::collector::{Collector, SegmentCollector};
+use tantivy::columnar::StrColumn;
+use tantivy::{DocId, Score, SegmentOrdinal, SegmentReader};
+
+use crate::api::{self, Aggregation, DateInterval};
+
+use super::hll::HyperLogLog;
+use super::virtual_sort::collector::FieldReader;
+
+/// Больше корзин после заполнения пропусков не отдаём, чтобы узкий `interval` не съел память
+const MAX_BUCKETS: usize = 10_000;
+
+const MINUTE_MS: i64 = 60_000;
+const HOUR_MS: i64 = 60 * MINUTE_MS;
+const DAY_MS: i64 = 24 * HOUR_MS;
+const WEEK_MS: i64 = 7 * DAY_MS;
+
+/// Считает агрегации по всем документам запроса через те же `FieldReader`, что и `virtual_sort`
+pub struct AggsCollector<'a> {
+    aggs: Vec<CompiledAgg<'a>>,
+}
+
+struct CompiledAgg<'a> {
+    name: String,
+    column: &'a MetaColumn,
+    kind: AggKind,
+}
+
+#[derive(Debug, Clone)]
+enum AggKind {
+    Stats,
+    Histogram { interval: f64, offset: f64 },
+    Range(Vec<CompiledRange>),
+    DateHistogram(DateInterval),
+    Terms { size: usize, min_doc_count: u64 },
+    Cardinality,
+}
+
+#[derive(Debug, Clone)]
+struct CompiledRange {
+    key: String,
+    from: Option<f64>,
+    to: Option<f64>,
+}
+
+impl CompiledRange {
+    fn contains(&self, value: f64) -> bool {
+        self.from.is_none_or(|from| value >= from) && self.to.is_none_or(|to| value < to)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum AggState {
+    Stats {
+        count: u64,
+        sum: f64,
+        min: f64,
+        max: f64,
+    },
+    /// Номер корзины гистограммы или начало календарной корзины в миллисекундах
+    Buckets(BucketCounts),
+    Ranges(Vec<u64>),
+    Terms(HashMap<String, u64>),
+    Cardinality(Box<HyperLogLog>),
+    /// Счётчики по ординалам текстовой колонки внутри сегмента, в `harvest` заменяются
+    /// на `Terms`/`Cardinality`: ординалы разных сегментов несравнимы
+    Ords(HashMap<u64, u64>),
+}
+
+/// Счётчики корзин гистограммы. Больше `MAX_BUCKETS` различных ключей не хранится ни в
+/// сегменте, ни после слияния: такая гистограмма всё равно отклоняется в `result`
+#[derive(Debug, Clone, Default)]
+pub struct BucketCounts {
+    counts: BTreeMap<i64, u64>,
+    /// Номер корзины вне диапазона i64 или различных ключей больше `MAX_BUCKETS`
+    overflow: bool,
+}
+
+impl BucketCounts {
+    fn add(&mut self, key: Option<i64>, count: u64) {
+        let Some(key) = key else {
+            self.overflow = true;
+            return;
+        };
+        if let Some(total) = self.counts.get_mut(&key) {
+            *total += count;
+        } else if self.counts.len() >= MAX_BUCKETS {
+            self.overflow = true;
+        } else {
+            self.counts.insert(key, count);
+        }
+    }
+
+    fn merge(&mut self, other: BucketCounts) {
+        self.overflow |= other.overflow;
+        for (key, count) in other.counts {
+            self.add(Some(key), count);
+        }
+    }
+}
+
+/// Номер корзины гистограммы; `None`, если он не помещается в i64
+/// (крайние значения или слишком маленький `interval`)
+fn histogram_key(value: f64, interval: f64, offset: f64) -> Option<i64> {
+    let idx = ((value - offset) / interval).floor();
+    // `as i64` насыщается, поэтому границы проверяются до приведения
+    (idx >= i64::MIN as f64 && idx < i64::MAX as f64).then_some(idx as i64)
+}
+
+impl AggState {
+    fn new(kind: &AggKind) -> Self {
+        match kind {
+            AggKind::Stats => AggState::Stats {
+                count: 0,
+                sum: 0.0,
+                min: f64::INFINITY,
+                max: f64::NEG_INFINITY,
+            },
+            AggKind::Histogram { .. } | AggKind::DateHistogram(_) => {
+                AggState::Buckets(BucketCounts::default())
+            }
+            AggKind::Range(ranges) => AggState::Ranges(vec![0; ranges.len()]),
+            AggKind::Terms { .. } => AggState::Terms(HashMap::new()),
+            AggKind::Cardinality => AggState::Cardinality(Box::default()),
+        }
+    }
+
+    fn add(&mut self, kind: &AggKind, value: f64) {
+        match (self, kind) {
+            (
+                AggState::Stats {
+                    count,
+                    sum,
+                    min,
+                    max,
+                },
+                _,
+            ) => {
+                *count += 1;
+                *sum += value;
+                *min = min.min(value);
+                *max = max.max(value);
+            }
+            (AggState::Buckets(buckets), AggKind::Histogram { interval, offset }) => {
+                buckets.add(histogram_key(value, *interval, *offset), 1);
+            }
+            (AggState::Buckets(buckets), AggKind::DateHistogram(interval)) => {
+                buckets.add(Some(floor_date(value as i64, *interval)), 1);
+            }
+            (AggState::Ranges(counts), AggKind::Range(ranges)) => {
+                for (count, range) in counts.iter_mut().zip(ranges) {
+                    if range.contains(value) {
+                        *count += 1;
+                    }
+                }
+            }
+            (AggState::Cardinality(hll), _) => hll.insert(&value.to_bits()),
+            _ => unreachable!("aggregation state does not match its kind"),
+        }
+    }
+
+    fn merge(&mut self, other: AggState) {
+        match (self, other) {
+            (
+                AggState::Stats {
+                    count,
+                    sum,
+                    min,
+                    max,
+                },
+                AggState::Stats {
+                    count: other_count,
+                    sum: other_sum,
+                    min: other_min,
+                    max: other_max,
+                },
+            ) => {
+                *count += other_count;
+                *sum += other_sum;
+                *min = min.min(other_min);
+                *max = max.max(other_max);
+            }
+            (AggState::Buckets(buckets), AggState::Buckets(other)) => buckets.merge(other),
+            (AggState::Ranges(counts), AggState::Ranges(other)) => {
+                for (count, other) in counts.iter_mut().zip(other) {
+                    *count += other;
+                }
+            }
+            (AggState::Terms(counts), AggState::Terms(other)) => {
+                for (term, count) in other {
+                    *counts.entry(term).or_default() += count;
+                }
+            }
+            (AggState::Cardinality(hll), AggState::Cardinality(other)) => hll.merge(&other),
+            _ => unreachable!("aggregation states of different kinds"),
+        }
+    }
+}
+
+/// Проверяет агрегации по схеме; `now` — точка отсчёта для границ вида `now-1d`
+pub fn compile_aggs<'a>(
+    schema: &'a MetaSchema,
+    aggs: &IndexMap<String, Aggregation>,
+    now: DateTime<Utc>,
+) -> Result<AggsCollector<'a>> {
+    let aggs = aggs
+        .iter()
+        .map(|(name, agg)| {
+            let path = format!("aggs.{name}");
+            let field = match agg {
+                Aggregation::Stats { field }
+                | Aggregation::Histogram { field, .. }
+                | Aggregation::Range { field, .. }
+                | Aggregation::DateHistogram { field, .. }
+                | Aggregation::Terms { field, .. }
+                | Aggregation::Cardinality { field } => field,
+            };
+            let column = fast_column(schema, field, &path)?;
+            let numeric = matches!(
+                column.column_type,
+                MetaColumnType::Double
+                    | MetaColumnType::Long
+                    | MetaColumnType::Ulong
+                    | MetaColumnType::DateTime
+                    | MetaColumnType::Bool
+            );
+            let text = column.column_type == MetaColumnType::Text;
+            let unsupported = || {
+                anyhow!(
+                    "{path}: {} column '{field}' cannot be aggregated",
+                    column.column_type
+                )
+            };
+            match agg {
+                Aggregation::Terms { .. } if !text => bail!(
+                    "{path}: terms requires a text column, '{field}' is {}",
+                    column.column_type
+                ),
+                Aggregation::Terms { .. } => {}
+                Aggregation::Cardinality { .. } if !(numeric || text) => return Err(unsupported()),
+                Aggregation::Cardinality { .. } => {}
+                _ if !numeric => return Err(unsupported()),
+                _ => {}
+            }
+
+            let kind = match agg {
+                Aggregation::Stats { .. } => AggKind::Stats,
+                Aggregation::Histogram {
+                    interval, offset, ..
+                } => {
+                    if !(interval.is_finite() && *interval > 0.0) {
+                        bail!("{path}: interval must be a positive number");
+                    }
+                    AggKind::Histogram {
+                        interval: *interval,
+                        offset: *offset,
+                    }
+                }
+                Aggregation::Range { ranges, .. } => AggKind::Range(
+                    ranges
+                        .iter()
+                        .enumerate()
+                        .map(|(i, range)| {
+                            compile_range(column, range, now, &format!("{path}.ranges[{i}]"))
+                        })
+                        .collect::<Result<_>>()?,
+                ),
+                Aggregation::DateHistogram { interval, .. } => {
+                    if column.column_type != MetaColumnType::DateTime {
+                        bail!(
+                            "{path}: date_histogram requires a date_time column, '{field}' is {}",
+                            column.column_type
+                        );
+                    }
+                    AggKind::DateHistogram(*interval)
+                }
+                Aggregation::Terms {
+                    size,
+                    min_doc_count,
+                    ..
+                } => AggKind::Terms {
+                    size: *size,
+                    min_doc_count: *min_doc_count,
+                },
+                Aggregation::Cardinality { .. } => AggKind::Cardinality,
+            };
+
+            Ok(CompiledAgg {
+                name: name.clone(),
+                column,
+                kind,
+            })
+        })
+        .collect::<Result<_>>()?;
+
+    Ok(AggsCollector { aggs })
+}
+
+fn fast_column<'a>(schema: &'a MetaSchema, field: &str, path: &str) -> Result<&'a MetaColumn> {
+    let column = schema
+        .get_column(field)
+        .map_err(|_| anyhow!("{path}: unknown column '{field}'"))?;
+    if !column.is_sort_range {
+        bail!("{path}: column '{field}' is not fast_sortable");
+    }
+    Ok(column)
+}
+
+fn compile_range(
+    column: &MetaColumn,
+    range: &api::RangeBucket,
+    now: DateTime<Utc>,
+    path: &str,
+) -> Result<CompiledRange> {
+    let bound = |value: &Option<Value>| -> Result<Option<f64>> {
+        value
+            .as_ref()
+            .map(|value| range_bound(column, value, now, path))
+            .transpose()
+    };
+    let label = |value: &Option<Value>| match value {
+        Some(Value::String(s)) => s.clone(),
+        Some(other) => other.to_string(),
+        None => "*".to_string(),
+    };
+
+    Ok(CompiledRange {
+        key: range
+            .key
+            .clone()
+            .unwrap_or_else(|| format!("{}-{}", label(&range.from), label(&range.to))),
+        from: bound(&range.from)?,
+        to: bound(&range.to)?,
+    })
+}
+
+fn range_bound(column: &MetaColumn, value: &Value, now: DateTime<Utc>, path: &str) -> Result<f64> {
+    let invalid = || {
+        anyhow!(
+            "{path}: invalid bound {value} for {} column '{}'",
+            column.column_type,
+            column.name
+        )
+    };
+    match (value, column.column_type) {
+        (Value::Number(n), _) => n.as_f64().ok_or_else(invalid),
+        (Value::String(s), MetaColumnType::DateTime) => {
+            date_bound(s, now).map(|ms| ms as f64).ok_or_else(invalid)
+        }
+        _ => Err(invalid()),
+    }
+}
+
+/// ISO8601, `now` или `now-<n><m|h|d|w>` → миллисекунды
+fn date_bound(s: &str, now: DateTime<Utc>) -> Option<i64> {
+    let now_ms = now.timestamp_millis();
+    let Some(relative) = s.strip_prefix("now") else {
+        return s
+            .parse::<DateTime<Utc>>()
+            .ok()
+            .map(|dt| dt.timestamp_millis());
+    };
+    if relative.is_empty() {
+        return Some(now_ms);
+    }
+
+    let (negative, rest) = match relative.as_bytes()[0] {
+        b'-' => (true, &relative[1..]),
+        b'+' => (false, &relative[1..]),
+        _ => return None,
+    };
+    let unit = match rest.chars().last()? {
+        'm' => MINUTE_MS,
+        'h' => HOUR_MS,
+        'd' => DAY_MS,
+        'w' => WEEK_MS,
+        _ => return None,
+    };
+    let amount: i64 = rest[..rest.len() - 1].parse().ok()?;
+    let offset = amount.checked_mul(unit)?;
+    if negative {
+        now_ms.checked_sub(offset)
+    } else {
+        now_ms.checked_add(offset)
+    }
+}
+
+/// Начало календарной корзины, в которую попадает момент `ms`
+fn floor_date(ms: i64, interval: DateInterval) -> i64 {
+    match interval {
+        DateInterval::Minute => ms.div_euclid(MINUTE_MS) * MINUTE_MS,
+        DateInterval::Hour => ms.div_euclid(HOUR_MS) * HOUR_MS,
+        DateInterval::Day => ms.div_euclid(DAY_MS) * DAY_MS,
+        DateInterval::Week => {
+            // 1970-01-01 — четверг, сдвигаем к понедельнику
+            let day = ms.div_euclid(DAY_MS);
+            (day - (day + 3).rem_euclid(7)) * DAY_MS
+        }
+        DateInterval::Month | DateInterval::Year => {
+            let date = DateTime::from_timestamp_millis(ms)
+                .unwrap_or_default()
+                .date_naive();
+            let month = match interval {
+                DateInterval::Year => 1,
+                _ => date.month(),
+            };
+            start_of_day(NaiveDate::from_ymd_opt(date.year(), month, 1).unwrap_or_default())
+        }
+    }
+}
+
+fn next_date(start: i64, interval: DateInterval) -> i64 {
+    let months = match interval {
+        DateInterval::Minute => return start + MINUTE_MS,
+        DateInterval::Hour => return start + HOUR_MS,
+        DateInterval::Day => return start + DAY_MS,
+        DateInterval::Week => return start + WEEK_MS,
+        DateInterval::Month => 1,
+        DateInterval::Year => 12,
+    };
+    DateTime::from_timestamp_millis(start)
+        .and_then(|dt| dt.date_naive().checked_add_months(Months::new(months)))
+        .map(start_of_day)
+        .unwrap_or(i64::MAX)
+}
+
+fn start_of_day(date: NaiveDate) -> i64 {
+    date.and_hms_opt(0, 0, 0)
+        .unwrap_or_default()
+        .and_utc()
+        .timestamp_millis()
+}
+
+impl CompiledAgg<'_> {
+    fn result(&self, state: AggState) -> tantivy::Result<api::AggregationResult> {
+        let too_many = || {
+            tantivy::TantivyError::InvalidArgument(format!(
+                "aggs.{}: more than {MAX_BUCKETS} buckets, increase the interval",
+                self.name
+            ))
+        };
+
+        Ok(match (state, &self.kind) {
+            (
+                AggState::Stats {
+                    count,
+                    sum,
+                    min,
+                    max,
+                },
+                _,
+            ) => {
+                let non_empty = |value: f64| (count > 0).then_some(value);
+                api::AggregationResult::Stats {
+                    count,
+                    min: non_empty(min),
+                    max: non_empty(max),
+                    avg: non_empty(sum / count as f64),
+                    sum,
+                }
+            }
+            (AggState::Buckets(counts), AggKind::Histogram { interval, offset }) => {
+                if counts.overflow {
+                    return Err(too_many());
+                }
+                let counts = counts.counts;
+                let mut buckets = Vec::new();
+                if let (Some((&first, _)), Some((&last, _))) =
+                    (counts.first_key_value(), counts.last_key_value())
+                {
+                    // пустые корзины между крайними тоже отдаём, слайдеру нужна непрерывная шкала
+                    match last.checked_sub(first) {
+                        Some(span) if span < MAX_BUCKETS as i64 => {}
+                        _ => return Err(too_many()),
+                    }
+                    for idx in first..=last {
+                        let from = offset + idx as f64 * interval;
+                        buckets.push(api::Bucket {
+                            key: Value::from(from),
+                            from: Some(from),
+                            to: Some(from + interval),
+                            doc_count: counts.get(&idx).copied().unwrap_or(0),
+                        });
+                    }
+                }
+                api::AggregationResult::Buckets { buckets }
+            }
+            (AggState::Buckets(counts), AggKind::DateHistogram(interval)) => {
+                if counts.overflow {
+                    return Err(too_many());
+                }
+                let counts = counts.counts;
+                let mut buckets = Vec::new();
+                if let (Some((&first, _)), Some((&last, _))) =
+                    (counts.first_key_value(), counts.last_key_value())
+                {
+                    let mut start = first;
+                    while start <= last {
+                        if buckets.len() >= MAX_BUCKETS {
+                            return Err(too_many());
+                        }
+                        let end = next_date(start, *interval);
+                        buckets.push(api::Bucket {
+                            key: Value::from(
+                                DateTime::from_timestamp_millis(start)
+                                    .unwrap_or_default()
+                                    .to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+                            ),
+                            from: Some(start as f64),
+                            to: Some(end as f64),
+                            doc_count: counts.get(&start).copied().unwrap_or(0),
+                        });
+                        start = end;
+                    }
+                }
+                api::AggregationResult::Buckets { buckets }
+            }
+            (AggState::Ranges(counts), AggKind::Range(ranges)) => api::AggregationResult::Buckets {
+                buckets: ranges
+                    .iter()
+                    .zip(counts)
+                    .map(|(range, doc_count)| api::Bucket {
+                        key: Value::from(range.key.clone()),
+                        from: range.from,
+                        to: range.to,
+                        doc_count,
+                    })
+                    .collect(),
+            },
+            (
+                AggState::Terms(counts),
+                AggKind::Terms {
+                    size,
+                    min_doc_count,
+                },
+            ) => {
+                let mut counts: Vec<_> = counts
+                    .into_iter()
+                    .filter(|(_, count)| count >= min_doc_count)
+                    .collect();
+                counts
+                    .sort_unstable_by(|(a_term, a), (b_term, b)| b.cmp(a).then(a_term.cmp(b_term)));
+                counts.truncate(*size);
+                api::AggregationResult::Buckets {
+                    buckets: counts
+                        .into_iter()
+                        .map(|(term, doc_count)| api::Bucket {
+                            key: Value::from(term),
+                            from: None,
+                            to: None,
+                            doc_count,
+                        })
+                        .collect(),
+                }
+            }
+            (AggState::Cardinality(hll), _) => api::AggregationResult::Cardinality {
+                value: hll.estimate(),
+            },
+            _ => unreachable!("aggregation state does not match its kind"),
+        })
+    }
+}
+
+/// Откуда агрегация читает значения в сегменте
+enum AggSource {
+    Number(FieldReader),
+    /// `None`, если в сегменте у колонки нет ни одного значения
+    Text(Option<StrColumn>),
+}
+
+pub struct AggsSegmentCollector {
+    sources: Vec<(AggSource, AggKind)>,
+    states: Vec<AggState>,
+    ords: Vec<u64>,
+}
+
+impl Collector for AggsCollector<'_> {
+    type Fruit = IndexMap<String, api::AggregationResult>;
+    type Child = AggsSegmentCollector;
+
+    fn requires_scoring(&self) -> bool {
+        false
+    }
+
+    fn for_segment(
+        &self,
+        _segment_ordinal: SegmentOrdinal,
+        segment: &SegmentReader,
+    ) -> tantivy::Result<Self::Child> {
+        let mut sources = Vec::with_capacity(self.aggs.len());
+        let mut states = Vec::with_capacity(self.aggs.len());
+        for agg in &self.aggs {
+            if agg.column.column_type == MetaColumnType::Text {
+                let column = segment.fast_fields().str(&agg.column.name)?;
+                sources.push((AggSource::Text(column), agg.kind.clone()));
+                states.push(AggState::Ords(HashMap::new()));
+            } else {
+                let reader = FieldReader::open(segment, agg.column)?;
+                sources.push((AggSource::Number(reader), agg.kind.clone()));
+                states.push(AggState::new(&agg.kind));
+            }
+        }
+        Ok(AggsSegmentCollector {
+            sources,
+            states,
+            ords: Vec::new(),
+        })
+    }
+
+    fn merge_fruits(&self, segment_fruits: Vec<Vec<AggState>>) -> tantivy::Result<Self::Fruit> {
+        let mut states: Vec<AggState> = self
+            .aggs
+            .iter()
+            .map(|agg| AggState::new(&agg.kind))
+            .collect();
+        for fruit in segment_fruits {
+            for (state, other) in states.iter_mut().zip(fruit) {
+                state.merge(other);
+            }
+        }
+
+        self.aggs
+            .iter()
+            .zip(states)
+            .map(|(agg, state)| Ok((agg.name.clone(), agg.result(state)?)))
+            .collect()
+    }
+}
+
+impl SegmentCollector for AggsSegmentCollector {
+    type Fruit = Vec<AggState>;
+
+    fn collect(&mut self, doc_id: DocId, _score: Score) {
+        for ((source, kind), state) in self.sources.iter().zip(&mut self.states) {
+            match (source, state) {
+                (AggSource::Number(reader), state) => {
+                    if let Some(value) = reader.read_f64(doc_id) {
+                        state.add(kind, value);
+                    }
+                }
+                (AggSource::Text(Some(column)), AggState::Ords(counts)) => {
+                    // повтор значения в одном документе считаем один раз
+                    self.ords.clear();
+                    self.ords.extend(column.term_ords(doc_id));
+                    self.ords.sort_unstable();
+                    self.ords.dedup();
+                    for &ord in &self.ords {
+                        *counts.entry(ord).or_default() += 1;
+                    }
+                }
+                (AggSource::Text(_), _) => {}
+            }
+        }
+    }
+
+    fn harvest(self) -> Vec<AggState> {
+        self.sources
+            .into_iter()
+            .zip(self.states)
+            .map(|((source, kind), state)| {
+                let AggState::Ords(counts) = state else {
+                    return state;
+                };
+                let mut result = AggState::new(&kind);
+                let AggSource::Text(Some(column)) = source else {
+                    return result;
+                };
+
+                let mut term = String::new();
+                for (ord, count) in counts {
+                    if !column.ord_to_str(ord, &mut term).unwrap_or(false) {
+                        continue;
+                    }
+                    match &mut result {
+                        AggState::Terms(terms) => *terms.entry(term.clone()).or_default() += count,
+                        AggState::Cardinality(hll) => hll.insert(term.as_str()),
+                        _ => unreachable!("text columns only feed terms and cardinality"),
+                    }
+                }
+                result
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(s: &str) -> i64 {
+        s.parse::<DateTime<Utc>>().unwrap().timestamp_millis()
+    }
+
+    #[test]
+    fn test_calendar_buckets() {
+        let at = ms("2025-03-19T15:42:10Z");
+        let cases = [
+            (
+                DateInterval::Hour,
+                "2025-03-19T15:00:00Z",
+                "2025-03-19T16:00:00Z",
+            ),
+            (
+                DateInterval::Day,
+                "2025-03-19T00:00:00Z",
+                "2025-03-20T00:00:00Z",
+            ),
+            (
+                DateInterval::Week,
+                "2025-03-17T00:00:00Z",
+                "2025-03-24T00:00:00Z",
+            ),
+            (
+                DateInterval::Month,
+                "2025-03-01T00:00:00Z",
+                "2025-04-01T00:00:00Z",
+            ),
+            (
+                DateInterval::Year,
+                "2025-01-01T00:00:00Z",
+                "2026-01-01T00:00:00Z",
+            ),
+        ];
+
+        for (interval, start, next) in cases {
+            let floor = floor_date(at, interval);
+            assert_eq!(floor, ms(start), "{interval:?}");
+            assert_eq!(next_date(floor, interval), ms(next), "{interval:?}");
+        }
+    }
+
+    #[test]
+    fn test_relative_date_bounds() {
+        let now = "2025-03-19T12:00:00Z".parse().unwrap();
+        assert_eq!(date_bound("now", now), Some(ms("2025-03-19T12:00:00Z")));
+        assert_eq!(date_bound("now-1d", now), Some(ms("2025-03-18T12:00:00Z")));
+        assert_eq!(date_bound("now-2w", now), Some(ms("2025-03-05T12:00:00Z")));
+        assert_eq!(date_bound("now+30m", now), Some(ms("2025-03-19T12:30:00Z")));
+        assert_eq!(
+            date_bound("2025-01-01T00:00:00Z", now),
+            Some(ms("2025-01-01T00:00:00Z"))
+        );
+        assert_eq!(date_bound("now-1y", now), None);
+        assert_eq!(date_bound("now-9999999999999999d", now), None);
+        assert_eq!(date_bound("now+9223372036854775807m", now), None);
+    }
+
+    #[test]
+    fn test_histogram_keys_out_of_range_overflow() {
+        let kind = AggKind::Histogram {
+            interval: 1e-300,
+            offset: 0.0,
+        };
+        let mut state = AggState::new(&kind);
+        state.add(&kind, 1e300);
+        state.add(&kind, -1e300);
+        let AggState::Buckets(buckets) = state else {
+            panic!("histogram state is not Buckets");
+        };
+        assert!(buckets.overflow);
+        assert_eq!(histogram_key(1.0, 1.0, 0.0), Some(1));
+        assert_eq!(histogram_key(-1e300, 1.0, 0.0), None);
+
+        // различных ключей не больше MAX_BUCKETS, в том числе после слияния сегментов
+        let mut left = BucketCounts::default();
+        let mut right = BucketCounts::default();
+        for key in 0..MAX_BUCKETS as i64 {
+            left.add(Some(key), 1);
+            right.add(Some(-key - 1), 1);
+        }
+        assert!(!left.overflow);
+        left.merge(right);
+        assert!(left.overflow);
+        assert_eq!(left.counts.len(), MAX_BUCKETS);
+    }
+}
@@ -0,0 +1,273 @@
+use std::cmp::Ordering;
+
+use anyhow::{Result, anyhow, bail};
+use tantivy::collector::{Collector, SegmentCollector};
+use tantivy::{DocAddress, DocId, Score, SegmentOrdinal, SegmentReader};
+
+const VERSION: u8 = 3;
+/// Версия, порядок, отпечаток, `now`, значение и адрес
+const ENCODED_LEN: usize = 30;
+
+/// Порядок, для которого выдан курсор: курсор одного порядка нельзя применить к другому
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CursorOrder {
+    /// BM25: по убыванию score
+    Score = 0,
+    /// Выражение `sort` с `sort_order: asc`
+    Expr = 1,
+    /// Выражение `sort` с `sort_order: desc`
+    ExprDesc = 2,
+}
+
+/// Позиция последнего документа страницы: значение сортировки и `DocAddress` для
+/// разрешения равенств. Адрес стабилен только в пределах одного ридера
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cursor {
+    pub order: CursorOrder,
+    /// Хеш выражения `sort` и политики `sort_invalid`, для BM25 — 0
+    pub fingerprint: u64,
+    /// `now` первой страницы в миллисекундах: следующие считаются в том же `now`
+    pub now_ms: i64,
+    pub sort_value: f32,
+    pub doc: DocAddress,
+}
+
+impl Cursor {
+    /// Непрозрачная hex-строка для клиента
+    pub fn encode(&self) -> String {
+        let mut bytes = Vec::with_capacity(ENCODED_LEN);
+        bytes.push(VERSION);
+        bytes.push(self.order as u8);
+        bytes.extend(self.fingerprint.to_be_bytes());
+        bytes.extend(self.now_ms.to_be_bytes());
+        bytes.extend(self.sort_value.to_bits().to_be_bytes());
+        bytes.extend(self.doc.segment_ord.to_be_bytes());
+        bytes.extend(self.doc.doc_id.to_be_bytes());
+        bytes.iter().map(|b| format!("{b:02x}")).collect()
+    }
+
+    pub fn decode(cursor: &str) -> Result<Self> {
+        let invalid = || anyhow!("Invalid cursor '{cursor}'");
+        if cursor.len() != 2 * ENCODED_LEN || !cursor.is_ascii() {
+            return Err(invalid());
+        }
+        let bytes = (0..cursor.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&cursor[i..i + 2], 16))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| invalid())?;
+        if bytes[0] != VERSION {
+            return Err(invalid());
+        }
+        let order = match bytes[1] {
+            0 => CursorOrder::Score,
+            1 => CursorOrder::Expr,
+            2 => CursorOrder::ExprDesc,
+            _ => return Err(invalid()),
+        };
+        let u32_at = |i: usize| u32::from_be_bytes(bytes[i..i + 4].try_into().unwrap());
+
+        Ok(Cursor {
+            order,
+            fingerprint: u64::from_be_bytes(bytes[2..10].try_into().unwrap()),
+            now_ms: i64::from_be_bytes(bytes[10..18].try_into().unwrap()),
+            sort_value: f32::from_bits(u32_at(18)),
+            doc: DocAddress::new(u32_at(22), u32_at(26)),
+        })
+    }
+
+    /// Курсор из запроса с проверкой, что он выдан для того же порядка и выражения
+    pub fn parse_for(
+        cursor: Option<&str>,
+        order: CursorOrder,
+        fingerprint: u64,
+    ) -> Result<Option<Self>> {
+        let Some(cursor) = cursor else {
+            return Ok(None);
+        };
+        let cursor = Cursor::decode(cursor)?;
+        if cursor.order != order {
+            bail!("Cursor was issued for a different sort order");
+        }
+        if cursor.fingerprint != fingerprint {
+            bail!("Cursor was issued for a different sort expression");
+        }
+        Ok(Some(cursor))
+    }
+
+    /// Курсор на последний документ полной страницы; у неполной следующей страницы нет
+    pub fn after_page(
+        order: CursorOrder,
+        fingerprint: u64,
+        now_ms: i64,
+        top_docs: &[(Score, DocAddress)],
+        limit: usize,
+    ) -> Option<Self> {
+        if top_docs.len() < limit {
+            return None;
+        }
+        top_docs.last().map(|&(sort_value, doc)| Cursor {
+            order,
+            fingerprint,
+            now_ms,
+            sort_value,
+            doc,
+        })
+    }
+}
+
+/// FNV-1a для отпечатка курсора. В отличие от `DefaultHasher` результат не зависит от
+/// версии Rust, поэтому курсор остаётся валидным после пересборки сервиса. Значения
+/// пишутся явными байтами, а не через `Hash`, у которого формат тоже не зафиксирован
+#[derive(Debug, Clone, Copy)]
+pub struct Fingerprint(u64);
+
+impl Default for Fingerprint {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Fingerprint {
+    pub fn bytes(mut self, bytes: &[u8]) -> Self {
+        for &byte in bytes {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+        self
+    }
+
+    /// Строка с длиной впереди, чтобы `["ab", "c"]` и `["a", "bc"]` различались
+    pub fn str(self, value: &str) -> Self {
+        self.bytes(&(value.len() as u64).to_le_bytes())
+            .bytes(value.as_bytes())
+    }
+
+    pub fn finish(self) -> u64 {
+        self.0
+    }
+}
+
+/// Порядок выдачи BM25: score по убыванию, при равенстве — меньший адрес раньше,
+/// как у `TopDocs`
+fn score_order(a: &(Score, DocAddress), b: &(Score, DocAddress)) -> Ordering {
+    b.0.total_cmp(&a.0).then(a.1.cmp(&b.1))
+}
+
+/// `TopDocs` по BM25, который пропускает всё до курсора включительно, поэтому
+/// в памяти держит только `offset + limit` документов после курсора
+pub struct ScoreAfterCollector {
+    pub limit: usize,
+    pub offset: usize,
+    pub after: Cursor,
+}
+
+pub struct ScoreAfterSegmentCollector {
+    segment_ordinal: SegmentOrdinal,
+    after: (Score, DocAddress),
+    max_docs: usize,
+    docs: Vec<(Score, DocAddress)>,
+}
+
+impl Collector for ScoreAfterCollector {
+    type Fruit = Vec<(Score, DocAddress)>;
+    type Child = ScoreAfterSegmentCollector;
+
+    fn requires_scoring(&self) -> bool {
+        true
+    }
+
+    fn for_segment(
+        &self,
+        segment_ordinal: SegmentOrdinal,
+        _segment: &SegmentReader,
+    ) -> tantivy::Result<Self::Child> {
+        Ok(ScoreAfterSegmentCollector {
+            segment_ordinal,
+            after: (self.after.sort_value, self.after.doc),
+            max_docs: self.offset + self.limit,
+            docs: Vec::new(),
+        })
+    }
+
+    fn merge_fruits(
+        &self,
+        segment_fruits: Vec<Vec<(Score, DocAddress)>>,
+    ) -> tantivy::Result<Self::Fruit> {
+        let mut docs: Vec<_> = segment_fruits.into_iter().flatten().collect();
+        docs.sort_unstable_by(score_order);
+        Ok(docs
+            .into_iter()
+            .skip(self.offset)
+            .take(self.limit)
+            .collect())
+    }
+}
+
+impl ScoreAfterSegmentCollector {
+    fn truncate(&mut self) {
+        if self.docs.len() > self.max_docs {
+            self.docs.select_nth_unstable_by(self.max_docs, score_order);
+            self.docs.truncate(self.max_docs);
+        }
+    }
+}
+
+impl SegmentCollector for ScoreAfterSegmentCollector {
+    type Fruit = Vec<(Score, DocAddress)>;
+
+    fn collect(&mut self, doc_id: DocId, score: Score) {
+        let doc = (score, DocAddress::new(self.segment_ordinal, doc_id));
+        if score_order(&doc, &self.after) != Ordering::Greater {
+            return;
+        }
+        self.docs.push(doc);
+        // отсекаем пачками, чтобы не сортировать на каждый документ
+        if self.docs.len() >= 2 * self.max_docs.max(1) {
+            self.truncate();
+        }
+    }
+
+    fn harvest(mut self) -> Self::Fruit {
+        self.truncate();
+        self.docs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cursor_round_trip() {
+        let cursor = Cursor {
+            order: CursorOrder::Expr,
+            fingerprint: 0xdead_beef_0042,
+            now_ms: 1_742_385_600_000,
+            sort_value: -12.5,
+            doc: DocAddress::new(3, 70_000),
+        };
+        let encoded = cursor.encode();
+        assert_eq!(Cursor::decode(&encoded).unwrap(), cursor);
+
+        assert!(Cursor::parse_for(Some(&encoded), CursorOrder::Expr, 0xdead_beef_0042).is_ok());
+        assert!(Cursor::parse_for(Some(&encoded), CursorOrder::Score, 0xdead_beef_0042).is_err());
+        assert!(Cursor::parse_for(Some(&encoded), CursorOrder::Expr, 7).is_err());
+        assert!(Cursor::decode("zz").is_err());
+        assert!(Cursor::decode(&encoded.replacen("03", "07", 1)).is_err());
+    }
+
+    #[test]
+    fn test_fingerprint_is_fixed() {
+        // значения FNV-1a из спецификации: отпечаток не должен меняться между сборками
+        assert_eq!(Fingerprint::default().finish(), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(
+            Fingerprint::default().bytes(b"a").finish(),
+            0xaf63_dc4c_8601_ec8c
+        );
+        assert_ne!(
+            Fingerprint::default().str("ab").str("c").finish(),
+            Fingerprint::default().str("a").str("bc").finish()
+        );
+    }
+}
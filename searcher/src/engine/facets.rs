@@ -0,0 +1,58 @@
+use anyhow::{Result, anyhow, bail};
+use corelib::api::MetaColumnType;
+use tantivy::collector::{FacetCollector, FacetCounts};
+use tantivy::schema::Facet;
+
+use crate::api;
+use crate::domain::index::SearchIndex;
+
+/// Проверяет запросы фасетов по схеме и готовит по коллектору на каждый
+pub fn facet_collectors(
+    index: &SearchIndex,
+    requests: &[api::FacetRequest],
+) -> Result<Vec<FacetCollector>> {
+    requests
+        .iter()
+        .enumerate()
+        .map(|(i, req)| {
+            let column = index
+                .schema
+                .get_column(&req.field)
+                .map_err(|_| anyhow!("facets[{i}]: unknown column '{}'", req.field))?;
+            if column.column_type != MetaColumnType::Tree {
+                bail!(
+                    "facets[{i}]: facet counts require a tree column, '{}' is {}",
+                    req.field,
+                    column.column_type
+                );
+            }
+            let facet = Facet::from_text(&req.path).map_err(|e| anyhow!("facets[{i}]: {e}"))?;
+
+            let mut collector = FacetCollector::for_field(&req.field);
+            collector.add_facet(facet);
+            Ok(collector)
+        })
+        .collect()
+}
+
+pub fn facet_results(
+    requests: &[api::FacetRequest],
+    counts: Vec<FacetCounts>,
+) -> Vec<api::FacetResult> {
+    requests
+        .iter()
+        .zip(counts)
+        .map(|(req, counts)| api::FacetResult {
+            field: req.field.clone(),
+            path: req.path.clone(),
+            counts: counts
+                .top_k(req.path.as_str(), req.top)
+                .into_iter()
+                .map(|(facet, count)| api::FacetCount {
+                    path: facet.to_path_string(),
+                    count,
+                })
+                .collect(),
+        })
+        .collect()
+}
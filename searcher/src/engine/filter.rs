@@ -0,0 +1,265 @@
+use std::ops::Bound;
+
+use anyhow::{Result, anyhow, bail};
+use corelib::api::MetaColumnType;
+use corelib::model::meta_schema::MetaColumn;
+use serde_json::Value;
+use tantivy::query::{
+    AllQuery, BooleanQuery, EmptyQuery, ExistsQuery, Occur, Query, RangeQuery, RegexQuery,
+    TermQuery, TermSetQuery,
+};
+use tantivy::schema::{Facet, IndexRecordOption, Term};
+use tantivy::tokenizer::TokenStream;
+
+use crate::api::{Filter, MatchOperator};
+use crate::domain::index::SearchIndex;
+
+/// Проверяет фильтр по схеме индекса и собирает из него запрос tantivy.
+/// Ошибка указывает путь до узла, например `where.and[1].range`.
+pub fn compile_filter(index: &SearchIndex, filter: &Filter) -> Result<Box<dyn Query>> {
+    FilterCompiler { index }.compile(filter, "where")
+}
+
+struct FilterCompiler<'a> {
+    index: &'a SearchIndex,
+}
+
+impl FilterCompiler<'_> {
+    fn compile(&self, filter: &Filter, path: &str) -> Result<Box<dyn Query>> {
+        match filter {
+            Filter::And(filters) => self.boolean(filters, Occur::Must, &format!("{path}.and")),
+            Filter::Or(filters) => self.boolean(filters, Occur::Should, &format!("{path}.or")),
+            Filter::Not(inner) => {
+                let inner = self.compile(inner, &format!("{path}.not"))?;
+                Ok(Box::new(BooleanQuery::new(vec![
+                    (Occur::Must, Box::new(AllQuery) as Box<dyn Query>),
+                    (Occur::MustNot, inner),
+                ])))
+            }
+            Filter::Term { field, value } => {
+                let path = format!("{path}.term");
+                let column = self.indexed_column(field, &path)?;
+                let term = term_for_search(column, value, &path)?;
+                Ok(Box::new(TermQuery::new(term, IndexRecordOption::Basic)))
+            }
+            Filter::Terms { field, values } => {
+                let path = format!("{path}.terms");
+                let column = self.indexed_column(field, &path)?;
+                let terms = values
+                    .iter()
+                    .map(|value| term_for_search(column, value, &path))
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(Box::new(TermSetQuery::new(terms)))
+            }
+            Filter::Range {
+                field,
+                gt,
+                gte,
+                lt,
+                lte,
+            } => {
+                let path = format!("{path}.range");
+                let column = self.column(field, &path)?;
+                if !(column.is_eq || column.is_id || column.is_sort_range) {
+                    bail!("{path}: column '{field}' is neither indexed nor fast_sortable");
+                }
+                if matches!(
+                    column.column_type,
+                    MetaColumnType::Tree | MetaColumnType::Bytes
+                ) {
+                    bail!(
+                        "{path}: range is not supported for {} column '{field}'",
+                        column.column_type
+                    );
+                }
+                let lower = bound(column, gt, gte, &path)?;
+                let upper = bound(column, lt, lte, &path)?;
+                if matches!((&lower, &upper), (Bound::Unbounded, Bound::Unbounded)) {
+                    bail!("{path}: at least one of gt, gte, lt, lte is required");
+                }
+                Ok(Box::new(RangeQuery::new(lower, upper)))
+            }
+            Filter::Prefix { field, value } => {
+                let path = format!("{path}.prefix");
+                let column = self.indexed_column(field, &path)?;
+                if column.column_type != MetaColumnType::Text {
+                    bail!(
+                        "{path}: prefix requires a text column, '{field}' is {}",
+                        column.column_type
+                    );
+                }
+                let pattern = format!("{}.*", escape_regex(value));
+                Ok(Box::new(RegexQuery::from_pattern(&pattern, column.idx)?))
+            }
+            Filter::Facet { field, path: facet } => {
+                let path = format!("{path}.facet");
+                let column = self.indexed_column(field, &path)?;
+                if column.column_type != MetaColumnType::Tree {
+                    bail!(
+                        "{path}: facet requires a tree column, '{field}' is {}",
+                        column.column_type
+                    );
+                }
+                let facet = Facet::from_text(facet).map_err(|e| anyhow!("{path}: {e}"))?;
+                Ok(Box::new(TermQuery::new(
+                    Term::from_facet(column.idx, &facet),
+                    IndexRecordOption::Basic,
+                )))
+            }
+            Filter::Match {
+                field,
+                query,
+                operator,
+            } => self.full_text(field, query, *operator, &format!("{path}.match")),
+            Filter::Exists { field } => {
+                let path = format!("{path}.exists");
+                let column = self.column(field, &path)?;
+                if !column.is_sort_range {
+                    bail!("{path}: exists requires a fast_sortable column, '{field}' is not");
+                }
+                Ok(Box::new(ExistsQuery::new(field.clone(), false)))
+            }
+        }
+    }
+
+    fn boolean(&self, filters: &[Filter], occur: Occur, path: &str) -> Result<Box<dyn Query>> {
+        if filters.is_empty() {
+            return Ok(match occur {
+                Occur::Should => Box::new(EmptyQuery),
+                _ => Box::new(AllQuery),
+            });
+        }
+        let clauses = filters
+            .iter()
+            .enumerate()
+            .map(|(i, filter)| Ok((occur, self.compile(filter, &format!("{path}[{i}]"))?)))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Box::new(BooleanQuery::new(clauses)))
+    }
+
+    fn full_text(
+        &self,
+        field: &str,
+        query: &str,
+        operator: MatchOperator,
+        path: &str,
+    ) -> Result<Box<dyn Query>> {
+        let column = self.column(field, path)?;
+        let Some(text_field) = column.full_text_idx else {
+            bail!("{path}: column '{field}' is not full_text");
+        };
+        let mut tokenizer = self.index.index.tokenizer_for_field(text_field)?;
+
+        let mut terms = Vec::new();
+        let mut stream = tokenizer.token_stream(query);
+        while stream.advance() {
+            terms.push(Term::from_field_text(text_field, &stream.token().text));
+        }
+
+        let occur = match operator {
+            MatchOperator::Or => Occur::Should,
+            MatchOperator::And => Occur::Must,
+        };
+        Ok(match terms.len() {
+            0 => Box::new(EmptyQuery),
+            1 => Box::new(TermQuery::new(
+                terms.remove(0),
+                IndexRecordOption::WithFreqs,
+            )),
+            _ => Box::new(BooleanQuery::new(
+                terms
+                    .into_iter()
+                    .map(|term| {
+                        let query: Box<dyn Query> =
+                            Box::new(TermQuery::new(term, IndexRecordOption::WithFreqs));
+                        (occur, query)
+                    })
+                    .collect(),
+            )),
+        })
+    }
+
+    fn column(&self, field: &str, path: &str) -> Result<&MetaColumn> {
+        self.index
+            .schema
+            .get_column(field)
+            .map_err(|_| anyhow!("{path}: unknown column '{field}'"))
+    }
+
+    fn indexed_column(&self, field: &str, path: &str) -> Result<&MetaColumn> {
+        let column = self.column(field, path)?;
+        if !(column.is_eq || column.is_id) {
+            bail!("{path}: column '{field}' is not indexed (needs the equals modifier)");
+        }
+        Ok(column)
+    }
+}
+
+fn bound(
+    column: &MetaColumn,
+    exclusive: &Option<Value>,
+    inclusive: &Option<Value>,
+    path: &str,
+) -> Result<Bound<Term>> {
+    match (exclusive, inclusive) {
+        (Some(_), Some(_)) => bail!("{path}: exclusive and inclusive bounds on the same side"),
+        (Some(value), None) => Ok(Bound::Excluded(term(column, value, path, false)?)),
+        (None, Some(value)) => Ok(Bound::Included(term(column, value, path, false)?)),
+        (None, None) => Ok(Bound::Unbounded),
+    }
+}
+
+fn term_for_search(column: &MetaColumn, value: &Value, path: &str) -> Result<Term> {
+    term(column, value, path, true)
+}
+
+/// JSON-значение → терм колонки. `for_search` обрезает дату до точности инвертированного индекса
+fn term(column: &MetaColumn, value: &Value, path: &str, for_search: bool) -> Result<Term> {
+    let field = column.idx;
+    let mismatch = || {
+        anyhow!(
+            "{path}: value {value} does not match {} column '{}'",
+            column.column_type,
+            column.name
+        )
+    };
+
+    Ok(match column.column_type {
+        MetaColumnType::Bool => Term::from_field_bool(field, value.as_bool().ok_or_else(mismatch)?),
+        MetaColumnType::Long => Term::from_field_i64(field, value.as_i64().ok_or_else(mismatch)?),
+        MetaColumnType::Ulong => Term::from_field_u64(field, value.as_u64().ok_or_else(mismatch)?),
+        MetaColumnType::Double => Term::from_field_f64(field, value.as_f64().ok_or_else(mismatch)?),
+        MetaColumnType::Text => Term::from_field_text(field, value.as_str().ok_or_else(mismatch)?),
+        MetaColumnType::DateTime => {
+            let dt: chrono::DateTime<chrono::Utc> = value
+                .as_str()
+                .and_then(|s| s.parse().ok())
+                .ok_or_else(mismatch)?;
+            let dt = tantivy::DateTime::from_timestamp_micros(dt.timestamp_micros());
+            if for_search {
+                Term::from_field_date_for_search(field, dt)
+            } else {
+                Term::from_field_date(field, dt)
+            }
+        }
+        MetaColumnType::Tree => {
+            let facet = value
+                .as_str()
+                .and_then(|s| Facet::from_text(s).ok())
+                .ok_or_else(mismatch)?;
+            Term::from_facet(field, &facet)
+        }
+        MetaColumnType::Bytes => bail!("{path}: bytes column '{}' cannot be filtered", column.name),
+    })
+}
+
+fn escape_regex(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if "\\.+*?()|[]{}^$#&-~".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
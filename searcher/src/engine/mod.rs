@@ -1,3 +1,10 @@
+pub mod aggs;
+pub mod cursor;
+pub mod facets;
+pub mod filter;
+pub mod hll;
+pub mod order_by;
+pub mod query_sql;
 pub mod response;
 pub mod search;
 pub mod virtual_sort;
@@ -0,0 +1,267 @@
+use anyhow::{Result, anyhow, bail};
+use corelib::api::MetaColumnType;
+use corelib::model::MetaSchema;
+use std::cmp::Ordering;
+use tantivy::collector::{Collector, SegmentCollector};
+use tantivy::columnar::StrColumn;
+use tantivy::fastfield::Column;
+use tantivy::{DateTime, DocAddress, DocId, Score, SegmentOrdinal, SegmentReader};
+
+use crate::api::{self, MissingOrder, SortOrder};
+
+#[derive(Debug, Clone)]
+struct SortKey {
+    column: String,
+    column_type: MetaColumnType,
+    dir: SortOrder,
+    missing: MissingOrder,
+}
+
+/// Значение ключа. Текст внутри сегмента сравнивается по ординалу (порядок ординалов
+/// совпадает с порядком строк), а перед слиянием сегментов заменяется строкой
+#[derive(Debug, Clone, PartialEq)]
+enum KeyValue {
+    I64(i64),
+    U64(u64),
+    F64(f64),
+    Ord(u64),
+    Str(String),
+}
+
+impl KeyValue {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (KeyValue::I64(a), KeyValue::I64(b)) => a.cmp(b),
+            (KeyValue::U64(a), KeyValue::U64(b)) | (KeyValue::Ord(a), KeyValue::Ord(b)) => a.cmp(b),
+            (KeyValue::F64(a), KeyValue::F64(b)) => a.total_cmp(b),
+            (KeyValue::Str(a), KeyValue::Str(b)) => a.cmp(b),
+            // у одной колонки все значения одного вида
+            _ => Ordering::Equal,
+        }
+    }
+}
+
+/// Значения ключей документа в порядке `order_by`
+pub struct KeyedDoc {
+    values: Vec<Option<KeyValue>>,
+    doc: DocAddress,
+}
+
+fn compare(keys: &[SortKey], a: &KeyedDoc, b: &KeyedDoc) -> Ordering {
+    for (key, (x, y)) in keys.iter().zip(a.values.iter().zip(&b.values)) {
+        let ordering = match (x, y) {
+            (None, None) => Ordering::Equal,
+            (None, Some(_)) if key.missing == MissingOrder::First => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (Some(_), None) if key.missing == MissingOrder::First => Ordering::Greater,
+            (Some(_), None) => Ordering::Less,
+            (Some(x), Some(y)) => match key.dir {
+                SortOrder::Asc => x.cmp(y),
+                SortOrder::Desc => y.cmp(x),
+            },
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    a.doc.cmp(&b.doc)
+}
+
+/// Проверяет `order_by` по схеме; ошибки вида `order_by[1]: ...`
+pub fn compile_order_by(
+    schema: &MetaSchema,
+    order_by: &[api::OrderBy],
+    limit: usize,
+    offset: usize,
+) -> Result<OrderByCollector> {
+    let keys = order_by
+        .iter()
+        .enumerate()
+        .map(|(i, order)| {
+            let column = schema
+                .get_column(&order.field)
+                .map_err(|_| anyhow!("order_by[{i}]: unknown column '{}'", order.field))?;
+            if !column.is_sort_range {
+                bail!(
+                    "order_by[{i}]: column '{}' is not fast_sortable",
+                    order.field
+                );
+            }
+            if matches!(
+                column.column_type,
+                MetaColumnType::Bytes | MetaColumnType::Tree
+            ) {
+                bail!(
+                    "order_by[{i}]: column '{}' of type {:?} cannot be sorted",
+                    order.field,
+                    column.column_type
+                );
+            }
+            Ok(SortKey {
+                column: column.name.clone(),
+                column_type: column.column_type,
+                dir: order.dir,
+                missing: order.missing,
+            })
+        })
+        .collect::<Result<_>>()?;
+
+    Ok(OrderByCollector {
+        keys,
+        limit,
+        offset,
+    })
+}
+
+/// Top-N по нескольким fast-колонкам
+pub struct OrderByCollector {
+    keys: Vec<SortKey>,
+    limit: usize,
+    offset: usize,
+}
+
+pub struct OrderBySegmentCollector {
+    keys: Vec<SortKey>,
+    readers: Vec<KeyReader>,
+    segment_ordinal: SegmentOrdinal,
+    max_docs: usize,
+    docs: Vec<KeyedDoc>,
+}
+
+impl Collector for OrderByCollector {
+    /// Score у документов не считается и всегда 0
+    type Fruit = Vec<(Score, DocAddress)>;
+    type Child = OrderBySegmentCollector;
+
+    fn requires_scoring(&self) -> bool {
+        false
+    }
+
+    fn for_segment(
+        &self,
+        segment_ordinal: SegmentOrdinal,
+        segment: &SegmentReader,
+    ) -> tantivy::Result<Self::Child> {
+        let readers = self
+            .keys
+            .iter()
+            .map(|key| KeyReader::open(segment, key))
+            .collect::<tantivy::Result<_>>()?;
+
+        Ok(OrderBySegmentCollector {
+            keys: self.keys.clone(),
+            readers,
+            segment_ordinal,
+            max_docs: self.offset + self.limit,
+            docs: Vec::new(),
+        })
+    }
+
+    fn merge_fruits(&self, segment_fruits: Vec<Vec<KeyedDoc>>) -> tantivy::Result<Self::Fruit> {
+        let mut docs: Vec<KeyedDoc> = segment_fruits.into_iter().flatten().collect();
+        docs.sort_unstable_by(|a, b| compare(&self.keys, a, b));
+        Ok(docs
+            .into_iter()
+            .skip(self.offset)
+            .take(self.limit)
+            .map(|keyed| (0.0, keyed.doc))
+            .collect())
+    }
+}
+
+impl OrderBySegmentCollector {
+    fn truncate(&mut self) {
+        if self.docs.len() > self.max_docs {
+            let keys = &self.keys;
+            self.docs
+                .select_nth_unstable_by(self.max_docs, |a, b| compare(keys, a, b));
+            self.docs.truncate(self.max_docs);
+        }
+    }
+}
+
+impl SegmentCollector for OrderBySegmentCollector {
+    type Fruit = Vec<KeyedDoc>;
+
+    fn collect(&mut self, doc_id: DocId, _score: Score) {
+        let values = self.readers.iter().map(|r| r.read(doc_id)).collect();
+        self.docs.push(KeyedDoc {
+            values,
+            doc: DocAddress::new(self.segment_ordinal, doc_id),
+        });
+        // отсекаем пачками, чтобы не сортировать на каждый документ
+        if self.docs.len() >= 2 * self.max_docs.max(1) {
+            self.truncate();
+        }
+    }
+
+    fn harvest(mut self) -> Self::Fruit {
+        self.truncate();
+        // ординалы разных сегментов несравнимы, наружу отдаём строки
+        for keyed in &mut self.docs {
+            for (value, reader) in keyed.values.iter_mut().zip(&self.readers) {
+                *value = reader.resolve(value.take());
+            }
+        }
+        self.docs
+    }
+}
+
+enum KeyReader {
+    I64(Column<i64>),
+    U64(Column<u64>),
+    F64(Column<f64>),
+    Bool(Column<bool>),
+    Date(Column<DateTime>),
+    /// `None`, если в сегменте у колонки нет ни одного значения
+    Text(Option<StrColumn>),
+}
+
+impl KeyReader {
+    fn open(segment: &SegmentReader, key: &SortKey) -> tantivy::Result<Self> {
+        let fast_fields = segment.fast_fields();
+        let name = key.column.as_str();
+        Ok(match key.column_type {
+            MetaColumnType::Long => KeyReader::I64(fast_fields.i64(name)?),
+            MetaColumnType::Ulong => KeyReader::U64(fast_fields.u64(name)?),
+            MetaColumnType::Double => KeyReader::F64(fast_fields.f64(name)?),
+            MetaColumnType::Bool => KeyReader::Bool(fast_fields.bool(name)?),
+            MetaColumnType::DateTime => KeyReader::Date(fast_fields.date(name)?),
+            MetaColumnType::Text => KeyReader::Text(fast_fields.str(name)?),
+            other => {
+                return Err(tantivy::TantivyError::InvalidArgument(format!(
+                    "Unsupported fast field type in order_by: {other:?}"
+                )));
+            }
+        })
+    }
+
+    fn read(&self, doc_id: DocId) -> Option<KeyValue> {
+        match self {
+            KeyReader::I64(col) => col.first(doc_id).map(KeyValue::I64),
+            KeyReader::U64(col) => col.first(doc_id).map(KeyValue::U64),
+            KeyReader::F64(col) => col.first(doc_id).map(KeyValue::F64),
+            KeyReader::Bool(col) => col.first(doc_id).map(|b| KeyValue::U64(b as u64)),
+            KeyReader::Date(col) => col
+                .first(doc_id)
+                .map(|dt| KeyValue::I64(dt.into_timestamp_nanos())),
+            KeyReader::Text(col) => col
+                .as_ref()
+                .and_then(|col| col.ords().first(doc_id))
+                .map(KeyValue::Ord),
+        }
+    }
+
+    fn resolve(&self, value: Option<KeyValue>) -> Option<KeyValue> {
+        match (self, value) {
+            (KeyReader::Text(Some(col)), Some(KeyValue::Ord(ord))) => {
+                let mut term = String::new();
+                col.ord_to_str(ord, &mut term)
+                    .ok()
+                    .filter(|found| *found)
+                    .map(|_| KeyValue::Str(term))
+            }
+            (_, value) => value,
+        }
+    }
+}
@@ -1,248 +1,783 @@
-// use anyhow::{Result, anyhow};
-// use sqlparser::ast::{
-//     BinaryOperator, Expr, Ident, Query as SqlQuery, SelectItem, Statement, Value,
-// };
-// use sqlparser::dialect::GenericDialect;
-// use sqlparser::parser::Parser;
-// use tantivy::query::{AllQuery, BooleanQuery, Occur, Query, RangeQuery, TermQuery};
-// use tantivy::schema::{Field, IndexRecordOption, Schema, Term};
-
-// pub fn parse_sql_statement(sql: &str) -> Result<Box<SqlQuery>> {
-//     let dialect = GenericDialect {};
-//     let ast = Parser::parse_sql(&dialect, sql)?;
-//     let stmt = ast.into_iter().next().ok_or(anyhow!("Empty SQL"))?;
-
-//     match stmt {
-//         Statement::Query(query_box) => Ok(query_box),
-//         _ => Err(anyhow!("Only SELECT statements are supported")),
-//     }
-// }
-
-// /// Строит Tantivy Query из WHERE-условия SQL-запроса
-// pub fn extract_filter_query(query: &SqlQuery, schema: &Schema) -> Result<Box<dyn Query>> {
-//     let select = query
-//         .body
-//         .as_select()
-//         .ok_or_else(|| anyhow!("Only SELECT statements are supported"))?;
-
-//     match &select.selection {
-//         Some(expr) => expr_to_query(expr, schema),
-//         None => Ok(Box::new(AllQuery)),
-//     }
-// }
-
-// /// Извлекает список полей из SELECT части SQL-запроса
-// pub fn extract_projection(query: &SqlQuery, schema: &Schema) -> Result<Vec<Field>> {
-//     let select = query
-//         .body
-//         .as_select()
-//         .ok_or_else(|| anyhow!("Only SELECT statements are supported"))?;
-
-//     let fields = select
-//         .projection
-//         .iter()
-//         .map(|item| {
-//             // 1) Получили Ident или сразу Err:
-//             let ident = match item {
-//                 SelectItem::UnnamedExpr(Expr::Identifier(ident)) => ident,
-//                 other => return Err(anyhow!("Unsupported projection item: {:?}", other)),
-//             };
-
-//             // 2) Конвертируем Option (schema.get_field) в Result:
-//             let field = schema
-//                 .get_field(&ident.value)
-//                 .map_err(|e| anyhow!(e.to_string()))?;
-
-//             // 3) Проверяем, stored ли оно, или возвращаем Err:
-//             if !schema.get_field_entry(field).is_stored() {
-//                 return Err(anyhow!(
-//                     "Field '{}' is not stored",
-//                     schema.get_field_entry(field).name()
-//                 ));
-//             }
-
-//             // 4) Всё ок → вернули field
-//             Ok(field)
-//         })
-//         .collect::<Result<Vec<_>>>()?;
-
-//     Ok(fields)
-// }
-
-// fn invert_operator(op: &BinaryOperator) -> Result<BinaryOperator> {
-//     use BinaryOperator::*;
-//     Ok(match op {
-//         Gt => Lt,
-//         GtEq => LtEq,
-//         Lt => Gt,
-//         LtEq => GtEq,
-//         Eq => Eq,
-//         NotEq => NotEq,
-//         And => And,
-//         Or => Or,
-//         op => return Err(anyhow!("Unsupported operator: {}", op)),
-//     })
-// }
-
-// fn build_leaf_query(
-//     ident: &Ident,
-//     val: &Value,
-//     op: &BinaryOperator,
-//     schema: &Schema,
-// ) -> Result<Box<dyn Query>> {
-//     let field_name = &ident.value;
-//     let field = schema.get_field(field_name)?;
-
-//     // Сначала определяем тип значения и создаём Term
-//     let (term, _): (Term, &'static str) = match val {
-//         Value::SingleQuotedString(s) => (Term::from_field_text(field, s), "text"),
-//         Value::Boolean(b) => (Term::from_field_bool(field, *b), "bool"),
-//         Value::Number(n, _) => {
-//             if let Ok(parsed) = n.parse::<i64>() {
-//                 (Term::from_field_i64(field, parsed), "i64")
-//             } else if let Ok(parsed) = n.parse::<f64>() {
-//                 (Term::from_field_f64(field, parsed), "f64")
-//             } else {
-//                 return Err(anyhow!("Invalid numeric literal"));
-//             }
-//         }
-//         _ => {
-//             return Err(anyhow!("Unsupported value type"));
-//         }
-//     };
-
-//     // Теперь матчим по оператору
-//     use BinaryOperator::*;
-//     match op {
-//         Eq => Ok(Box::new(TermQuery::new(term, IndexRecordOption::Basic))),
-//         NotEq => Ok(Box::new(BooleanQuery::new(vec![(
-//             Occur::MustNot,
-//             Box::new(TermQuery::new(term, IndexRecordOption::Basic)),
-//         )]))),
-
-//         Gt | GtEq | Lt | LtEq => {
-//             // Используем RangeQuery::new с Bound<Term>
-//             use std::ops::Bound::*;
-//             let (lower, upper) = match op {
-//                 Gt => (Excluded(term), Unbounded),
-//                 GtEq => (Included(term), Unbounded),
-//                 Lt => (Unbounded, Excluded(term)),
-//                 LtEq => (Unbounded, Included(term)),
-//                 _ => unreachable!(),
-//             };
-//             Ok(Box::new(RangeQuery::new(lower, upper)))
-//         }
-
-//         _ => Err(anyhow!(format!("Unsupported operator: {:?}", op))),
-//     }
-// }
-
-// fn expr_to_query(expr: &Expr, schema: &Schema) -> Result<Box<dyn Query>> {
-//     match expr {
-//         // Бинарная операция
-//         Expr::BinaryOp { left, op, right } => {
-//             match (&**left, &**right) {
-//                 // Поле = Значение  => TermQuery
-//                 (Expr::Identifier(ident), Expr::Value(vws)) => {
-//                     build_leaf_query(ident, &vws.value, op, schema)
-//                 }
-
-//                 // Значение = Поле  => тоже TermQuery (переставим местами)
-//                 (Expr::Value(vws), Expr::Identifier(ident)) => {
-//                     let inverted_op = invert_operator(op)
-//                         .map_err(|err| tantivy::TantivyError::InvalidArgument(err.to_string()))?;
-//                     build_leaf_query(ident, &vws.value, &inverted_op, schema)
-//                 }
-
-//                 // Вложенные выражения: (a = 1) AND (b = 2)
-//                 _ => {
-//                     let left_query = expr_to_query(left, schema)?;
-//                     let right_query = expr_to_query(right, schema)?;
-
-//                     let query: Box<dyn Query> = match op {
-//                         BinaryOperator::And => {
-//                             Box::new(BooleanQuery::intersection(vec![left_query, right_query]))
-//                         }
-//                         BinaryOperator::Or => {
-//                             Box::new(BooleanQuery::union(vec![left_query, right_query]))
-//                         }
-//                         _ => {
-//                             return Err(anyhow!("Unsupported binary op: {:?}", op));
-//                         }
-//                     };
-
-//                     Ok(query)
-//                 }
-//             }
-//         }
-
-//         // Просто отдельное поле — ошибка, оно ничего не означает само по себе
-//         Expr::Identifier(_) | Expr::Value(_) => Err(anyhow!("Unpaired identifier or value")),
-
-//         _ => Err(anyhow!("Unsupported expression")),
-//     }
-// }
-
-// #[cfg(test)]
-// mod tests {
-//     use super::*;
-//     use tantivy::schema::{FAST, STRING, Schema};
-
-//     #[test]
-//     fn test_sql_where_to_tantivy_query() -> anyhow::Result<()> {
-//         // 1. Схема с двумя полями
-//         let mut builder = Schema::builder();
-//         builder.add_text_field("brand", STRING);
-//         builder.add_text_field("category", STRING);
-//         let schema = builder.build();
-
-//         // 2. Пример SQL-запроса
-//         let sql = "SELECT brand FROM products WHERE brand = 'Apple' AND category = 'Phones'";
-
-//         // 3. Парсим в Tantivy Query
-//         let sql_query = parse_sql_statement(sql)?;
-//         let query = extract_filter_query(&sql_query, &schema)?;
-
-//         // 4. Проверка, что вернулся Query с 2 условиями
-//         let debug_str = format!("{:#?}", query);
-
-//         assert_eq!(
-//             debug_str.matches("TermQuery").count(),
-//             2,
-//             "Expected 2 TermQuery instances"
-//         );
-//         Ok(())
-//     }
-
-//     #[test]
-//     fn test_sql_comparison_operators() -> anyhow::Result<()> {
-//         // Создаём схему с нужными полями
-//         let mut builder = Schema::builder();
-//         builder.add_text_field("brand", STRING);
-//         builder.add_f64_field("price", FAST);
-//         builder.add_i64_field("rating", FAST);
-//         let schema = builder.build();
-
-//         // SQL-запрос с разными операторами
-//         let sql = "
-//                 SELECT brand, price FROM products
-//                 WHERE brand = 'Apple'
-//                   AND price > 100
-//                   AND price <= 500
-//                   AND rating != 3
-//                   AND rating >= 1
-//                   AND rating < 5
-//             ";
-
-//         // Парсим SQL → Tantivy Query
-//         let sql_query = parse_sql_statement(sql)?;
-//         let query = extract_filter_query(&sql_query, &schema)?;
-
-//         //Проверяем, что это BooleanQuery с 6 условиями
-//         let debug_str = format!("{:#?}", query);
-//         assert_eq!(debug_str.matches("TermQuery").count(), 2);
-//         assert_eq!(debug_str.matches("RangeQuery").count(), 4);
-
-//         Ok(())
-//     }
-// }
+use std::fmt;
+
+use corelib::api::MetaColumnType;
+use serde_json::{Number, Value as JsonValue};
+use sqlparser::ast::{
+    BinaryOperator, Expr, FunctionArg, FunctionArgExpr, FunctionArguments, GroupByExpr, Ident,
+    ObjectName, ObjectNamePart, Query as SqlQuery, Select, SelectItem, SetExpr, Spanned, Statement,
+    TableFactor, UnaryOperator, Value,
+};
+use sqlparser::dialect::GenericDialect;
+use sqlparser::parser::Parser;
+use sqlparser::tokenizer::Span;
+
+use crate::api::{Filter, MatchOperator, SearchRequest, SortOrder};
+use crate::domain::index::SearchIndex;
+
+use super::filter::compile_filter;
+
+/// Ошибка SQL-запроса с местом в исходном тексте, если его удалось определить
+#[derive(Debug)]
+pub struct SqlError {
+    pub message: String,
+    pub span: Option<Span>,
+}
+
+impl SqlError {
+    fn at(span: Span, message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            // у части узлов sqlparser позиция не заполняется
+            span: (span != Span::empty()).then_some(span),
+        }
+    }
+}
+
+impl fmt::Display for SqlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.span {
+            Some(span) => write!(
+                f,
+                "{} at line {}, column {}",
+                self.message, span.start.line, span.start.column
+            ),
+            None => f.write_str(&self.message),
+        }
+    }
+}
+
+impl std::error::Error for SqlError {}
+
+type SqlResult<T> = std::result::Result<T, SqlError>;
+
+/// SQL, переведённый в [`SearchRequest`]: WHERE становится структурным фильтром,
+/// ORDER BY — выражением `virtual_sort`
+#[derive(Debug)]
+pub struct SqlSearch {
+    pub request: SearchRequest,
+    /// Путь узла фильтра (`where.and[0].term`) → место в SQL
+    filter_spans: Vec<(String, Span)>,
+    select_columns: Vec<(String, Span)>,
+    sort_columns: Vec<(String, Span)>,
+}
+
+/// Разбирает `SELECT cols FROM index [WHERE ...] [ORDER BY <expr> [ASC|DESC]] [LIMIT n] [OFFSET m]`
+pub fn parse_sql(sql: &str) -> SqlResult<SqlSearch> {
+    let mut statements = Parser::parse_sql(&GenericDialect {}, sql).map_err(|e| SqlError {
+        message: e.to_string(),
+        span: None,
+    })?;
+    if statements.len() != 1 {
+        return Err(SqlError {
+            message: "Expected exactly one SQL statement".to_string(),
+            span: None,
+        });
+    }
+    match statements.remove(0) {
+        Statement::Query(query) => SqlTranslator::default().translate(&query),
+        other => Err(SqlError::at(
+            other.span(),
+            "Only SELECT statements are supported",
+        )),
+    }
+}
+
+impl SqlSearch {
+    /// Проверяет колонки и фильтр по схеме индекса, ошибки привязываются к месту в SQL
+    pub fn validate(&self, index: &SearchIndex) -> SqlResult<()> {
+        for (name, span) in &self.select_columns {
+            if index.schema.get_column(name).is_err() {
+                return Err(SqlError::at(*span, format!("unknown column '{name}'")));
+            }
+        }
+
+        for (name, span) in &self.sort_columns {
+            let column = index
+                .schema
+                .get_column(name)
+                .map_err(|_| SqlError::at(*span, format!("unknown column '{name}'")))?;
+            let sortable = matches!(
+                column.column_type,
+                MetaColumnType::Double
+                    | MetaColumnType::Long
+                    | MetaColumnType::Ulong
+                    | MetaColumnType::DateTime
+                    | MetaColumnType::Bool
+                    | MetaColumnType::Text
+            );
+            if !(column.is_sort_range && sortable) {
+                return Err(SqlError::at(
+                    *span,
+                    format!(
+                        "column '{name}' cannot be used in ORDER BY, it is not a fast_sortable number, date, bool or text column"
+                    ),
+                ));
+            }
+        }
+
+        if let Some(filter) = &self.request.where_filter {
+            compile_filter(index, filter).map_err(|err| {
+                let message = err.to_string();
+                let located = message.split_once(": ").and_then(|(path, rest)| {
+                    self.filter_spans
+                        .iter()
+                        .find(|(p, _)| p == path)
+                        .map(|(_, span)| SqlError::at(*span, rest))
+                });
+                located.unwrap_or(SqlError {
+                    message,
+                    span: None,
+                })
+            })?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct SqlTranslator {
+    filter_spans: Vec<(String, Span)>,
+    sort_columns: Vec<(String, Span)>,
+}
+
+impl SqlTranslator {
+    fn translate(mut self, query: &SqlQuery) -> SqlResult<SqlSearch> {
+        if let Some(with) = &query.with {
+            return Err(SqlError::at(with.span(), "WITH is not supported"));
+        }
+        let SetExpr::Select(select) = query.body.as_ref() else {
+            return Err(SqlError::at(
+                query.body.span(),
+                "Only a plain SELECT is supported",
+            ));
+        };
+        check_unsupported_clauses(select)?;
+
+        let from = table_name(select)?;
+        let select_columns = projection(select)?;
+
+        let where_filter = match &select.selection {
+            Some(expr) => Some(self.filter(expr, "where")?),
+            None => None,
+        };
+
+        let (sort, sort_order) = match &query.order_by {
+            Some(order_by) => {
+                let (sort, sort_order) = self.order_by(order_by)?;
+                (Some(sort), sort_order)
+            }
+            None => (None, SortOrder::default()),
+        };
+
+        let limit = match &query.limit {
+            Some(expr) => usize_literal(expr, "LIMIT")?,
+            None => 10,
+        };
+        let offset = match &query.offset {
+            Some(offset) => usize_literal(&offset.value, "OFFSET")?,
+            None => 0,
+        };
+
+        Ok(SqlSearch {
+            request: SearchRequest {
+                select: select_columns
+                    .iter()
+                    .map(|(name, _)| name.clone())
+                    .collect(),
+                from,
+                filter: String::new(),
+                where_filter,
+                sort,
+                sort_order,
+                sort_invalid: Default::default(),
+                order_by: Vec::new(),
+                functions: Vec::new(),
+                params: Default::default(),
+                now: None,
+                facets: Vec::new(),
+                aggs: Default::default(),
+                offset,
+                limit,
+                track_total: false,
+                cursor: None,
+                pit_id: None,
+                min_opstamp: None,
+            },
+            filter_spans: self.filter_spans,
+            select_columns,
+            sort_columns: self.sort_columns,
+        })
+    }
+
+    /// Переводит условие WHERE в [`Filter`]; `path` совпадает с путём в ошибках `compile_filter`
+    fn filter(&mut self, expr: &Expr, path: &str) -> SqlResult<Filter> {
+        match expr {
+            Expr::Nested(inner) => self.filter(inner, path),
+
+            Expr::BinaryOp {
+                op: op @ (BinaryOperator::And | BinaryOperator::Or),
+                ..
+            } => {
+                // цепочку `a AND b AND c` собираем в один узел
+                let mut operands = Vec::new();
+                flatten(expr, op, &mut operands);
+                let name = if *op == BinaryOperator::And {
+                    "and"
+                } else {
+                    "or"
+                };
+                let filters = operands
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, operand)| self.filter(operand, &format!("{path}.{name}[{i}]")))
+                    .collect::<SqlResult<Vec<_>>>()?;
+                Ok(match *op {
+                    BinaryOperator::And => Filter::And(filters),
+                    _ => Filter::Or(filters),
+                })
+            }
+
+            Expr::UnaryOp {
+                op: UnaryOperator::Not,
+                expr: inner,
+            } => Ok(Filter::Not(Box::new(
+                self.filter(inner, &format!("{path}.not"))?,
+            ))),
+
+            Expr::BinaryOp { left, op, right } => {
+                let (field, op, value) = match (left.as_ref(), right.as_ref()) {
+                    (Expr::Identifier(ident), value) => (ident, op.clone(), value),
+                    (value, Expr::Identifier(ident)) => (ident, invert_operator(op, expr)?, value),
+                    _ => {
+                        return Err(SqlError::at(
+                            expr.span(),
+                            "Comparison must have a column on one side and a literal on the other",
+                        ));
+                    }
+                };
+                let value = literal(value)?;
+                let field = field.value.clone();
+
+                use BinaryOperator::*;
+                let range = |gt, gte, lt, lte| Filter::Range {
+                    field: field.clone(),
+                    gt,
+                    gte,
+                    lt,
+                    lte,
+                };
+                Ok(match op {
+                    Eq => self.leaf(path, "term", expr, Filter::Term { field, value }),
+                    NotEq => Filter::Not(Box::new(self.leaf(
+                        &format!("{path}.not"),
+                        "term",
+                        expr,
+                        Filter::Term { field, value },
+                    ))),
+                    Gt => self.leaf(path, "range", expr, range(Some(value), None, None, None)),
+                    GtEq => self.leaf(path, "range", expr, range(None, Some(value), None, None)),
+                    Lt => self.leaf(path, "range", expr, range(None, None, Some(value), None)),
+                    LtEq => self.leaf(path, "range", expr, range(None, None, None, Some(value))),
+                    op => {
+                        return Err(SqlError::at(
+                            expr.span(),
+                            format!("Unsupported operator: {op}"),
+                        ));
+                    }
+                })
+            }
+
+            Expr::Between {
+                expr: field,
+                negated,
+                low,
+                high,
+            } => {
+                let field = column_name(field)?;
+                let filter = Filter::Range {
+                    field,
+                    gt: None,
+                    gte: Some(literal(low)?),
+                    lt: None,
+                    lte: Some(literal(high)?),
+                };
+                Ok(self.negatable(path, "range", expr, *negated, filter))
+            }
+
+            Expr::InList {
+                expr: field,
+                list,
+                negated,
+            } => {
+                let filter = Filter::Terms {
+                    field: column_name(field)?,
+                    values: list.iter().map(literal).collect::<SqlResult<_>>()?,
+                };
+                Ok(self.negatable(path, "terms", expr, *negated, filter))
+            }
+
+            Expr::Like {
+                negated,
+                any: false,
+                expr: field,
+                pattern,
+                escape_char: None,
+            } => {
+                let field = column_name(field)?;
+                let pattern = match literal(pattern)? {
+                    JsonValue::String(pattern) => pattern,
+                    _ => {
+                        return Err(SqlError::at(
+                            pattern.span(),
+                            "LIKE pattern must be a string",
+                        ));
+                    }
+                };
+                // поддерживается только префикс: 'abc%'
+                let prefix = pattern
+                    .strip_suffix('%')
+                    .filter(|prefix| !prefix.contains(['%', '_']))
+                    .ok_or_else(|| {
+                        SqlError::at(
+                            expr.span(),
+                            "Only prefix patterns like 'abc%' are supported",
+                        )
+                    })?;
+                let filter = Filter::Prefix {
+                    field,
+                    value: prefix.to_string(),
+                };
+                Ok(self.negatable(path, "prefix", expr, *negated, filter))
+            }
+
+            Expr::IsNotNull(field) => {
+                let filter = Filter::Exists {
+                    field: column_name(field)?,
+                };
+                Ok(self.leaf(path, "exists", expr, filter))
+            }
+            Expr::IsNull(field) => {
+                let filter = Filter::Exists {
+                    field: column_name(field)?,
+                };
+                Ok(self.negatable(path, "exists", expr, true, filter))
+            }
+
+            Expr::Function(function) => {
+                let name = object_name(&function.name).to_lowercase();
+                let args = function_args(&function.args, expr)?;
+                let string_arg = |i: usize| match literal(args[i])? {
+                    JsonValue::String(s) => Ok(s),
+                    _ => Err(SqlError::at(args[i].span(), "Expected a string literal")),
+                };
+                match (name.as_str(), args.len()) {
+                    ("match", 2 | 3) => {
+                        let operator = match args.len() {
+                            2 => MatchOperator::Or,
+                            _ => match string_arg(2)?.to_lowercase().as_str() {
+                                "or" => MatchOperator::Or,
+                                "and" => MatchOperator::And,
+                                _ => {
+                                    return Err(SqlError::at(
+                                        args[2].span(),
+                                        "MATCH operator must be 'and' or 'or'",
+                                    ));
+                                }
+                            },
+                        };
+                        let filter = Filter::Match {
+                            field: column_name(args[0])?,
+                            query: string_arg(1)?,
+                            operator,
+                        };
+                        Ok(self.leaf(path, "match", expr, filter))
+                    }
+                    ("facet", 2) => {
+                        let filter = Filter::Facet {
+                            field: column_name(args[0])?,
+                            path: string_arg(1)?,
+                        };
+                        Ok(self.leaf(path, "facet", expr, filter))
+                    }
+                    _ => Err(SqlError::at(
+                        expr.span(),
+                        format!(
+                            "Unsupported function {name}/{}, expected match(col, 'text' [, 'and']) or facet(col, '/path')",
+                            args.len()
+                        ),
+                    )),
+                }
+            }
+
+            Expr::Identifier(_) | Expr::Value(_) => Err(SqlError::at(
+                expr.span(),
+                "Expected a condition, got a bare column or value",
+            )),
+
+            _ => Err(SqlError::at(expr.span(), "Unsupported WHERE expression")),
+        }
+    }
+
+    fn leaf(&mut self, path: &str, name: &str, expr: &Expr, filter: Filter) -> Filter {
+        self.filter_spans
+            .push((format!("{path}.{name}"), expr.span()));
+        filter
+    }
+
+    fn negatable(
+        &mut self,
+        path: &str,
+        name: &str,
+        expr: &Expr,
+        negated: bool,
+        filter: Filter,
+    ) -> Filter {
+        if negated {
+            Filter::Not(Box::new(self.leaf(
+                &format!("{path}.not"),
+                name,
+                expr,
+                filter,
+            )))
+        } else {
+            self.leaf(path, name, expr, filter)
+        }
+    }
+
+    /// ORDER BY → выражение `virtual_sort` и его `sort_order`
+    fn order_by(&mut self, order_by: &sqlparser::ast::OrderBy) -> SqlResult<(String, SortOrder)> {
+        let sqlparser::ast::OrderByKind::Expressions(exprs) = &order_by.kind else {
+            return Err(SqlError::at(
+                order_by.span(),
+                "ORDER BY ALL is not supported",
+            ));
+        };
+        let [order_expr] = exprs.as_slice() else {
+            return Err(SqlError::at(
+                order_by.span(),
+                "ORDER BY supports exactly one expression",
+            ));
+        };
+        if order_expr.options.nulls_first.is_some() || order_expr.with_fill.is_some() {
+            return Err(SqlError::at(
+                order_expr.span(),
+                "NULLS FIRST/LAST and WITH FILL are not supported",
+            ));
+        }
+
+        let rendered = self.sort_expr(&order_expr.expr, false)?;
+        let sort_order = match order_expr.options.asc {
+            Some(false) => SortOrder::Desc,
+            _ => SortOrder::Asc,
+        };
+        Ok((rendered, sort_order))
+    }
+
+    /// Колонки вне вызовов функций проверяются как fast_sortable в `validate`, аргументы
+    /// функций (`bm25(title)`, `has_facet(category, ...)`) проверяет компилятор выражения
+    fn sort_expr(&mut self, expr: &Expr, in_function: bool) -> SqlResult<String> {
+        Ok(match expr {
+            Expr::Identifier(ident) => {
+                if !in_function {
+                    self.sort_columns.push((ident.value.clone(), ident.span));
+                }
+                ident.value.clone()
+            }
+            Expr::Value(value) => match &value.value {
+                Value::Number(n, _) => n.clone(),
+                _ => {
+                    return Err(SqlError::at(
+                        expr.span(),
+                        "Only numeric literals are allowed in ORDER BY",
+                    ));
+                }
+            },
+            Expr::Nested(inner) => format!("({})", self.sort_expr(inner, in_function)?),
+            Expr::UnaryOp {
+                op: UnaryOperator::Minus,
+                expr: inner,
+            } => format!("-({})", self.sort_expr(inner, in_function)?),
+            Expr::UnaryOp {
+                op: UnaryOperator::Plus,
+                expr: inner,
+            } => self.sort_expr(inner, in_function)?,
+            Expr::BinaryOp { left, op, right } => {
+                let op = match op {
+                    BinaryOperator::Plus => "+",
+                    BinaryOperator::Minus => "-",
+                    BinaryOperator::Multiply => "*",
+                    BinaryOperator::Divide => "/",
+                    op => {
+                        return Err(SqlError::at(
+                            expr.span(),
+                            format!("Unsupported operator in ORDER BY: {op}"),
+                        ));
+                    }
+                };
+                format!(
+                    "({} {op} {})",
+                    self.sort_expr(left, in_function)?,
+                    self.sort_expr(right, in_function)?
+                )
+            }
+            Expr::Function(function) => {
+                let name = object_name(&function.name).to_lowercase();
+                let args = function_args(&function.args, expr)?
+                    .into_iter()
+                    .map(|arg| self.sort_expr(arg, true))
+                    .collect::<SqlResult<Vec<_>>>()?;
+                format!("{name}({})", args.join(", "))
+            }
+            _ => {
+                return Err(SqlError::at(expr.span(), "Unsupported ORDER BY expression"));
+            }
+        })
+    }
+}
+
+fn check_unsupported_clauses(select: &Select) -> SqlResult<()> {
+    let span = select.span();
+    if select.distinct.is_some() {
+        return Err(SqlError::at(span, "DISTINCT is not supported"));
+    }
+    if !matches!(&select.group_by, GroupByExpr::Expressions(exprs, _) if exprs.is_empty()) {
+        return Err(SqlError::at(span, "GROUP BY is not supported"));
+    }
+    if let Some(having) = &select.having {
+        return Err(SqlError::at(having.span(), "HAVING is not supported"));
+    }
+    Ok(())
+}
+
+fn table_name(select: &Select) -> SqlResult<String> {
+    let [table] = select.from.as_slice() else {
+        return Err(SqlError::at(
+            select.span(),
+            "Expected exactly one index in FROM",
+        ));
+    };
+    if let Some(join) = table.joins.first() {
+        return Err(SqlError::at(join.span(), "JOIN is not supported"));
+    }
+    match &table.relation {
+        TableFactor::Table { name, .. } => Ok(object_name(name)),
+        other => Err(SqlError::at(other.span(), "FROM must name an index")),
+    }
+}
+
+fn projection(select: &Select) -> SqlResult<Vec<(String, Span)>> {
+    select
+        .projection
+        .iter()
+        .map(|item| match item {
+            SelectItem::UnnamedExpr(Expr::Identifier(ident)) => {
+                Ok((ident.value.clone(), ident.span))
+            }
+            other => Err(SqlError::at(
+                other.span(),
+                "Only plain column names are supported in SELECT",
+            )),
+        })
+        .collect()
+}
+
+fn flatten<'a>(expr: &'a Expr, chain_op: &BinaryOperator, out: &mut Vec<&'a Expr>) {
+    match expr {
+        Expr::BinaryOp { left, op, right } if op == chain_op => {
+            flatten(left, chain_op, out);
+            flatten(right, chain_op, out);
+        }
+        other => out.push(other),
+    }
+}
+
+/// `5 < price` → `price > 5`
+fn invert_operator(op: &BinaryOperator, expr: &Expr) -> SqlResult<BinaryOperator> {
+    use BinaryOperator::*;
+    Ok(match op {
+        Gt => Lt,
+        GtEq => LtEq,
+        Lt => Gt,
+        LtEq => GtEq,
+        Eq => Eq,
+        NotEq => NotEq,
+        op => {
+            return Err(SqlError::at(
+                expr.span(),
+                format!("Unsupported operator: {op}"),
+            ));
+        }
+    })
+}
+
+fn literal(expr: &Expr) -> SqlResult<JsonValue> {
+    let invalid = || SqlError::at(expr.span(), "Expected a string, number or boolean literal");
+    match expr {
+        Expr::Value(value) => match &value.value {
+            Value::SingleQuotedString(s) => Ok(JsonValue::String(s.clone())),
+            Value::Boolean(b) => Ok(JsonValue::Bool(*b)),
+            Value::Number(n, _) => number(n).ok_or_else(invalid),
+            _ => Err(invalid()),
+        },
+        Expr::UnaryOp {
+            op: UnaryOperator::Minus,
+            expr: inner,
+        } => match inner.as_ref() {
+            Expr::Value(value) => match &value.value {
+                Value::Number(n, _) => number(&format!("-{n}")).ok_or_else(invalid),
+                _ => Err(invalid()),
+            },
+            _ => Err(invalid()),
+        },
+        _ => Err(invalid()),
+    }
+}
+
+/// Целые остаются целыми, чтобы подходить к колонкам `long`/`ulong`
+fn number(n: &str) -> Option<JsonValue> {
+    if let Ok(i) = n.parse::<i64>() {
+        return Some(JsonValue::from(i));
+    }
+    if let Ok(u) = n.parse::<u64>() {
+        return Some(JsonValue::from(u));
+    }
+    n.parse::<f64>()
+        .ok()
+        .and_then(Number::from_f64)
+        .map(JsonValue::Number)
+}
+
+fn usize_literal(expr: &Expr, clause: &str) -> SqlResult<usize> {
+    match expr {
+        Expr::Value(value) => match &value.value {
+            Value::Number(n, _) => n.parse().ok(),
+            _ => None,
+        },
+        _ => None,
+    }
+    .ok_or_else(|| {
+        SqlError::at(
+            expr.span(),
+            format!("{clause} must be a non-negative integer"),
+        )
+    })
+}
+
+fn column_name(expr: &Expr) -> SqlResult<String> {
+    match expr {
+        Expr::Identifier(Ident { value, .. }) => Ok(value.clone()),
+        other => Err(SqlError::at(other.span(), "Expected a column name")),
+    }
+}
+
+fn object_name(name: &ObjectName) -> String {
+    name.0
+        .iter()
+        .map(|part| match part {
+            ObjectNamePart::Identifier(ident) => ident.value.as_str(),
+        })
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+fn function_args<'a>(args: &'a FunctionArguments, expr: &Expr) -> SqlResult<Vec<&'a Expr>> {
+    let FunctionArguments::List(list) = args else {
+        return Err(SqlError::at(
+            expr.span(),
+            "Expected a function argument list",
+        ));
+    };
+    list.args
+        .iter()
+        .map(|arg| match arg {
+            FunctionArg::Unnamed(FunctionArgExpr::Expr(arg)) => Ok(arg),
+            other => Err(SqlError::at(
+                other.span(),
+                "Only positional arguments are supported",
+            )),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_sql_where_to_filter() -> anyhow::Result<()> {
+        let sql = "SELECT id, brand FROM products \
+                   WHERE brand = 'Apple' AND (price > 100 OR 500 >= price) AND rating != 3 \
+                   ORDER BY price * 2 DESC LIMIT 5 OFFSET 10";
+        let search = parse_sql(sql)?;
+        let req = &search.request;
+
+        assert_eq!(req.select, vec!["id", "brand"]);
+        assert_eq!(req.from, "products");
+        assert_eq!(req.limit, 5);
+        assert_eq!(req.offset, 10);
+        assert_eq!(req.sort.as_deref(), Some("(price * 2)"));
+        assert_eq!(req.sort_order, SortOrder::Desc);
+        assert_eq!(
+            serde_json::to_value(&req.where_filter)?,
+            json!({"and": [
+                {"term": {"field": "brand", "value": "Apple"}},
+                {"or": [
+                    {"range": {"field": "price", "gt": 100}},
+                    {"range": {"field": "price", "lte": 500}}
+                ]},
+                {"not": {"term": {"field": "rating", "value": 3}}}
+            ]})
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_sql_predicates() -> anyhow::Result<()> {
+        let sql = "SELECT id FROM products WHERE id IN ('1', '2') AND title LIKE 'mac%' \
+                   AND price IS NOT NULL AND price BETWEEN 1 AND 2.5 \
+                   AND match(title, 'pro', 'and') AND facet(category, '/laptops')";
+        let search = parse_sql(sql)?;
+
+        assert_eq!(
+            serde_json::to_value(&search.request.where_filter)?,
+            json!({"and": [
+                {"terms": {"field": "id", "values": ["1", "2"]}},
+                {"prefix": {"field": "title", "value": "mac"}},
+                {"exists": {"field": "price"}},
+                {"range": {"field": "price", "gte": 1, "lte": 2.5}},
+                {"match": {"field": "title", "query": "pro", "operator": "and"}},
+                {"facet": {"field": "category", "path": "/laptops"}}
+            ]})
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_sql_errors_point_at_span() {
+        let cases = [
+            (
+                "UPDATE products SET a = 1",
+                "Only SELECT statements are supported",
+            ),
+            (
+                "SELECT id FROM products WHERE title LIKE '%mac'",
+                "Only prefix patterns like 'abc%' are supported at line 1, column 31",
+            ),
+            (
+                "SELECT id FROM products\nWHERE price > 1 AND brand % 2",
+                "Unsupported operator: % at line 2, column 21",
+            ),
+            (
+                "SELECT id FROM products ORDER BY price LIMIT 'ten'",
+                "LIMIT must be a non-negative integer at line 1, column 46",
+            ),
+        ];
+
+        for (sql, expected) in cases {
+            let err = parse_sql(sql).unwrap_err();
+            assert!(err.to_string().starts_with(expected), "{sql}: {err}");
+        }
+    }
+}
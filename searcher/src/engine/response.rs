@@ -1,39 +1,38 @@
-use anyhow::{Result, anyhow};
+use anyhow::{Result, anyhow, bail};
 use corelib::api::MetaColumnType;
+use corelib::model::MetaColumn;
+use corelib::telemetry::metrics;
 use indexmap::IndexSet;
 use std::collections::HashMap;
-use tantivy::{
-    DocAddress, Score,
-    schema::{Field, OwnedValue},
-};
+use std::time::Instant;
+use tantivy::schema::{Field, OwnedValue};
+use tantivy::{DocAddress, Score};
 use tonic::Status;
 
 use crate::api::SearchValue::*;
-use crate::api::{self, SearchField};
-use crate::domain::document::map_owned_value;
+use crate::api::{self, ColumnValues, SearchField};
+use crate::domain::document::{map_owned_value, tantivy_datetime_to_iso};
 use crate::domain::index::SearchIndex;
 
+use super::search::{Ranking, SearchHits};
+
 // use super::virtual_sort::program::Program;
 
+#[tracing::instrument(name = "response_building", skip_all, fields(index = %index.schema.name, docs = hits.top_docs.len()))]
 pub fn build_search_response(
     index: &SearchIndex,
-    top_docs: &[(Score, DocAddress)],
+    hits: &SearchHits,
     req: &api::SearchRequest,
 ) -> Result<api::SearchResponse> {
-    let searcher = index.reader.searcher();
+    let top_docs = &hits.top_docs;
+    let started = Instant::now();
+    let searcher = &hits.searcher;
     let schema = &index.schema;
 
-    let mut field_set = IndexSet::new();
-    for field in &req.select {
-        if field == "*" {
-            field_set.extend(index.schema.icol_by_name.keys());
-        } else {
-            field_set.insert(field);
-        }
-    }
+    let field_set = selected_fields(index, req);
     let mut rows = Vec::with_capacity(top_docs.len());
 
-    for &(_, addr) in top_docs {
+    for (row, &(score, addr)) in top_docs.iter().enumerate() {
         let mut fields = Vec::with_capacity(field_set.len());
 
         let doc: HashMap<Field, OwnedValue> = searcher
@@ -41,6 +40,13 @@ pub fn build_search_response(
             .map_err(|e| Status::internal(format!("Failed to retrieve document: {e}")))?;
 
         for &field_name in &field_set {
+            if let Some(pseudo) = PseudoColumn::lookup(index, field_name) {
+                fields.push(SearchField {
+                    name: field_name.to_string(),
+                    value: pseudo.value(hits, row, score, addr),
+                });
+                continue;
+            }
             let field = schema.get_column(field_name).map(|col| col.idx)?;
             //.map_err(|e| {
             // Status::invalid_argument(format!("Invalid field name '{}': {}", field_name, e))
@@ -86,7 +92,18 @@ pub fn build_search_response(
         // }
         rows.push(api::Row { fields });
     }
-    Ok(api::SearchResponse { rows })
+
+    metrics::SEARCH_RESPONSE_DURATION
+        .with_label_values(&[schema.name.as_str()])
+        .observe(started.elapsed().as_secs_f64());
+    Ok(api::SearchResponse {
+        rows,
+        total: hits.total,
+        cursor: hits.cursor.clone(),
+        invalid_sort_values: hits.invalid_sort_values,
+        facets: hits.facets.clone(),
+        aggs: hits.aggs.clone(),
+    })
 }
 
 // fn build_context(
@@ -115,177 +132,267 @@ pub fn build_search_response(
 //         .collect()
 // }
 
-// pub fn build_matrix_response(
-//     index: &SearchIndex,
-//     top_docs: &[(Score, DocAddress)],
-// ) -> Result<SearchMatrixResponse, Status> {
-//     let searcher = index.reader.searcher();
-//     let schema = index.index.schema();
+/// Колоночный ответ: по типизированному вектору на каждую колонку из `select`
+#[tracing::instrument(name = "response_building", skip_all, fields(index = %index.schema.name, docs = hits.top_docs.len()))]
+pub fn build_matrix_response(
+    index: &SearchIndex,
+    hits: &SearchHits,
+    req: &api::SearchRequest,
+) -> Result<api::SearchMatrixResponse> {
+    let started = Instant::now();
+    let searcher = &hits.searcher;
+    let schema = &index.schema;
+    let row_count = hits.top_docs.len();
 
-//     let mut matrix: HashMap<u32, Vec<OwnedValue>> = HashMap::with_capacity(schema.num_fields());
-//     let init_vec = || Vec::with_capacity(top_docs.len());
-//     for &(_, addr) in top_docs {
-//         let doc: HashMap<Field, OwnedValue> = searcher
-//             .doc(addr)
-//             .map_err(|e| Status::internal(format!("Failed to retrieve doc: {e}")))?;
+    let mut columns = selected_fields(index, req)
+        .into_iter()
+        .map(|name| {
+            if let Some(pseudo) = PseudoColumn::lookup(index, name) {
+                return Ok((
+                    name,
+                    Selected::Pseudo(pseudo),
+                    pseudo.empty_values(row_count),
+                ));
+            }
+            let column = schema.get_column(name)?;
+            Ok((
+                name,
+                Selected::Column(column),
+                empty_column_values(column, row_count),
+            ))
+        })
+        .collect::<Result<Vec<_>>>()?;
 
-//         for (field, value) in doc {
-//             matrix
-//                 .entry(field.field_id())
-//                 .or_insert_with(init_vec)
-//                 .push(value);
-//         }
-//     }
+    for (row, &(score, addr)) in hits.top_docs.iter().enumerate() {
+        let doc: HashMap<Field, OwnedValue> = searcher
+            .doc(addr)
+            .map_err(|e| anyhow!("Failed to retrieve document: {e}"))?;
 
-//     let row_count = top_docs.len() as u32;
+        for (_, source, values) in &mut columns {
+            match source {
+                Selected::Column(column) => {
+                    push_column_value(values, column, doc.get(&column.idx))?
+                }
+                Selected::Pseudo(pseudo) => pseudo.push_value(values, hits, row, score, addr),
+            }
+        }
+    }
 
-//     let columns = matrix
-//         .into_iter()
-//         .map(|(id, values)| {
-//             let field = Field::from_field_id(id);
-//             let name = schema.get_field_name(field).to_string();
-//             values_to_column(name, values, &schema).expect("must be valid")
-//         })
-//         .collect();
+    metrics::SEARCH_RESPONSE_DURATION
+        .with_label_values(&[schema.name.as_str()])
+        .observe(started.elapsed().as_secs_f64());
+    Ok(api::SearchMatrixResponse {
+        row_count: row_count as u32,
+        columns: columns
+            .into_iter()
+            .map(|(name, _, values)| api::Column {
+                name: name.clone(),
+                values,
+            })
+            .collect(),
+        total: hits.total,
+        cursor: hits.cursor.clone(),
+        invalid_sort_values: hits.invalid_sort_values,
+        facets: hits.facets.clone(),
+        aggs: hits.aggs.clone(),
+    })
+}
 
-//     Ok(SearchMatrixResponse { row_count, columns })
-// }
+/// Источник значений колонки колоночного ответа
+enum Selected<'a> {
+    Column(&'a MetaColumn),
+    Pseudo(PseudoColumn),
+}
 
-// pub fn values_to_column(
-//     name: String,
-//     values: Vec<OwnedValue>,
-//     schema: &Schema,
-// ) -> Result<ColumnVector, Status> {
-//     let field = schema
-//         .get_field(&name)
-//         .map_err(|_| Status::invalid_argument(format!("Field not in schema: {name}")))?;
+/// Служебные колонки, которые можно запросить в `select` наравне с колонками схемы.
+/// Колонка схемы с тем же именем важнее
+#[derive(Debug, Clone, Copy)]
+enum PseudoColumn {
+    /// BM25 score; для `order_by` не считается
+    Score,
+    /// Значение выражения `sort` или BM25 score; null для `order_by`
+    Sort,
+    /// Порядковый номер сегмента и документа в нём, для отладки
+    Segment,
+    Doc,
+}
 
-//     let field_type = schema.get_field_entry(field).field_type();
+impl PseudoColumn {
+    fn lookup(index: &SearchIndex, name: &str) -> Option<Self> {
+        if index.schema.get_column(name).is_ok() {
+            return None;
+        }
+        match name {
+            "_score" => Some(PseudoColumn::Score),
+            "_sort" => Some(PseudoColumn::Sort),
+            "_segment" => Some(PseudoColumn::Segment),
+            "_doc" => Some(PseudoColumn::Doc),
+            _ => None,
+        }
+    }
 
-//     let column = match field_type {
-//         FieldType::Bool(_) => {
-//             let values = values
-//                 .into_iter()
-//                 .map(|v| OptionalBool {
-//                     value: match v {
-//                         OwnedValue::Bool(b) => Some(b),
-//                         _ => None,
-//                     },
-//                 })
-//                 .collect();
-//             ColumnVector {
-//                 name,
-//                 values: Some(Values::Bools(BoolColumn { values })),
-//             }
-//         }
-//         FieldType::U64(_) => {
-//             let values = values
-//                 .into_iter()
-//                 .map(|v| OptionalUInt64 {
-//                     value: match v {
-//                         OwnedValue::U64(u) => Some(u),
-//                         _ => None,
-//                     },
-//                 })
-//                 .collect();
-//             ColumnVector {
-//                 name,
-//                 values: Some(Values::Ulongs(UInt64Column { values })),
-//             }
-//         }
-//         FieldType::I64(_) => {
-//             let values = values
-//                 .into_iter()
-//                 .map(|v| OptionalInt64 {
-//                     value: match v {
-//                         OwnedValue::I64(i) => Some(i),
-//                         _ => None,
-//                     },
-//                 })
-//                 .collect();
-//             ColumnVector {
-//                 name,
-//                 values: Some(Values::Longs(Int64Column { values })),
-//             }
-//         }
-//         FieldType::F64(_) => {
-//             let values = values
-//                 .into_iter()
-//                 .map(|v| OptionalDouble {
-//                     value: match v {
-//                         OwnedValue::F64(f) => Some(f),
-//                         _ => None,
-//                     },
-//                 })
-//                 .collect();
-//             ColumnVector {
-//                 name,
-//                 values: Some(Values::Doubles(DoubleColumn { values })),
-//             }
-//         }
-//         FieldType::Str(_) => {
-//             let values = values
-//                 .into_iter()
-//                 .map(|v| OptionalString {
-//                     value: match v {
-//                         OwnedValue::Str(s) => Some(s),
-//                         _ => None,
-//                     },
-//                 })
-//                 .collect();
-//             ColumnVector {
-//                 name,
-//                 values: Some(Values::Strings(StringColumn { values })),
-//             }
-//         }
-//         FieldType::Bytes(_) => {
-//             let values = values
-//                 .into_iter()
-//                 .map(|v| OptionalBytes {
-//                     value: match v {
-//                         OwnedValue::Bytes(b) => Some(b),
-//                         _ => None,
-//                     },
-//                 })
-//                 .collect();
-//             ColumnVector {
-//                 name,
-//                 values: Some(Values::Bytes(BytesColumn { values })),
-//             }
-//         }
-//         FieldType::Facet(_) => {
-//             let values = values
-//                 .into_iter()
-//                 .map(|v| OptionalString {
-//                     value: match v {
-//                         OwnedValue::Facet(f) => Some(f.to_path_string()),
-//                         _ => None,
-//                     },
-//                 })
-//                 .collect();
-//             ColumnVector {
-//                 name,
-//                 values: Some(Values::Facets(FacetColumn { values })),
-//             }
-//         }
-//         FieldType::Date(_) => {
-//             let values = values
-//                 .into_iter()
-//                 .map(|v| OptionalTimestampMs {
-//                     value: match v {
-//                         OwnedValue::Date(dt) => Some(dt.into_timestamp_millis()),
-//                         _ => None,
-//                     },
-//                 })
-//                 .collect();
-//             ColumnVector {
-//                 name,
-//                 values: Some(Values::Timestamps(TimestampColumn { values })),
-//             }
-//         }
-//         FieldType::JsonObject(_) => todo!(),
-//         FieldType::IpAddr(_) => todo!(),
-//     };
+    fn score(self, hits: &SearchHits, row: usize, score: Score) -> Option<f64> {
+        let score = match (self, hits.ranking) {
+            (PseudoColumn::Score, _) => hits.bm25(row)?,
+            (PseudoColumn::Sort, Ranking::Relevance | Ranking::Expr) => score,
+            _ => return None,
+        };
+        // NaN — выражение не дало значения
+        (!score.is_nan()).then_some(score as f64)
+    }
 
-//     Ok(column)
-// }
+    fn value(
+        self,
+        hits: &SearchHits,
+        row: usize,
+        score: Score,
+        addr: DocAddress,
+    ) -> api::SearchValue {
+        match self {
+            PseudoColumn::Score | PseudoColumn::Sort => {
+                NullableDouble(self.score(hits, row, score))
+            }
+            PseudoColumn::Segment => Ulong(addr.segment_ord as u64),
+            PseudoColumn::Doc => Ulong(addr.doc_id as u64),
+        }
+    }
+
+    fn empty_values(self, capacity: usize) -> ColumnValues {
+        match self {
+            PseudoColumn::Score | PseudoColumn::Sort => {
+                ColumnValues::NullableDouble(Vec::with_capacity(capacity))
+            }
+            PseudoColumn::Segment | PseudoColumn::Doc => {
+                ColumnValues::UInt64(Vec::with_capacity(capacity))
+            }
+        }
+    }
+
+    fn push_value(
+        self,
+        values: &mut ColumnValues,
+        hits: &SearchHits,
+        row: usize,
+        score: Score,
+        addr: DocAddress,
+    ) {
+        match (values, self.value(hits, row, score, addr)) {
+            (ColumnValues::NullableDouble(v), NullableDouble(value)) => v.push(value),
+            (ColumnValues::UInt64(v), Ulong(value)) => v.push(value),
+            _ => unreachable!("pseudo column values are created by empty_values"),
+        }
+    }
+}
+
+/// Колонки из `select` без повторов, `*` раскрывается во все колонки схемы
+fn selected_fields<'a>(
+    index: &'a SearchIndex,
+    req: &'a api::SearchRequest,
+) -> IndexSet<&'a String> {
+    let mut field_set = IndexSet::new();
+    for field in &req.select {
+        if field == "*" {
+            field_set.extend(index.schema.icol_by_name.keys());
+        } else {
+            field_set.insert(field);
+        }
+    }
+    field_set
+}
+
+fn empty_column_values(column: &MetaColumn, capacity: usize) -> ColumnValues {
+    use ColumnValues::*;
+    match (column.column_type, column.is_nullable) {
+        (MetaColumnType::Bool, false) => Bool(Vec::with_capacity(capacity)),
+        (MetaColumnType::Ulong, false) => UInt64(Vec::with_capacity(capacity)),
+        (MetaColumnType::Long, false) => Int64(Vec::with_capacity(capacity)),
+        (MetaColumnType::Double, false) => Double(Vec::with_capacity(capacity)),
+        (MetaColumnType::Text, false) => String(Vec::with_capacity(capacity)),
+        (MetaColumnType::Bytes, false) => Bytes(Vec::with_capacity(capacity)),
+        (MetaColumnType::DateTime, false) => DateTime(Vec::with_capacity(capacity)),
+        (MetaColumnType::Tree, false) => Facet(Vec::with_capacity(capacity)),
+        (MetaColumnType::Bool, true) => NullableBool(Vec::with_capacity(capacity)),
+        (MetaColumnType::Ulong, true) => NullableUInt64(Vec::with_capacity(capacity)),
+        (MetaColumnType::Long, true) => NullableInt64(Vec::with_capacity(capacity)),
+        (MetaColumnType::Double, true) => NullableDouble(Vec::with_capacity(capacity)),
+        (MetaColumnType::Text, true) => NullableString(Vec::with_capacity(capacity)),
+        (MetaColumnType::Bytes, true) => NullableBytes(Vec::with_capacity(capacity)),
+        (MetaColumnType::DateTime, true) => NullableDateTime(Vec::with_capacity(capacity)),
+        (MetaColumnType::Tree, true) => NullableFacet(Vec::with_capacity(capacity)),
+    }
+}
+
+fn push_column_value(
+    values: &mut ColumnValues,
+    column: &MetaColumn,
+    value: Option<&OwnedValue>,
+) -> Result<()> {
+    use ColumnValues::*;
+    let text = |value: &OwnedValue| match value {
+        OwnedValue::Str(s) => Some(s.clone()),
+        OwnedValue::PreTokStr(p) => Some(p.text.clone()),
+        _ => None,
+    };
+
+    match (values, value) {
+        (_, None) if !column.is_nullable => bail!("Unexpected null for {}", column.name),
+        (Bool(v), Some(OwnedValue::Bool(b))) => v.push(*b),
+        (UInt64(v), Some(OwnedValue::U64(n))) => v.push(*n),
+        (Int64(v), Some(OwnedValue::I64(n))) => v.push(*n),
+        (Double(v), Some(OwnedValue::F64(n))) => v.push(*n),
+        (String(v), Some(value)) if text(value).is_some() => v.extend(text(value)),
+        (Bytes(v), Some(OwnedValue::Bytes(b))) => v.push(b.clone()),
+        (DateTime(v), Some(OwnedValue::Date(dt))) => v.push(tantivy_datetime_to_iso(*dt)),
+        (Facet(v), Some(OwnedValue::Facet(f))) => v.push(f.to_string()),
+        (NullableBool(v), value) => match value {
+            Some(OwnedValue::Bool(b)) => v.push(Some(*b)),
+            None => v.push(None),
+            _ => return Err(type_mismatch(column, value)),
+        },
+        (NullableUInt64(v), value) => match value {
+            Some(OwnedValue::U64(n)) => v.push(Some(*n)),
+            None => v.push(None),
+            _ => return Err(type_mismatch(column, value)),
+        },
+        (NullableInt64(v), value) => match value {
+            Some(OwnedValue::I64(n)) => v.push(Some(*n)),
+            None => v.push(None),
+            _ => return Err(type_mismatch(column, value)),
+        },
+        (NullableDouble(v), value) => match value {
+            Some(OwnedValue::F64(n)) => v.push(Some(*n)),
+            None => v.push(None),
+            _ => return Err(type_mismatch(column, value)),
+        },
+        (NullableString(v), value) => match value {
+            Some(value) if text(value).is_some() => v.push(text(value)),
+            None => v.push(None),
+            _ => return Err(type_mismatch(column, value)),
+        },
+        (NullableBytes(v), value) => match value {
+            Some(OwnedValue::Bytes(b)) => v.push(Some(b.clone())),
+            None => v.push(None),
+            _ => return Err(type_mismatch(column, value)),
+        },
+        (NullableDateTime(v), value) => match value {
+            Some(OwnedValue::Date(dt)) => v.push(Some(tantivy_datetime_to_iso(*dt))),
+            None => v.push(None),
+            _ => return Err(type_mismatch(column, value)),
+        },
+        (NullableFacet(v), value) => match value {
+            Some(OwnedValue::Facet(f)) => v.push(Some(f.to_string())),
+            None => v.push(None),
+            _ => return Err(type_mismatch(column, value)),
+        },
+        (_, value) => return Err(type_mismatch(column, value)),
+    }
+    Ok(())
+}
+
+fn type_mismatch(column: &MetaColumn, value: Option<&OwnedValue>) -> anyhow::Error {
+    anyhow!(
+        "Stored value {value:?} does not match {} column '{}'",
+        column.column_type,
+        column.name
+    )
+}
@@ -1,61 +1,328 @@
-use anyhow::{Result, anyhow};
-use tantivy::query::QueryParser;
-use tantivy::{DocAddress, Score, collector::TopDocs};
-use tonic::Status;
+use anyhow::{Context, Result, anyhow, bail};
+use chrono::{DateTime, Utc};
+use corelib::api::MetaColumnType;
+use corelib::telemetry::metrics;
+use indexmap::IndexMap;
+use std::collections::HashMap;
+use std::time::Instant;
+use tantivy::collector::{Count, FruitHandle, MultiCollector, TopDocs};
+use tantivy::query::{
+    AllQuery, BooleanQuery, EmptyQuery, EnableScoring, Occur, Query, QueryParser, Weight,
+};
+use tantivy::schema::Facet;
+use tantivy::{DocAddress, Score, Searcher};
 use tracing::info;
 
 use crate::api;
 use crate::domain::index::SearchIndex;
+use crate::domain::text_ranks::TextRanks;
 
-use super::virtual_sort::collector::SortByVirtualFieldCollector;
+use super::aggs::compile_aggs;
+use super::cursor::{Cursor, CursorOrder, Fingerprint, ScoreAfterCollector};
+use super::facets::{facet_collectors, facet_results};
+use super::filter::compile_filter;
+use super::order_by::compile_order_by;
+use super::virtual_sort::collector::{SortByVirtualFieldCollector, SortedPage};
 use super::virtual_sort::expr::Expr;
-use super::virtual_sort::program::Program;
+use super::virtual_sort::functions::Functions;
+use super::virtual_sort::params::bind_params;
+use super::virtual_sort::program::{OpCode, Program, SCORE_VAR, VarSource};
 
-pub fn execute_search(
-    index: &SearchIndex,
-    req: &api::SearchRequest,
-) -> Result<Vec<(Score, DocAddress)>, Status> {
-    let searcher = index.reader.searcher();
+/// Результат одного прохода по индексу: страница выдачи, общее число, фасеты и агрегации
+#[derive(Debug)]
+pub struct SearchHits {
+    /// Снимок, в котором найдены `top_docs`: по нему же читаются документы
+    pub searcher: Searcher,
+    /// Что лежит в `Score`, см. [`Ranking`]
+    pub top_docs: Vec<(Score, DocAddress)>,
+    pub ranking: Ranking,
+    /// Для `sort` с `_score` в `select`: BM25 score документов `top_docs`, иначе пусто
+    pub bm25_scores: Vec<Score>,
+    /// Заполняется при `track_total`
+    pub total: Option<u64>,
+    /// Закодированный курсор следующей страницы
+    pub cursor: Option<String>,
+    /// Для `sort`: сколько документов получили NaN или ошибку
+    pub invalid_sort_values: Option<u64>,
+    pub facets: Vec<api::FacetResult>,
+    pub aggs: IndexMap<String, api::AggregationResult>,
+}
+
+/// Чем отсортирована выдача
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ranking {
+    /// BM25, в `Score` — релевантность
+    Relevance,
+    /// Выражение `sort`, в `Score` — его значение
+    Expr,
+    /// `order_by`, `Score` не заполняется
+    Fields,
+}
+
+impl SearchHits {
+    /// BM25 score документа `row` выдачи, если запрос его считал
+    pub fn bm25(&self, row: usize) -> Option<Score> {
+        match self.ranking {
+            Ranking::Relevance => self.top_docs.get(row).map(|&(score, _)| score),
+            Ranking::Expr => self.bm25_scores.get(row).copied(),
+            Ranking::Fields => None,
+        }
+    }
+}
+
+/// Выдача по выражению дополнительно считает документы без значения
+enum TopDocsHandle {
+    Docs(FruitHandle<Vec<(Score, DocAddress)>>),
+    Expr(FruitHandle<SortedPage>),
+}
+
+pub fn execute_search(index: &SearchIndex, req: &api::SearchRequest) -> Result<SearchHits> {
+    let started = Instant::now();
+    let index_name = [index.schema.name.as_str()];
+    let searcher = index.searcher(req.pit_id.as_deref())?;
+    metrics::INDEX_SEGMENTS
+        .with_label_values(&index_name)
+        .set(searcher.segment_readers().len() as i64);
     // let schema = index.index.schema();
 
-    // let default_fields = index.schema.columns.iter().map(|c| c.idx).collect();
-    let default_fields = index.schema.get_full_text_col_idx();
-    let parser = QueryParser::for_index(&index.index, default_fields);
-    let query = parser
-        .parse_query(&req.filter)
-        .map_err(|e| Status::invalid_argument(format!("Invalid query: {e}")))?;
+    let query = tracing::info_span!("query_parsing")
+        .in_scope(|| build_query(index, &req.filter, req.where_filter.as_ref()))?;
+    let facet_collectors = facet_collectors(index, &req.facets)?;
+    let keep_score = selects_score(index, req);
+    let order_by_collector = if req.order_by.is_empty() {
+        None
+    } else if req.sort.is_some() || req.cursor.is_some() {
+        bail!("order_by cannot be combined with sort or cursor");
+    } else if keep_score {
+        bail!("_score cannot be selected with order_by: documents are not scored");
+    } else {
+        Some(compile_order_by(
+            &index.schema,
+            &req.order_by,
+            req.limit,
+            req.offset,
+        )?)
+    };
+    let cursor_order = match (&req.sort, req.sort_order) {
+        (Some(_), api::SortOrder::Asc) => CursorOrder::Expr,
+        (Some(_), api::SortOrder::Desc) => CursorOrder::ExprDesc,
+        (None, _) => CursorOrder::Score,
+    };
+    let fingerprint = sort_fingerprint(req);
+    let after = Cursor::parse_for(req.cursor.as_deref(), cursor_order, fingerprint)?;
+    let now = request_now(req.now.as_deref(), after.as_ref())?;
+    let aggs_collector = (!req.aggs.is_empty())
+        .then(|| compile_aggs(&index.schema, &req.aggs, now))
+        .transpose()?;
+
+    let _collection = tracing::info_span!("collection", index = %index.schema.name).entered();
 
-    let top_docs = match &req.sort {
-        Some(sort_func) => {
+    // выдача, счётчик, фасеты и агрегации собираются одним проходом
+    let mut collectors = MultiCollector::new();
+    let top_docs_handle = match (&req.sort, order_by_collector) {
+        (_, Some(order_by)) => {
+            info!("ORDER_BY sort");
+            TopDocsHandle::Docs(collectors.add_collector(order_by))
+        }
+        (Some(sort_func), None) => {
             info!("USED sort_func");
-            let program = parse_and_compile_program(&sort_func)
-                .map_err(|e| Status::internal(format!("Search failed: {e}")))?;
+            let program = parse_and_compile_program(sort_func, req, now)?;
+            check_facet_vars(index, &program)?;
+            let bm25_weights = bm25_weights(index, &searcher, &program, &req.filter)?;
+            let text_ranks = text_ranks(index, &searcher, &program)?;
 
-            let collector = SortByVirtualFieldCollector {
+            TopDocsHandle::Expr(collectors.add_collector(SortByVirtualFieldCollector {
                 limit: req.limit,
                 offset: req.offset,
                 program,
                 schema: &index.schema,
-            };
-
-            searcher
-                .search(&query, &collector)
-                .map_err(|e| Status::internal(format!("Search failed: {e}")))?
+                order: req.sort_order,
+                invalid: req.sort_invalid,
+                after: after.map(|cursor| (cursor.sort_value, cursor.doc)),
+                bm25_weights,
+                text_ranks,
+                keep_score,
+            }))
         }
-        None => {
-            info!("TOP_N sort");
-            let collector = TopDocs::with_limit(req.limit).and_offset(req.offset);
+        (None, None) => TopDocsHandle::Docs(match after {
+            Some(after) => collectors.add_collector(ScoreAfterCollector {
+                limit: req.limit,
+                offset: req.offset,
+                after,
+            }),
+            None => {
+                info!("TOP_N sort");
+                collectors.add_collector(TopDocs::with_limit(req.limit).and_offset(req.offset))
+            }
+        }),
+    };
+    let total_handle = req.track_total.then(|| collectors.add_collector(Count));
+    let facet_handles: Vec<_> = facet_collectors
+        .into_iter()
+        .map(|collector| collectors.add_collector(collector))
+        .collect();
+    let aggs_handle = aggs_collector.map(|collector| collectors.add_collector(collector));
 
-            searcher
-                .search(&query, &collector)
-                .map_err(|e| Status::internal(format!("Search failed: {e}")))?
+    let mut fruits = searcher
+        .search(&query, &collectors)
+        .context("Search failed")?;
+    let (top_docs, bm25_scores, invalid_sort_values) = match top_docs_handle {
+        TopDocsHandle::Docs(handle) => (handle.extract(&mut fruits), Vec::new(), None),
+        TopDocsHandle::Expr(handle) => {
+            let page = handle.extract(&mut fruits);
+            let scores = if keep_score { page.scores } else { Vec::new() };
+            (page.top_docs, scores, Some(page.invalid))
         }
     };
+    let total = total_handle.map(|handle| handle.extract(&mut fruits) as u64);
+    let facet_counts = facet_handles
+        .into_iter()
+        .map(|handle| handle.extract(&mut fruits))
+        .collect();
+    let aggs = aggs_handle
+        .map(|handle| handle.extract(&mut fruits))
+        .unwrap_or_default();
+
+    metrics::SEARCH_COLLECT_DURATION
+        .with_label_values(&index_name)
+        .observe(started.elapsed().as_secs_f64());
+    metrics::SEARCH_ROWS_RETURNED
+        .with_label_values(&index_name)
+        .observe(top_docs.len() as f64);
+
+    // курсор хранит одно значение сортировки, для order_by его нет
+    let cursor = req
+        .order_by
+        .is_empty()
+        .then(|| {
+            let now_ms = now.timestamp_millis();
+            Cursor::after_page(cursor_order, fingerprint, now_ms, &top_docs, req.limit)
+        })
+        .flatten()
+        .map(|c| c.encode());
+
+    let ranking = match (&req.sort, req.order_by.is_empty()) {
+        (_, false) => Ranking::Fields,
+        (Some(_), true) => Ranking::Expr,
+        (None, true) => Ranking::Relevance,
+    };
 
-    Ok(top_docs)
+    Ok(SearchHits {
+        searcher,
+        top_docs,
+        ranking,
+        bm25_scores,
+        total,
+        cursor,
+        invalid_sort_values,
+        facets: facet_results(&req.facets, facet_counts),
+        aggs,
+    })
 }
 
-fn parse_and_compile_program(func: &str) -> Result<Program> {
+/// Только число найденных документов, без выборки строк
+pub fn execute_count(index: &SearchIndex, req: &api::CountRequest) -> Result<u64> {
+    let started = Instant::now();
+    let searcher = index.searcher(req.pit_id.as_deref())?;
+
+    let query = tracing::info_span!("query_parsing")
+        .in_scope(|| build_query(index, &req.filter, req.where_filter.as_ref()))?;
+    let count = tracing::info_span!("collection", index = %index.schema.name)
+        .in_scope(|| searcher.search(&query, &Count))
+        .context("Count failed")?;
+
+    metrics::SEARCH_COLLECT_DURATION
+        .with_label_values(&[index.schema.name.as_str()])
+        .observe(started.elapsed().as_secs_f64());
+    Ok(count as u64)
+}
+
+/// Строковый `filter` и структурный `where` объединяются через AND; без обоих — все документы
+pub fn build_query(
+    index: &SearchIndex,
+    filter: &str,
+    where_filter: Option<&api::Filter>,
+) -> Result<Box<dyn Query>> {
+    let mut clauses: Vec<(Occur, Box<dyn Query>)> = Vec::new();
+
+    if !filter.trim().is_empty() {
+        // let default_fields = index.schema.columns.iter().map(|c| c.idx).collect();
+        let default_fields = index.schema.get_full_text_col_idx();
+        let parser = QueryParser::for_index(&index.index, default_fields);
+        let query = parser
+            .parse_query(filter)
+            .map_err(|e| anyhow!("Invalid query: {e}"))?;
+        clauses.push((Occur::Must, query));
+    }
+    if let Some(filter) = where_filter {
+        clauses.push((Occur::Must, compile_filter(index, filter)?));
+    }
+
+    Ok(match clauses.len() {
+        0 => Box::new(AllQuery),
+        1 => clauses.pop().unwrap().1,
+        _ => Box::new(BooleanQuery::new(clauses)),
+    })
+}
+
+/// Отпечаток выражения `sort` для курсора: текст, функции, параметры и `sort_invalid`.
+/// Без `sort` курсор BM25 получает 0
+/// Запрошена ли псевдоколонка `_score`: колонка схемы с тем же именем её скрывает
+fn selects_score(index: &SearchIndex, req: &api::SearchRequest) -> bool {
+    index.schema.get_column(SCORE_VAR).is_err() && req.select.iter().any(|f| f == SCORE_VAR)
+}
+
+fn sort_fingerprint(req: &api::SearchRequest) -> u64 {
+    let Some(sort) = &req.sort else {
+        return 0;
+    };
+    let mut fingerprint = Fingerprint::default().str(sort);
+    fingerprint = fingerprint.bytes(&(req.functions.len() as u64).to_le_bytes());
+    for function in &req.functions {
+        fingerprint = fingerprint.str(function);
+    }
+    let mut params: Vec<_> = req.params.iter().collect();
+    params.sort_unstable_by(|a, b| a.0.cmp(b.0));
+    for (name, value) in params {
+        fingerprint = fingerprint.str(name).bytes(&value.to_bits().to_le_bytes());
+    }
+    let invalid = match req.sort_invalid {
+        api::InvalidSortValue::Drop => 0,
+        api::InvalidSortValue::First => 1,
+        api::InvalidSortValue::Last => 2,
+    };
+    fingerprint.bytes(&[invalid]).finish()
+}
+
+/// `now` запроса или текущее время с точностью до миллисекунд. Следующие страницы
+/// курсора считаются в `now` первой, явный `now` должен с ним совпадать
+fn request_now(now: Option<&str>, after: Option<&Cursor>) -> Result<DateTime<Utc>> {
+    let now = match now {
+        Some(now) => DateTime::parse_from_rfc3339(now)
+            .map(|now| now.with_timezone(&Utc))
+            .map_err(|e| anyhow!("Invalid now '{now}': {e}"))?,
+        None => match after {
+            Some(cursor) => DateTime::from_timestamp_millis(cursor.now_ms)
+                .ok_or_else(|| anyhow!("Invalid now in cursor"))?,
+            None => Utc::now(),
+        },
+    };
+    let now = DateTime::from_timestamp_millis(now.timestamp_millis())
+        .ok_or_else(|| anyhow!("Invalid now {now}"))?;
+    if let Some(cursor) = after
+        && cursor.now_ms != now.timestamp_millis()
+    {
+        bail!("Cursor was issued for a different now");
+    }
+    Ok(now)
+}
+
+fn parse_and_compile_program(
+    func: &str,
+    req: &api::SearchRequest,
+    now: DateTime<Utc>,
+) -> Result<Program> {
+    let functions = Functions::parse(&req.functions)?;
     let expr = Expr::parse(func).into_result().map_err(|errs| {
         anyhow!(
             "Failed to parse function: {}",
@@ -65,5 +332,106 @@ fn parse_and_compile_program(func: &str) -> Result<Program> {
                 .join(", ")
         )
     })?;
-    Ok(Program::compile_expr(expr))
+    let expr = bind_params(functions.inline(expr)?, &req.params)?;
+    let program = Program::compile_expr_at(expr, now);
+    // вызовы, которые компилятор не превратил в переменные, записаны неверно
+    for op in &program.ops {
+        let OpCode::CallFunction { name, .. } = op else {
+            continue;
+        };
+        match name.as_str() {
+            "bm25" => bail!("bm25() expects a single column name, e.g. bm25(title)"),
+            "has_facet" => {
+                bail!(
+                    "has_facet() expects a column and a quoted path, e.g. has_facet(category, \"/phones\")"
+                )
+            }
+            "facet_depth" => {
+                bail!("facet_depth() expects a single column name, e.g. facet_depth(category)")
+            }
+            _ => {}
+        }
+    }
+    if program.env.iter().any(|var| var.starts_with('"')) {
+        bail!("String literals are only allowed as the path in has_facet()");
+    }
+    Ok(program)
+}
+
+/// `has_facet` и `facet_depth` читают только `tree` колонки, путь должен начинаться с `/`
+fn check_facet_vars(index: &SearchIndex, program: &Program) -> Result<()> {
+    for var in &program.env {
+        let (column_name, path) = match VarSource::of(var) {
+            VarSource::HasFacet { column, path } => (column, Some(path)),
+            VarSource::FacetDepth(column) => (column, None),
+            _ => continue,
+        };
+        let column = index.schema.get_column(column_name)?;
+        if column.column_type != MetaColumnType::Tree {
+            bail!(
+                "{var}: facet functions require a tree column, '{column_name}' is {}",
+                column.column_type
+            );
+        }
+        if let Some(path) = path {
+            Facet::from_text(path).map_err(|e| anyhow!("{var}: {e}"))?;
+        }
+    }
+    Ok(())
+}
+
+/// Для каждого `bm25(col)` из программы — вес запроса `filter`, разобранного только по
+/// full-text полю колонки. Пустой `filter` даёт 0 у всех документов
+fn bm25_weights(
+    index: &SearchIndex,
+    searcher: &Searcher,
+    program: &Program,
+    filter: &str,
+) -> Result<HashMap<String, Box<dyn Weight>>> {
+    let mut weights = HashMap::new();
+    for var in &program.env {
+        let VarSource::Bm25(column_name) = VarSource::of(var) else {
+            continue;
+        };
+        let column = index.schema.get_column(column_name)?;
+        let Some(full_text) = column.full_text_idx else {
+            bail!("bm25({column_name}): column is not full_text");
+        };
+
+        let query: Box<dyn Query> = if filter.trim().is_empty() {
+            Box::new(EmptyQuery)
+        } else {
+            QueryParser::for_index(&index.index, vec![full_text])
+                .parse_query(filter)
+                .map_err(|e| anyhow!("Invalid query: {e}"))?
+        };
+        let weight = query.weight(EnableScoring::enabled_from_searcher(searcher))?;
+        weights.insert(column_name.to_string(), weight);
+    }
+    Ok(weights)
+}
+
+/// Ранги строк для text-колонок выражения: ординалы словарей разных сегментов несравнимы
+fn text_ranks(
+    index: &SearchIndex,
+    searcher: &Searcher,
+    program: &Program,
+) -> Result<HashMap<String, TextRanks>> {
+    let mut ranks = HashMap::new();
+    for var in &program.env {
+        let VarSource::Column(column_name) = VarSource::of(var) else {
+            continue;
+        };
+        let column = index.schema.get_column(column_name)?;
+        if column.column_type == MetaColumnType::Text {
+            let column_ranks = index.text_ranks.get_or_compute(searcher, column_name, || {
+                Ok(super::virtual_sort::collector::text_ranks(
+                    searcher,
+                    column_name,
+                )?)
+            })?;
+            ranks.insert(column_name.to_string(), column_ranks);
+        }
+    }
+    Ok(ranks)
 }
@@ -1,38 +1,84 @@
-use corelib::{api, model::MetaSchema};
-use std::cmp::Ordering;
+use corelib::api;
+use corelib::model::{MetaColumn, MetaSchema};
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap};
+use std::ops::{Bound, Range};
+use std::sync::Arc;
 use tantivy::collector::{Collector, SegmentCollector};
-use tantivy::{DocAddress, DocId, Score, SegmentOrdinal, SegmentReader};
+use tantivy::columnar::StrColumn;
+use tantivy::fastfield::FacetReader;
+use tantivy::query::{Scorer, Weight};
+use tantivy::schema::Facet;
+use tantivy::{DocAddress, DocId, DocSet, Score, Searcher, SegmentOrdinal, SegmentReader};
 use tracing::debug;
 
+use crate::api::{InvalidSortValue, SortOrder};
+use crate::domain::text_ranks::TextRanks;
 use crate::engine::virtual_sort::eval::eval_program;
-use crate::engine::virtual_sort::program::Program;
+use crate::engine::virtual_sort::program::{Program, VarSource};
 
 pub struct SortByVirtualFieldCollector<'a> {
     pub limit: usize,
     pub offset: usize,
     pub program: Program,
     pub schema: &'a MetaSchema,
+    /// `desc` оставляет наибольшие значения
+    pub order: SortOrder,
+    /// Куда девать документы, у которых выражение дало NaN или ошибку
+    pub invalid: InvalidSortValue,
+    /// Курсор предыдущей страницы: документы до него включительно пропускаются
+    pub after: Option<(f32, DocAddress)>,
+    /// Веса запроса `filter` по отдельным full-text полям для `bm25(col)`, по имени колонки
+    pub bm25_weights: HashMap<String, Box<dyn Weight>>,
+    /// Общие для всех сегментов ранги строк text-колонок, см. [`text_ranks`]
+    pub text_ranks: HashMap<String, TextRanks>,
+    /// Считать BM25 score запроса, даже если выражение его не использует: нужен для `_score`
+    pub keep_score: bool,
 }
 
 pub struct VirtualFieldSegmentCollector {
     pub program: Program,
     pub segment_ordinal: SegmentOrdinal,
     pub field_readers: Vec<(usize, FieldReader)>,
-    pub doc_ids: Vec<DocId>,
+    /// Индекс `_score` в env
+    pub score_var: Option<usize>,
+    pub bm25_scorers: Vec<(usize, Box<dyn Scorer>)>,
     pub max_docs: usize,
+    pub order: SortOrder,
+    pub invalid: InvalidSortValue,
+    pub after: Option<ScoredDoc>,
+    /// Значения переменных env для текущего документа
+    ctx: Vec<f32>,
+    /// Не больше `2 * max_docs` документов после курсора
+    results: Vec<ScoredDoc>,
+    invalid_count: u64,
+}
+
+/// Страница выдачи по выражению и число документов, для которых оно не дало числа
+#[derive(Debug)]
+pub struct SortedPage {
+    pub top_docs: Vec<(f32, DocAddress)>,
+    /// BM25 score документов `top_docs` в том же порядке
+    pub scores: Vec<Score>,
+    pub invalid: u64,
 }
 
 #[derive(Debug, PartialEq)]
 pub struct ScoredDoc {
     pub sort_value: f32,
     pub doc: DocAddress,
+    /// BM25 score запроса, в порядке не участвует
+    pub score: Score,
 }
 
 impl Eq for ScoredDoc {}
 
 impl Ord for ScoredDoc {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.sort_value.total_cmp(&other.sort_value)
+        // адрес разрешает равенства, чтобы порядок был детерминирован для курсора
+        self.sort_value
+            .total_cmp(&other.sort_value)
+            .then(self.doc.cmp(&other.doc))
     }
 }
 
@@ -42,12 +88,32 @@ impl PartialOrd for ScoredDoc {
     }
 }
 
+impl ScoredDoc {
+    /// Порядок выдачи: по значению в направлении `order`, NaN — в начале или в конце
+    /// по `invalid`, при равенстве меньший адрес раньше
+    pub fn cmp_in(&self, other: &Self, order: SortOrder, invalid: InvalidSortValue) -> Ordering {
+        let nan_first = invalid == InvalidSortValue::First;
+        let by_value = match (self.sort_value.is_nan(), other.sort_value.is_nan()) {
+            (true, true) => Ordering::Equal,
+            (true, false) if nan_first => Ordering::Less,
+            (true, false) => Ordering::Greater,
+            (false, true) if nan_first => Ordering::Greater,
+            (false, true) => Ordering::Less,
+            (false, false) => match order {
+                SortOrder::Asc => self.sort_value.total_cmp(&other.sort_value),
+                SortOrder::Desc => other.sort_value.total_cmp(&self.sort_value),
+            },
+        };
+        by_value.then(self.doc.cmp(&other.doc))
+    }
+}
+
 impl<'a> Collector for SortByVirtualFieldCollector<'a> {
-    type Fruit = Vec<(f32, DocAddress)>;
+    type Fruit = SortedPage;
     type Child = VirtualFieldSegmentCollector;
 
     fn requires_scoring(&self) -> bool {
-        false
+        self.keep_score || self.program.requires_scoring()
     }
 
     fn for_segment(
@@ -56,8 +122,40 @@ impl<'a> Collector for SortByVirtualFieldCollector<'a> {
         segment: &SegmentReader,
     ) -> tantivy::Result<Self::Child> {
         let mut field_readers = Vec::with_capacity(self.program.env.len());
+        let mut score_var = None;
+        let mut bm25_scorers = Vec::new();
 
         for (var_idx, var_name) in self.program.env.iter().enumerate() {
+            let var_name = match VarSource::of(var_name) {
+                VarSource::Score => {
+                    score_var = Some(var_idx);
+                    continue;
+                }
+                VarSource::Bm25(column) => {
+                    let weight = self.bm25_weights.get(column).ok_or_else(|| {
+                        tantivy::TantivyError::InvalidArgument(format!(
+                            "bm25(`{column}`) requires a full_text column"
+                        ))
+                    })?;
+                    bm25_scorers.push((var_idx, weight.scorer(segment, 1.0)?));
+                    continue;
+                }
+                VarSource::HasFacet { column, path } => {
+                    let facet = Facet::from_text(path).map_err(|e| {
+                        tantivy::TantivyError::InvalidArgument(format!(
+                            "has_facet(`{column}`): {e}"
+                        ))
+                    })?;
+                    let reader = FieldReader::has_facet(segment, column, &facet)?;
+                    field_readers.push((var_idx, reader));
+                    continue;
+                }
+                VarSource::FacetDepth(column) => {
+                    field_readers.push((var_idx, FieldReader::facet_depth(segment, column)?));
+                    continue;
+                }
+                VarSource::Column(name) => name,
+            };
             let column = self.schema.get_column(var_name).map_err(|e| {
                 tantivy::TantivyError::InvalidArgument(format!("Unknown column `{var_name}`: {e}"))
             })?;
@@ -70,24 +168,19 @@ impl<'a> Collector for SortByVirtualFieldCollector<'a> {
             );
 
             let reader = match column.column_type {
-                api::MetaColumnType::DateTime => {
-                    let col = segment.fast_fields().date(var_name)?;
-                    FieldReader::Date(col)
-                }
-                api::MetaColumnType::Double => {
-                    let col = segment.fast_fields().f64(var_name)?;
-                    FieldReader::F64(col)
-                }
-                api::MetaColumnType::Bool => {
-                    let col = segment.fast_fields().bool(var_name)?;
-                    FieldReader::Bool(col)
-                }
-                other => {
-                    return Err(tantivy::TantivyError::InvalidArgument(format!(
-                        "Unsupported fast field type in virtual sort: {:?}",
-                        other
-                    )));
+                api::MetaColumnType::Text => {
+                    let ranks = self
+                        .text_ranks
+                        .get(var_name)
+                        .and_then(|ranks| ranks.get(segment_ordinal as usize))
+                        .ok_or_else(|| {
+                            tantivy::TantivyError::InvalidArgument(format!(
+                                "No global ranks for text column `{var_name}`"
+                            ))
+                        })?;
+                    FieldReader::text(segment, var_name, ranks.clone())?
                 }
+                _ => FieldReader::open(segment, column)?,
             };
 
             field_readers.push((var_idx, reader));
@@ -97,8 +190,19 @@ impl<'a> Collector for SortByVirtualFieldCollector<'a> {
             program: self.program.clone(),
             segment_ordinal,
             field_readers,
-            doc_ids: Vec::new(),
+            score_var,
+            bm25_scorers,
             max_docs: self.offset + self.limit,
+            order: self.order,
+            invalid: self.invalid,
+            after: self.after.map(|(sort_value, doc)| ScoredDoc {
+                sort_value,
+                doc,
+                score: 0.0,
+            }),
+            ctx: vec![0.0; self.program.env.len()],
+            results: Vec::new(),
+            invalid_count: 0,
         })
     }
 
@@ -106,82 +210,290 @@ impl<'a> Collector for SortByVirtualFieldCollector<'a> {
         &self,
         segment_fruits: Vec<<Self::Child as SegmentCollector>::Fruit>,
     ) -> tantivy::Result<Self::Fruit> {
-        let mut scored_docs: Vec<ScoredDoc> = segment_fruits.into_iter().flatten().collect();
+        let invalid = segment_fruits.iter().map(|(_, invalid)| invalid).sum();
+        let mut scored_docs: Vec<ScoredDoc> = segment_fruits
+            .into_iter()
+            .flat_map(|(docs, _)| docs)
+            .collect();
         let cutoff = self.offset + self.limit;
+        let order = |a: &ScoredDoc, b: &ScoredDoc| a.cmp_in(b, self.order, self.invalid);
 
         if cutoff < scored_docs.len() {
-            scored_docs.select_nth_unstable_by(cutoff, ScoredDoc::cmp);
-            scored_docs[..cutoff].sort_unstable_by(ScoredDoc::cmp);
-            Ok(scored_docs[self.offset..cutoff]
-                .iter()
-                .map(|sd| (sd.sort_value, sd.doc))
-                .collect())
-        } else {
-            scored_docs.sort_unstable_by(ScoredDoc::cmp);
-            Ok(scored_docs
-                .into_iter()
-                .skip(self.offset)
-                .map(|sd| (sd.sort_value, sd.doc))
-                .collect())
+            scored_docs.select_nth_unstable_by(cutoff, order);
+            scored_docs.truncate(cutoff);
         }
+        scored_docs.sort_unstable_by(order);
+        let page = scored_docs.get(self.offset..).unwrap_or_default();
+        Ok(SortedPage {
+            top_docs: page.iter().map(|sd| (sd.sort_value, sd.doc)).collect(),
+            scores: page.iter().map(|sd| sd.score).collect(),
+            invalid,
+        })
     }
 }
 
-impl SegmentCollector for VirtualFieldSegmentCollector {
-    type Fruit = Vec<ScoredDoc>;
-
-    fn collect(&mut self, doc_id: DocId, _score: Score) {
-        self.doc_ids.push(doc_id);
+impl VirtualFieldSegmentCollector {
+    fn truncate(&mut self) {
+        if self.results.len() > self.max_docs {
+            self.results.select_nth_unstable_by(self.max_docs, |a, b| {
+                a.cmp_in(b, self.order, self.invalid)
+            });
+            self.results.truncate(self.max_docs);
+        }
     }
+}
 
-    fn harvest(self) -> Vec<ScoredDoc> {
-        let mut results = Vec::with_capacity(self.doc_ids.len());
-        let mut ctx = vec![0.0f32; self.program.env.len()];
+impl SegmentCollector for VirtualFieldSegmentCollector {
+    /// Документы сегмента и число документов с NaN или ошибкой
+    type Fruit = (Vec<ScoredDoc>, u64);
 
-        for doc_id in self.doc_ids {
-            // читаем все значения из fastfield'ов
-            for &(var_idx, ref reader) in &self.field_readers {
-                ctx[var_idx] = reader.read_f32(doc_id);
+    fn collect(&mut self, doc_id: DocId, score: Score) {
+        // читаем все значения из fastfield'ов
+        for &(var_idx, ref reader) in &self.field_readers {
+            self.ctx[var_idx] = reader.read_f32(doc_id);
+        }
+        if let Some(var_idx) = self.score_var {
+            self.ctx[var_idx] = score;
+        }
+        // doc_ids возрастают, поэтому скореры только продвигаются вперёд
+        for (var_idx, scorer) in &mut self.bm25_scorers {
+            if scorer.doc() < doc_id {
+                scorer.seek(doc_id);
             }
+            self.ctx[*var_idx] = if scorer.doc() == doc_id {
+                scorer.score()
+            } else {
+                0.0
+            };
+        }
 
-            if let Ok(score) = eval_program(&self.program, &ctx) {
-                results.push(ScoredDoc {
-                    sort_value: score,
-                    doc: DocAddress::new(self.segment_ordinal, doc_id),
-                });
+        let sort_value = match eval_program(&self.program, &self.ctx) {
+            Ok(score) if !score.is_nan() => score,
+            _ => {
+                self.invalid_count += 1;
+                if self.invalid == InvalidSortValue::Drop {
+                    return;
+                }
+                f32::NAN
             }
+        };
+        let scored = ScoredDoc {
+            sort_value,
+            doc: DocAddress::new(self.segment_ordinal, doc_id),
+            score,
+        };
+        let after_cursor = self.after.as_ref().is_none_or(|after| {
+            scored.cmp_in(after, self.order, self.invalid) == Ordering::Greater
+        });
+        if !after_cursor {
+            return;
         }
-
-        // отсекаем лишнее
-        if results.len() > self.max_docs {
-            results.select_nth_unstable_by(self.max_docs, ScoredDoc::cmp);
-            results.truncate(self.max_docs);
+        self.results.push(scored);
+        // отсекаем пачками, чтобы не сортировать на каждый документ
+        if self.results.len() >= 2 * self.max_docs.max(1) {
+            self.truncate();
         }
+    }
 
-        results
+    fn harvest(mut self) -> Self::Fruit {
+        self.truncate();
+        (self.results, self.invalid_count)
     }
 }
 
+/// Разделитель уровней в закодированном пути фасета tantivy
+const FACET_SEP: u8 = 0;
+
 pub enum FieldReader {
     Date(tantivy::fastfield::Column<tantivy::DateTime>),
     F64(tantivy::fastfield::Column<f64>),
+    I64(tantivy::fastfield::Column<i64>),
+    U64(tantivy::fastfield::Column<u64>),
     Bool(tantivy::fastfield::Column<bool>),
+    /// Ранг строки среди строк всех сегментов по ординалу в словаре сегмента.
+    /// `None` — колонка в сегменте пуста
+    Text {
+        column: Option<StrColumn>,
+        ranks: Arc<[u32]>,
+    },
+    /// `has_facet`: ординалы пути и всех его потомков в словаре сегмента
+    HasFacet {
+        facets: FacetReader,
+        ords: Range<u64>,
+    },
+    /// `facet_depth`: глубина каждого пути словаря сегмента по его ординалу
+    FacetDepth {
+        facets: FacetReader,
+        depths: Vec<u32>,
+    },
 }
 
 impl FieldReader {
+    /// Открывает fast-field колонку в сегменте; `tree` читается только через
+    /// [`FieldReader::has_facet`] и [`FieldReader::facet_depth`]
+    pub fn open(segment: &SegmentReader, column: &MetaColumn) -> tantivy::Result<Self> {
+        let name = column.name.as_str();
+        let fast_fields = segment.fast_fields();
+        Ok(match column.column_type {
+            api::MetaColumnType::DateTime => FieldReader::Date(fast_fields.date(name)?),
+            api::MetaColumnType::Double => FieldReader::F64(fast_fields.f64(name)?),
+            api::MetaColumnType::Long => FieldReader::I64(fast_fields.i64(name)?),
+            api::MetaColumnType::Ulong => FieldReader::U64(fast_fields.u64(name)?),
+            api::MetaColumnType::Bool => FieldReader::Bool(fast_fields.bool(name)?),
+            api::MetaColumnType::Text => {
+                return Err(tantivy::TantivyError::InvalidArgument(format!(
+                    "Text column `{name}` needs global ranks, open it with FieldReader::text"
+                )));
+            }
+            api::MetaColumnType::Tree => {
+                return Err(tantivy::TantivyError::InvalidArgument(format!(
+                    "Tree column `{name}` can only be used in has_facet() or facet_depth()"
+                )));
+            }
+            other => {
+                return Err(tantivy::TantivyError::InvalidArgument(format!(
+                    "Unsupported fast field type in virtual sort: {:?}",
+                    other
+                )));
+            }
+        })
+    }
+
+    /// Text-колонка сегмента с рангами из [`text_ranks`] для этого сегмента
+    pub fn text(segment: &SegmentReader, column: &str, ranks: Arc<[u32]>) -> tantivy::Result<Self> {
+        let column = segment.fast_fields().str(column)?;
+        Ok(FieldReader::Text { column, ranks })
+    }
+
+    pub fn has_facet(
+        segment: &SegmentReader,
+        column: &str,
+        facet: &Facet,
+    ) -> tantivy::Result<Self> {
+        let facets = segment.facet_reader(column)?;
+        let dict = facets.facet_dict();
+        let ords = if facet.is_root() {
+            0..dict.num_terms() as u64
+        } else {
+            // потомки `a\0b` лежат в словаре подряд: `a\0b\0...` < `a\0b\x01`
+            let path = facet.encoded_str().as_bytes();
+            let upper = [path, &[1]].concat();
+            let (lo, hi) =
+                dict.term_bounds_to_ord(Bound::Included(path), Bound::Excluded(&upper))?;
+            ord_range(lo, hi, dict.num_terms() as u64)
+        };
+        Ok(FieldReader::HasFacet { facets, ords })
+    }
+
+    pub fn facet_depth(segment: &SegmentReader, column: &str) -> tantivy::Result<Self> {
+        let facets = segment.facet_reader(column)?;
+        let mut depths = Vec::with_capacity(facets.num_facets());
+        let mut stream = facets.facet_dict().stream()?;
+        while stream.advance() {
+            let path = stream.key();
+            depths.push(if path.is_empty() {
+                0
+            } else {
+                path.iter().filter(|&&b| b == FACET_SEP).count() as u32 + 1
+            });
+        }
+        Ok(FieldReader::FacetDepth { facets, depths })
+    }
+
+    /// Пустое значение — NaN, его проверяют `is_null` и `coalesce`
     pub fn read_f32(&self, doc_id: DocId) -> f32 {
+        self.read_f64(doc_id).map_or(f32::NAN, |value| value as f32)
+    }
+
+    /// Первое значение документа; дата — миллисекунды от эпохи, bool — 0/1
+    pub fn read_f64(&self, doc_id: DocId) -> Option<f64> {
         match self {
             FieldReader::Date(col) => col
                 .values_for_doc(doc_id)
                 .next()
-                .map(|dt| dt.into_timestamp_millis() as f32)
-                .unwrap_or(0.0),
-            FieldReader::F64(col) => col.values_for_doc(doc_id).next().unwrap_or(0.0) as f32,
+                .map(|dt| dt.into_timestamp_millis() as f64),
+            FieldReader::F64(col) => col.values_for_doc(doc_id).next(),
+            FieldReader::I64(col) => col.values_for_doc(doc_id).next().map(|v| v as f64),
+            FieldReader::U64(col) => col.values_for_doc(doc_id).next().map(|v| v as f64),
             FieldReader::Bool(col) => col
                 .values_for_doc(doc_id)
                 .next()
-                .map(|b| if b { 1.0 } else { 0.0 })
-                .unwrap_or(0.0),
+                .map(|b| if b { 1.0 } else { 0.0 }),
+            FieldReader::Text { column, ranks } => column
+                .as_ref()
+                .and_then(|col| col.ords().first(doc_id))
+                .and_then(|ord| ranks.get(ord as usize))
+                .map(|&rank| rank as f64),
+            FieldReader::HasFacet { facets, ords } => {
+                let found = facets.facet_ords(doc_id).any(|ord| ords.contains(&ord));
+                Some(if found { 1.0 } else { 0.0 })
+            }
+            FieldReader::FacetDepth { facets, depths } => facets
+                .facet_ords(doc_id)
+                .filter_map(|ord| depths.get(ord as usize))
+                .max()
+                .map(|&depth| depth as f64),
+        }
+    }
+}
+
+/// Ранги строк text-колонки, общие для всех сегментов: для каждого сегмента —
+/// ранг строки по её ординалу в словаре сегмента. Ординалы сравнимы только внутри
+/// сегмента, поэтому словари сливаются, и одинаковые строки получают один ранг.
+/// В f32 ранги точны до 2^24 различных строк. Между запросами ранги хранит
+/// `SearchIndex::text_ranks`
+pub fn text_ranks(searcher: &Searcher, column: &str) -> tantivy::Result<TextRanks> {
+    let columns = searcher
+        .segment_readers()
+        .iter()
+        .map(|segment| segment.fast_fields().str(column))
+        .collect::<tantivy::Result<Vec<_>>>()?;
+
+    let mut streams = Vec::with_capacity(columns.len());
+    let mut ranks = Vec::with_capacity(columns.len());
+    let mut heap = BinaryHeap::new();
+    for (segment, col) in columns.iter().enumerate() {
+        let Some(col) = col else {
+            streams.push(None);
+            ranks.push(Vec::new());
+            continue;
+        };
+        let mut stream = col.dictionary().stream()?;
+        if stream.advance() {
+            heap.push(Reverse((stream.key().to_vec(), segment)));
         }
+        streams.push(Some(stream));
+        ranks.push(Vec::with_capacity(col.num_terms()));
     }
+
+    let mut rank = 0u32;
+    let mut last: Option<Vec<u8>> = None;
+    while let Some(Reverse((key, segment))) = heap.pop() {
+        if last.as_ref().is_some_and(|last| *last != key) {
+            rank += 1;
+        }
+        // ординалы словаря идут по порядку строк, поэтому ранг дописывается в конец
+        ranks[segment].push(rank);
+        if let Some(stream) = streams[segment].as_mut()
+            && stream.advance()
+        {
+            heap.push(Reverse((stream.key().to_vec(), segment)));
+        }
+        last = Some(key);
+    }
+    Ok(ranks.into_iter().map(Arc::from).collect())
+}
+
+fn ord_range(lo: Bound<u64>, hi: Bound<u64>, num_terms: u64) -> Range<u64> {
+    let start = match lo {
+        Bound::Included(ord) => ord,
+        Bound::Excluded(ord) => ord + 1,
+        Bound::Unbounded => 0,
+    };
+    let end = match hi {
+        Bound::Included(ord) => ord + 1,
+        Bound::Excluded(ord) => ord,
+        Bound::Unbounded => num_terms,
+    };
+    start..end
 }
@@ -27,7 +27,16 @@ pub fn eval_program(program: &Program, ctx: &[f32]) -> Result<f32> {
                     // унарные
                     ("exp", [x]) => Ok(x.exp()),
                     ("ln", [x]) => Ok(x.ln()),
+                    ("log10", [x]) => Ok(x.log10()),
+                    ("log1p", [x]) => Ok(x.ln_1p()),
                     ("sqrt", [x]) => Ok(x.sqrt()),
+                    ("abs", [x]) => Ok(x.abs()),
+                    ("floor", [x]) => Ok(x.floor()),
+                    ("ceil", [x]) => Ok(x.ceil()),
+                    ("round", [x]) => Ok(x.round()),
+                    ("sigmoid", [x]) => Ok(1.0 / (1.0 + (-x).exp())),
+                    ("!", [x]) => Ok(bool_value(!truthy(*x))),
+                    ("is_null", [x]) => Ok(bool_value(x.is_nan())),
 
                     // бинарные
                     ("pow", [x, y]) => Ok(x.powf(*y)),
@@ -35,6 +44,29 @@ pub fn eval_program(program: &Program, ctx: &[f32]) -> Result<f32> {
                     ("-", [x, y]) => Ok(x - y),
                     ("*", [x, y]) => Ok(x * y),
                     ("/", [x, y]) => Ok(x / y),
+                    ("%", [x, y]) => Ok(x % y),
+                    ("<", [x, y]) => Ok(bool_value(x < y)),
+                    ("<=", [x, y]) => Ok(bool_value(x <= y)),
+                    ("==", [x, y]) => Ok(bool_value(x == y)),
+                    ("!=", [x, y]) => Ok(bool_value(x != y)),
+                    (">", [x, y]) => Ok(bool_value(x > y)),
+                    (">=", [x, y]) => Ok(bool_value(x >= y)),
+                    ("&&", [x, y]) => Ok(bool_value(truthy(*x) && truthy(*y))),
+                    ("||", [x, y]) => Ok(bool_value(truthy(*x) || truthy(*y))),
+
+                    // обе ветки уже вычислены: выражения без побочных эффектов
+                    ("if", [cond, then, otherwise]) => {
+                        Ok(if truthy(*cond) { *then } else { *otherwise })
+                    }
+                    ("clamp", [x, lo, hi]) if lo <= hi => Ok(x.clamp(*lo, *hi)),
+                    ("min", [first, rest @ ..]) => Ok(rest.iter().fold(*first, |a, b| a.min(*b))),
+                    ("max", [first, rest @ ..]) => Ok(rest.iter().fold(*first, |a, b| a.max(*b))),
+                    // первое непустое значение
+                    ("coalesce", [_, _, ..]) => Ok(args
+                        .iter()
+                        .copied()
+                        .find(|x| !x.is_nan())
+                        .unwrap_or(f32::NAN)),
 
                     _ => Err(anyhow!(
                         "Unknown function or wrong args: {}({})",
@@ -52,3 +84,12 @@ pub fn eval_program(program: &Program, ctx: &[f32]) -> Result<f32> {
         .pop_back()
         .ok_or_else(|| anyhow!("Stack empty after execution"))
 }
+
+fn bool_value(value: bool) -> f32 {
+    if value { 1.0 } else { 0.0 }
+}
+
+/// Пустое значение (NaN) ложно, как и 0
+fn truthy(value: f32) -> bool {
+    value != 0.0 && !value.is_nan()
+}
@@ -2,10 +2,14 @@ use chumsky::prelude::*;
 
 use chumsky::error::Simple;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Expr {
     Number(f32),
+    /// Строка в двойных кавычках, допустима только как путь в `has_facet`
+    Text(String),
     Variable(String),
+    /// `$name`: значение из `params` запроса, подставляется при компиляции
+    Param(String),
     FunctionCall {
         name: String,
         args: Vec<Expr>,
@@ -24,23 +28,69 @@ pub enum Expr {
 #[derive(Debug, Clone, Copy)]
 pub enum UnaryOp {
     Neg,
+    /// Логическое отрицание: 1, если операнд равен 0
+    Not,
 }
 
+/// Сравнения и логические операторы возвращают 0/1, истинно всё ненулевое
 #[derive(Debug, Clone, Copy)]
 pub enum BinaryOp {
     Add,
     Sub,
     Mul,
     Div,
+    Mod,
+    Lt,
+    Le,
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    And,
+    Or,
 }
 
 impl Expr {
-    pub fn parse(input: &str) -> ParseResult<Self, Simple<char>> {
-        build_parser().parse(input)
+    pub fn parse(input: &str) -> ParseResult<Self, Simple<'_, char>> {
+        expr_parser().then_ignore(end()).parse(input)
     }
 }
 
-fn build_parser<'a>() -> impl Parser<'a, &'a str, Expr, extra::Err<Simple<'a, char>>> {
+/// Пользовательская функция: `freshness(t) = exp(-0.01 * (now_ms() - t) / 86400000)`
+#[derive(Debug, Clone)]
+pub struct FunctionDef {
+    pub name: String,
+    pub params: Vec<String>,
+    pub body: Expr,
+}
+
+impl FunctionDef {
+    pub fn parse(input: &str) -> ParseResult<Self, Simple<'_, char>> {
+        let params = text::ident()
+            .padded()
+            .map(|s: &str| s.to_string())
+            .separated_by(just(','))
+            .collect::<Vec<String>>()
+            .delimited_by(just('(').padded(), just(')').padded());
+
+        text::ident()
+            .padded()
+            .then(params)
+            .then_ignore(just('=').padded())
+            .then(expr_parser())
+            .then_ignore(end())
+            .map(
+                |((name, params), body): ((&str, Vec<String>), Expr)| FunctionDef {
+                    name: name.to_string(),
+                    params,
+                    body,
+                },
+            )
+            .parse(input)
+    }
+}
+
+fn expr_parser<'a>() -> impl Parser<'a, &'a str, Expr, extra::Err<Simple<'a, char>>> + Clone {
     recursive(|expr| {
         let number = text::int(10)
             .then(
@@ -62,6 +112,18 @@ fn build_parser<'a>() -> impl Parser<'a, &'a str, Expr, extra::Err<Simple<'a, ch
             .map(|s: &str| Expr::Variable(s.to_string()))
             .padded();
 
+        let string = none_of('"')
+            .repeated()
+            .to_slice()
+            .delimited_by(just('"'), just('"'))
+            .map(|s: &str| Expr::Text(s.to_string()))
+            .padded();
+
+        let param = just('$')
+            .ignore_then(text::ident())
+            .map(|s: &str| Expr::Param(s.to_string()))
+            .padded();
+
         let args = expr
             .clone()
             .separated_by(just(',').padded())
@@ -78,62 +140,91 @@ fn build_parser<'a>() -> impl Parser<'a, &'a str, Expr, extra::Err<Simple<'a, ch
             })
             .padded();
 
-        let atom = choice((func_call, ident, number))
+        let atom = choice((func_call, ident, param, number, string))
             .or(expr
                 .clone()
                 .delimited_by(just('(').padded(), just(')').padded()))
             .padded();
 
-        let unary = just('-')
-            .or_not()
-            .then(atom)
-            .map(
-                |(maybe_minus, expr): (Option<char>, Expr)| match maybe_minus {
-                    Some(_) => Expr::UnaryOp {
-                        op: UnaryOp::Neg,
-                        expr: Box::new(expr),
-                    },
-                    None => expr,
-                },
-            )
-            .padded();
+        // приоритет по убыванию: унарные, `* / %`, `+ -`, сравнения, `&&`, `||`
+        let unary = recursive(|unary| {
+            just('-')
+                .to(UnaryOp::Neg)
+                .or(just('!').to(UnaryOp::Not))
+                .padded()
+                .then(unary)
+                .map(|(op, expr): (UnaryOp, Expr)| Expr::UnaryOp {
+                    op,
+                    expr: Box::new(expr),
+                })
+                .or(atom)
+        })
+        .padded();
 
         let op_mul_div = just('*')
-            .padded()
             .to(BinaryOp::Mul)
-            .or(just('/').padded().to(BinaryOp::Div))
-            .map(|op: BinaryOp| op)
+            .or(just('/').to(BinaryOp::Div))
+            .or(just('%').to(BinaryOp::Mod))
             .padded();
 
         let product = unary
             .clone()
-            .foldl(op_mul_div.then(unary).repeated(), |lhs, (op, rhs)| {
-                Expr::BinaryOp {
-                    op,
-                    lhs: Box::new(lhs),
-                    rhs: Box::new(rhs),
-                }
-            })
+            .foldl(op_mul_div.then(unary).repeated(), fold_binary)
             .padded();
 
         let op_add_sub = just('+')
-            .padded()
             .to(BinaryOp::Add)
-            .or(just('-').padded().to(BinaryOp::Sub))
-            .map(|op: BinaryOp| op)
+            .or(just('-').to(BinaryOp::Sub))
             .padded();
 
         let sum = product
             .clone()
-            .foldl(op_add_sub.then(product).repeated(), |lhs, (op, rhs)| {
-                Expr::BinaryOp {
-                    op,
-                    lhs: Box::new(lhs),
-                    rhs: Box::new(rhs),
-                }
-            });
+            .foldl(op_add_sub.then(product).repeated(), fold_binary)
+            .padded();
 
-        sum
+        // двухсимвольные операторы раньше односимвольных
+        let op_cmp = choice((
+            just("<=").to(BinaryOp::Le),
+            just(">=").to(BinaryOp::Ge),
+            just("==").to(BinaryOp::Eq),
+            just("!=").to(BinaryOp::Ne),
+            just("<").to(BinaryOp::Lt),
+            just(">").to(BinaryOp::Gt),
+        ))
+        .padded();
+
+        let comparison = sum
+            .clone()
+            .foldl(op_cmp.then(sum).repeated(), fold_binary)
+            .padded();
+
+        let conjunction = comparison
+            .clone()
+            .foldl(
+                just("&&")
+                    .to(BinaryOp::And)
+                    .padded()
+                    .then(comparison)
+                    .repeated(),
+                fold_binary,
+            )
+            .padded();
+
+        conjunction.clone().foldl(
+            just("||")
+                .to(BinaryOp::Or)
+                .padded()
+                .then(conjunction)
+                .repeated(),
+            fold_binary,
+        )
     })
-    .then_ignore(end())
+}
+
+fn fold_binary(lhs: Expr, (op, rhs): (BinaryOp, Expr)) -> Expr {
+    Expr::BinaryOp {
+        op,
+        lhs: Box::new(lhs),
+        rhs: Box::new(rhs),
+    }
 }
@@ -0,0 +1,254 @@
+use anyhow::{Result, anyhow, bail};
+use std::collections::HashMap;
+
+use crate::engine::virtual_sort::expr::{Expr, FunctionDef};
+
+/// Верхняя граница числа аргументов у `min`, `max` и `coalesce`
+const VARIADIC: usize = usize::MAX;
+
+/// Сколько узлов может добавить раскрытие пользовательских функций: вложенные вызовы
+/// удваивают выражение на каждом уровне
+const MAX_INLINED_NODES: usize = 10_000;
+
+/// Функции, которые вычисляются самим `eval_program` или компилятором,
+/// с минимальным и максимальным числом аргументов
+const BUILTINS: &[(&str, usize, usize)] = &[
+    ("exp", 1, 1),
+    ("ln", 1, 1),
+    ("log10", 1, 1),
+    ("log1p", 1, 1),
+    ("sqrt", 1, 1),
+    ("abs", 1, 1),
+    ("floor", 1, 1),
+    ("ceil", 1, 1),
+    ("round", 1, 1),
+    ("sigmoid", 1, 1),
+    ("pow", 2, 2),
+    ("if", 3, 3),
+    ("clamp", 3, 3),
+    ("min", 1, VARIADIC),
+    ("max", 1, VARIADIC),
+    ("is_null", 1, 1),
+    ("coalesce", 2, VARIADIC),
+    ("now_ms", 0, 0),
+    ("bm25", 1, 1),
+    ("has_facet", 2, 2),
+    ("facet_depth", 1, 1),
+];
+
+fn is_builtin(name: &str) -> bool {
+    BUILTINS.iter().any(|(builtin, ..)| *builtin == name)
+}
+
+/// Вызов встроенной функции: имя известно, число аргументов подходит
+fn check_builtin_call(name: &str, n_args: usize) -> Result<()> {
+    let Some(&(_, min, max)) = BUILTINS.iter().find(|(builtin, ..)| *builtin == name) else {
+        bail!("Unknown function '{name}'");
+    };
+    if n_args < min || n_args > max {
+        let expected = if max == VARIADIC {
+            format!("at least {min}")
+        } else {
+            min.to_string()
+        };
+        bail!("Function '{name}' expects {expected} argument(s), got {n_args}");
+    }
+    Ok(())
+}
+
+/// Пользовательские функции из `SearchRequest.functions`, подставляются в выражение
+/// до компиляции
+#[derive(Debug, Default)]
+pub struct Functions {
+    defs: HashMap<String, FunctionDef>,
+}
+
+impl Functions {
+    /// Разбирает определения и сразу проверяет их тела: рекурсию, имена и число
+    /// аргументов во вложенных вызовах
+    pub fn parse(sources: &[String]) -> Result<Self> {
+        let mut functions = Functions::default();
+        for source in sources {
+            let def = FunctionDef::parse(source).into_result().map_err(|errs| {
+                anyhow!(
+                    "Failed to parse function definition `{source}`: {}",
+                    errs.into_iter()
+                        .map(|e| e.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            })?;
+            if is_builtin(&def.name) {
+                bail!("Function '{}' shadows a built-in", def.name);
+            }
+            for (i, param) in def.params.iter().enumerate() {
+                if def.params[..i].contains(param) {
+                    bail!("Function '{}' repeats parameter '{param}'", def.name);
+                }
+            }
+            if functions.defs.contains_key(&def.name) {
+                bail!("Function '{}' is defined twice", def.name);
+            }
+            functions.defs.insert(def.name.clone(), def);
+        }
+
+        for def in functions.defs.values() {
+            Inliner::new(&functions, &def.name).inline(def.body.clone())?;
+        }
+        Ok(functions)
+    }
+
+    /// Раскрывает вызовы пользовательских функций в `expr` и проверяет оставшиеся
+    /// вызовы встроенных, чтобы опечатка не превращалась в ошибку на каждом документе
+    pub fn inline(&self, expr: Expr) -> Result<Expr> {
+        Inliner {
+            functions: self,
+            stack: Vec::new(),
+            nodes: 0,
+        }
+        .inline(expr)
+    }
+}
+
+struct Inliner<'a> {
+    functions: &'a Functions,
+    /// Цепочка раскрываемых функций, для поиска рекурсии
+    stack: Vec<&'a str>,
+    /// Узлов, созданных подстановкой аргументов, не больше [`MAX_INLINED_NODES`]
+    nodes: usize,
+}
+
+impl<'a> Inliner<'a> {
+    fn new(functions: &'a Functions, root: &'a str) -> Self {
+        Self {
+            functions,
+            stack: vec![root],
+            nodes: 0,
+        }
+    }
+
+    fn inline(&mut self, expr: Expr) -> Result<Expr> {
+        Ok(match expr {
+            Expr::FunctionCall { name, args } => {
+                let args = args
+                    .into_iter()
+                    .map(|arg| self.inline(arg))
+                    .collect::<Result<Vec<_>>>()?;
+                let Some(def) = self.functions.defs.get(&name) else {
+                    check_builtin_call(&name, args.len())?;
+                    return Ok(Expr::FunctionCall { name, args });
+                };
+                if args.len() != def.params.len() {
+                    bail!(
+                        "Function '{name}' expects {} argument(s), got {}",
+                        def.params.len(),
+                        args.len()
+                    );
+                }
+                if self.stack.contains(&def.name.as_str()) {
+                    bail!(
+                        "Recursive function call: {} -> {name}",
+                        self.stack.join(" -> ")
+                    );
+                }
+
+                // подставляем аргументы до раскрытия вложенных вызовов, иначе параметр
+                // перехватил бы одноимённую колонку из тела вызываемой функции
+                self.stack.push(&def.name);
+                let args: Vec<_> = args
+                    .into_iter()
+                    .map(|arg| (node_count(&arg), arg))
+                    .collect();
+                let body = self.substitute(def.body.clone(), &def.params, &args)?;
+                let body = self.inline(body)?;
+                self.stack.pop();
+                body
+            }
+            Expr::UnaryOp { op, expr } => Expr::UnaryOp {
+                op,
+                expr: Box::new(self.inline(*expr)?),
+            },
+            Expr::BinaryOp { op, lhs, rhs } => Expr::BinaryOp {
+                op,
+                lhs: Box::new(self.inline(*lhs)?),
+                rhs: Box::new(self.inline(*rhs)?),
+            },
+            expr @ (Expr::Number(_) | Expr::Text(_) | Expr::Variable(_) | Expr::Param(_)) => expr,
+        })
+    }
+
+    /// Заменяет параметры аргументами; остальные переменные остаются колонками.
+    /// `args` — аргументы вместе с числом их узлов
+    fn substitute(
+        &mut self,
+        expr: Expr,
+        params: &[String],
+        args: &[(usize, Expr)],
+    ) -> Result<Expr> {
+        Ok(match expr {
+            Expr::Variable(name) => match params.iter().position(|p| *p == name) {
+                Some(i) => {
+                    let (nodes, arg) = &args[i];
+                    self.grow(*nodes)?;
+                    arg.clone()
+                }
+                None => {
+                    self.grow(1)?;
+                    Expr::Variable(name)
+                }
+            },
+            Expr::FunctionCall {
+                name,
+                args: call_args,
+            } => {
+                self.grow(1)?;
+                Expr::FunctionCall {
+                    name,
+                    args: call_args
+                        .into_iter()
+                        .map(|arg| self.substitute(arg, params, args))
+                        .collect::<Result<_>>()?,
+                }
+            }
+            Expr::UnaryOp { op, expr } => {
+                self.grow(1)?;
+                Expr::UnaryOp {
+                    op,
+                    expr: Box::new(self.substitute(*expr, params, args)?),
+                }
+            }
+            Expr::BinaryOp { op, lhs, rhs } => {
+                self.grow(1)?;
+                Expr::BinaryOp {
+                    op,
+                    lhs: Box::new(self.substitute(*lhs, params, args)?),
+                    rhs: Box::new(self.substitute(*rhs, params, args)?),
+                }
+            }
+            expr @ (Expr::Number(_) | Expr::Text(_) | Expr::Param(_)) => {
+                self.grow(1)?;
+                expr
+            }
+        })
+    }
+
+    fn grow(&mut self, nodes: usize) -> Result<()> {
+        self.nodes += nodes;
+        if self.nodes > MAX_INLINED_NODES {
+            bail!(
+                "Inlining {} expands the expression beyond {MAX_INLINED_NODES} nodes",
+                self.stack.join(" -> ")
+            );
+        }
+        Ok(())
+    }
+}
+
+fn node_count(expr: &Expr) -> usize {
+    match expr {
+        Expr::FunctionCall { args, .. } => 1 + args.iter().map(node_count).sum::<usize>(),
+        Expr::UnaryOp { expr, .. } => 1 + node_count(expr),
+        Expr::BinaryOp { lhs, rhs, .. } => 1 + node_count(lhs) + node_count(rhs),
+        Expr::Number(_) | Expr::Text(_) | Expr::Variable(_) | Expr::Param(_) => 1,
+    }
+}
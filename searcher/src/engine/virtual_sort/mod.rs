@@ -1,11 +1,22 @@
 pub mod collector;
 pub mod eval;
 pub mod expr;
+pub mod functions;
+pub mod params;
 pub mod program;
 
 #[cfg(test)]
 mod tests {
-    use crate::engine::virtual_sort::{eval::eval_program, expr::Expr, program::Program};
+    use crate::engine::virtual_sort::{
+        eval::eval_program,
+        expr::Expr,
+        functions::Functions,
+        params::bind_params,
+        program::{Program, VarSource},
+    };
+    use std::collections::HashMap;
+
+    type Case<'a> = (&'a str, f32, &'a [(&'a str, f32)]);
 
     fn exec(src: &str, ctx_map: &[(&str, f32)]) -> f32 {
         let expr = Expr::parse(src).unwrap();
@@ -80,7 +91,7 @@ mod tests {
 
     #[test]
     fn test_multiple_variable_usage() {
-        let cases: Vec<(&str, f32, &[(&str, f32)])> = vec![
+        let cases: Vec<Case> = vec![
             ("x + x", 6.0, &[("x", 3.0)]),
             ("x + y + x", 11.0, &[("x", 4.0), ("y", 3.0)]),
             ("x * x + y", 19.0, &[("x", 4.0), ("y", 3.0)]),
@@ -88,7 +99,7 @@ mod tests {
             ("x + y + z + x", 18.0, &[("x", 5.0), ("y", 4.0), ("z", 4.0)]),
             ("x * y + x * y", 30.0, &[("x", 3.0), ("y", 5.0)]),
             ("pow(x + y, 2)", 49.0, &[("x", 3.0), ("y", 4.0)]),
-            ("sqrt(x * x + x * x)", (18.0 as f32).sqrt(), &[("x", 3.0)]),
+            ("sqrt(x * x + x * x)", 18.0f32.sqrt(), &[("x", 3.0)]),
             ("exp(x) + exp(x)", 2.0 * 3.0f32.exp(), &[("x", 3.0)]),
         ];
 
@@ -100,4 +111,203 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_score_variables() {
+        let program = Program::compile_expr(Expr::parse("0.7 * _score + bm25(title)").unwrap());
+        assert_eq!(program.env, ["_score", "bm25(title)"]);
+        assert!(program.requires_scoring());
+
+        let program = Program::compile_expr(Expr::parse("bm25(title) - price").unwrap());
+        assert!(!program.requires_scoring());
+    }
+
+    #[test]
+    fn test_comparisons_and_logic() {
+        let cases: Vec<Case> = vec![
+            ("1 < 2", 1.0, &[]),
+            ("2 <= 1", 0.0, &[]),
+            ("x == 3", 1.0, &[("x", 3.0)]),
+            ("x != 3", 0.0, &[("x", 3.0)]),
+            ("x > 2 && x < 4", 1.0, &[("x", 3.0)]),
+            ("x > 5 || x < 1", 0.0, &[("x", 3.0)]),
+            ("!x", 1.0, &[("x", 0.0)]),
+            ("!(x > 1)", 0.0, &[("x", 3.0)]),
+            ("!!x", 1.0, &[("x", 5.0)]),
+            ("-x * 2", -6.0, &[("x", 3.0)]),
+            // приоритеты: арифметика, сравнения, && и только потом ||
+            ("1 + 2 > 2 * 1", 1.0, &[]),
+            ("0 || 1 && 0", 0.0, &[]),
+            ("1 || 0 && 0", 1.0, &[]),
+            ("1 < 2 == 1", 1.0, &[]),
+            ("7 % 4 + 1", 4.0, &[]),
+            ("2 * 7 % 4", 2.0, &[]),
+        ];
+
+        for (src, expected, ctx_map) in cases {
+            let result = exec(src, ctx_map);
+            assert_eq!(result, expected, "Expr `{src}`");
+        }
+    }
+
+    #[test]
+    fn test_math_builtins() {
+        let cases: Vec<Case> = vec![
+            ("if(x > 1, 10, 20)", 10.0, &[("x", 3.0)]),
+            ("if(x, 10, 20)", 20.0, &[("x", 0.0)]),
+            ("min(3, x, 2)", 1.0, &[("x", 1.0)]),
+            ("max(3, x)", 5.0, &[("x", 5.0)]),
+            ("abs(-2.5)", 2.5, &[]),
+            ("log10(1000)", 3.0, &[]),
+            ("log1p(0)", 0.0, &[]),
+            ("sigmoid(0)", 0.5, &[]),
+            ("clamp(x, 0, 1)", 1.0, &[("x", 7.0)]),
+            ("clamp(-x, 0, 1)", 0.0, &[("x", 7.0)]),
+            ("floor(2.7) + ceil(2.1) + round(2.5)", 8.0, &[]),
+        ];
+
+        for (src, expected, ctx_map) in cases {
+            let result = exec(src, ctx_map);
+            assert!(
+                (result - expected).abs() < 1e-6,
+                "Expr `{src}` → expected {expected}, got {result}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_invalid_calls_fail() {
+        for src in ["clamp(1, 2, 0)", "min()", "if(1, 2)", "abs(1, 2)"] {
+            let program = Program::compile_expr(Expr::parse(src).unwrap());
+            assert!(eval_program(&program, &[]).is_err(), "Expr `{src}`");
+        }
+        for src in ["1 <", "x && ", "1 ! 2", "a ||| b"] {
+            assert!(Expr::parse(src).into_result().is_err(), "Expr `{src}`");
+        }
+    }
+
+    fn exec_with(functions: &[&str], src: &str, ctx: &[f32]) -> anyhow::Result<f32> {
+        let functions =
+            Functions::parse(&functions.iter().map(|f| f.to_string()).collect::<Vec<_>>())?;
+        let program = Program::compile_expr(functions.inline(Expr::parse(src).unwrap())?);
+        eval_program(&program, ctx)
+    }
+
+    #[test]
+    fn test_user_functions() {
+        let functions = [
+            "sq(x) = x * x",
+            "hyp(a, b) = sqrt(sq(a) + sq(b))",
+            "bonus() = if(price > 10, 1, 0)",
+            // параметр `x` не должен перехватывать колонку `x` в теле sq
+            "shift(x) = sq(2) + x",
+        ];
+        assert_eq!(exec_with(&functions, "hyp(3, 4)", &[]).unwrap(), 5.0);
+        assert_eq!(exec_with(&functions, "hyp(x, 4)", &[3.0]).unwrap(), 5.0);
+        assert_eq!(
+            exec_with(&functions, "bonus() + price", &[20.0]).unwrap(),
+            21.0
+        );
+        assert_eq!(exec_with(&functions, "shift(1)", &[]).unwrap(), 5.0);
+    }
+
+    #[test]
+    fn test_user_function_errors() {
+        let err = |functions: &[&str], src: &str| {
+            exec_with(functions, src, &[0.0]).unwrap_err().to_string()
+        };
+
+        assert!(err(&["f(x) = g(x)", "g(y) = f(y) + 1"], "1").contains("Recursive"));
+        assert!(err(&["f(x) = f(x)"], "1").contains("Recursive"));
+        assert!(err(&["f(x) = x"], "f(1, 2)").contains("expects 1 argument"));
+        assert!(err(&["f(x) = x", "g() = f()"], "1").contains("expects 1 argument"));
+        assert!(err(&["exp(x) = x"], "1").contains("built-in"));
+        assert!(err(&["coalesce(x, y) = x"], "1").contains("built-in"));
+        assert!(err(&["is_null(x) = 0"], "1").contains("built-in"));
+        assert!(err(&["f(x) = x", "f(y) = y"], "1").contains("twice"));
+        assert!(err(&["f(x, x) = x"], "1").contains("repeats"));
+        assert!(err(&["f(x) == x"], "1").contains("parse"));
+        // встроенные проверяются при компиляции, а не на каждом документе
+        assert!(err(&[], "exp(1, 2)").contains("'exp' expects 1 argument"));
+        assert!(err(&[], "sigmod(x)").contains("Unknown function 'sigmod'"));
+        assert!(err(&[], "coalesce(x)").contains("at least 2"));
+        assert!(err(&["f(x) = sigmod(x)"], "1").contains("Unknown function"));
+    }
+
+    #[test]
+    fn test_user_function_expansion_limit() {
+        let nested = |depth: usize| format!("{}1{}", "f(".repeat(depth), ")".repeat(depth));
+        let functions = ["f(x) = x + x"];
+        assert_eq!(exec_with(&functions, &nested(8), &[]).unwrap(), 256.0);
+        let err = exec_with(&functions, &nested(40), &[]).unwrap_err();
+        assert!(err.to_string().contains("beyond 10000 nodes"), "{err}");
+
+        // то же удвоение через цепочку определений ловится при их разборе
+        let chain: Vec<_> = (1..40)
+            .map(|i| format!("f{i}(x) = f{}(x) + f{}(x)", i - 1, i - 1))
+            .chain(["f0(x) = x".to_string()])
+            .collect();
+        let chain: Vec<_> = chain.iter().map(String::as_str).collect();
+        let err = exec_with(&chain, "1", &[]).unwrap_err();
+        assert!(err.to_string().contains("beyond 10000 nodes"), "{err}");
+    }
+
+    #[test]
+    fn test_params_and_now() {
+        let params = HashMap::from([("w".to_string(), 2.0), ("floor".to_string(), 5.0)]);
+        let bind = |src: &str| bind_params(Expr::parse(src).unwrap(), &params);
+
+        let program = Program::compile_expr(bind("max($floor, price * $w)").unwrap());
+        assert_eq!(eval_program(&program, &[1.0]).unwrap(), 5.0);
+        assert_eq!(eval_program(&program, &[4.0]).unwrap(), 8.0);
+        assert!(
+            bind("$missing + 1")
+                .unwrap_err()
+                .to_string()
+                .contains("$missing")
+        );
+
+        let now = chrono::DateTime::from_timestamp_millis(3_000_000).unwrap();
+        let program = Program::compile_expr_at(Expr::parse("now_ms() / 1000").unwrap(), now);
+        assert_eq!(eval_program(&program, &[]).unwrap(), 3000.0);
+    }
+
+    #[test]
+    fn test_facet_vars() {
+        let src = r#"has_facet(category, "/a, b/c") + facet_depth(category)"#;
+        let program = Program::compile_expr(Expr::parse(src).unwrap());
+        let sources: Vec<_> = program.env.iter().map(|v| VarSource::of(v)).collect();
+        assert_eq!(
+            sources,
+            [
+                VarSource::HasFacet {
+                    column: "category",
+                    path: "/a, b/c"
+                },
+                VarSource::FacetDepth("category"),
+            ]
+        );
+        assert!(
+            Expr::parse(r#"has_facet(category, "/a)"#)
+                .into_result()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_null_handling() {
+        let null = f32::NAN;
+        assert_eq!(exec("is_null(x)", &[("x", null)]), 1.0);
+        assert_eq!(exec("is_null(x)", &[("x", 0.0)]), 0.0);
+        assert_eq!(exec("coalesce(x, 7)", &[("x", null)]), 7.0);
+        assert_eq!(exec("coalesce(x, 7)", &[("x", 0.0)]), 0.0);
+        assert!(exec("coalesce(x, x)", &[("x", null)]).is_nan());
+        assert!(exec("x * 2 + 1", &[("x", null)]).is_nan());
+
+        // пустое значение ложно
+        assert_eq!(exec("if(x, 1, 2)", &[("x", null)]), 2.0);
+        assert_eq!(exec("!x", &[("x", null)]), 1.0);
+        assert_eq!(exec("x || 0", &[("x", null)]), 0.0);
+        assert_eq!(exec("x > 0", &[("x", null)]), 0.0);
+    }
 }
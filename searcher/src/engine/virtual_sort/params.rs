@@ -0,0 +1,31 @@
+use anyhow::{Result, bail};
+use std::collections::HashMap;
+
+use crate::engine::virtual_sort::expr::Expr;
+
+/// Подставляет `$name` из `params` запроса как константы
+pub fn bind_params(expr: Expr, params: &HashMap<String, f64>) -> Result<Expr> {
+    Ok(match expr {
+        Expr::Param(name) => match params.get(&name) {
+            Some(value) => Expr::Number(*value as f32),
+            None => bail!("Unknown parameter ${name}, pass it in `params`"),
+        },
+        Expr::FunctionCall { name, args } => Expr::FunctionCall {
+            name,
+            args: args
+                .into_iter()
+                .map(|arg| bind_params(arg, params))
+                .collect::<Result<_>>()?,
+        },
+        Expr::UnaryOp { op, expr } => Expr::UnaryOp {
+            op,
+            expr: Box::new(bind_params(*expr, params)?),
+        },
+        Expr::BinaryOp { op, lhs, rhs } => Expr::BinaryOp {
+            op,
+            lhs: Box::new(bind_params(*lhs, params)?),
+            rhs: Box::new(bind_params(*rhs, params)?),
+        },
+        expr @ (Expr::Number(_) | Expr::Text(_) | Expr::Variable(_)) => expr,
+    })
+}
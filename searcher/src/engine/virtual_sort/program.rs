@@ -1,3 +1,5 @@
+use chrono::{DateTime, Utc};
+
 use crate::engine::virtual_sort::expr::{BinaryOp, Expr, UnaryOp};
 
 #[derive(Debug, Clone)]
@@ -7,6 +9,47 @@ pub enum OpCode {
     CallFunction { name: String, n_arg: usize },
 }
 
+/// Переменная с BM25 score документа по запросу `filter`
+pub const SCORE_VAR: &str = "_score";
+
+/// Откуда берётся значение переменной программы
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VarSource<'a> {
+    /// `_score`
+    Score,
+    /// `bm25(col)`: BM25 запроса `filter` только по full-text полю колонки
+    Bm25(&'a str),
+    /// `has_facet(col, "/path")`: 1, если у документа есть путь или его потомок
+    HasFacet { column: &'a str, path: &'a str },
+    /// `facet_depth(col)`: глубина самого длинного пути документа
+    FacetDepth(&'a str),
+    /// fast-колонка
+    Column(&'a str),
+}
+
+impl<'a> VarSource<'a> {
+    pub fn of(var: &'a str) -> Self {
+        if var == SCORE_VAR {
+            return VarSource::Score;
+        }
+        let call = |prefix: &str| {
+            var.strip_prefix(prefix)
+                .and_then(|rest| rest.strip_suffix(')'))
+        };
+        if let Some(column) = call("bm25(") {
+            return VarSource::Bm25(column);
+        }
+        if let Some(column) = call("facet_depth(") {
+            return VarSource::FacetDepth(column);
+        }
+        // имя колонки без запятых, поэтому путь — всё после первой
+        match call("has_facet(").and_then(|args| args.split_once(',')) {
+            Some((column, path)) => VarSource::HasFacet { column, path },
+            None => VarSource::Column(var),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Program {
     pub ops: Vec<OpCode>,
@@ -15,48 +58,90 @@ pub struct Program {
 
 impl Program {
     pub fn compile_expr(expr: Expr) -> Self {
+        Self::compile_expr_at(expr, chrono::Utc::now())
+    }
+
+    /// Компиляция с заданным моментом для `now_ms()`: ранжирование воспроизводимо
+    pub fn compile_expr_at(expr: Expr, now: DateTime<Utc>) -> Self {
         let mut ops = Vec::new();
         let mut env = Vec::new();
-        Self::compile_expr_rec(expr, &mut ops, &mut env);
+        Self::compile_expr_rec(expr, now, &mut ops, &mut env);
         Self { ops, env }
     }
 
-    fn compile_expr_rec(expr: Expr, ops: &mut Vec<OpCode>, env: &mut Vec<String>) {
+    /// Нужен ли коллектору score документа
+    pub fn requires_scoring(&self) -> bool {
+        self.env
+            .iter()
+            .any(|var| VarSource::of(var) == VarSource::Score)
+    }
+
+    fn push_variable(name: String, ops: &mut Vec<OpCode>, env: &mut Vec<String>) {
+        let idx = match env.iter().position(|v| v == &name) {
+            Some(i) => i,
+            None => {
+                env.push(name);
+                env.len() - 1
+            }
+        };
+        ops.push(OpCode::PushVariable(idx));
+    }
+
+    fn compile_expr_rec(
+        expr: Expr,
+        now: DateTime<Utc>,
+        ops: &mut Vec<OpCode>,
+        env: &mut Vec<String>,
+    ) {
         match expr {
             Expr::Number(n) => ops.push(OpCode::PushNumber(n)),
 
             Expr::Variable(name) => {
                 tracing::debug!(var = %name, "Compiling Expr::Variable");
-                let idx = match env.iter().position(|v| v == &name) {
-                    Some(i) => i,
-                    None => {
-                        env.push(name.clone());
-                        env.len() - 1
-                    }
-                };
-                ops.push(OpCode::PushVariable(idx));
+                Self::push_variable(name, ops, env);
             }
 
-            // Специальная обработка now_ms()
-            Expr::FunctionCall { ref name, ref args } if name == "now_ms" && args.is_empty() => {
-                let now_ms = chrono::Utc::now().timestamp_millis() as f32;
-                tracing::debug!(now_ms, "Replacing now_ms() with constant");
-                ops.push(OpCode::PushNumber(now_ms));
-            }
+            // строка вне has_facet: поиск отклонит программу по кавычкам в имени
+            Expr::Text(text) => Self::push_variable(format!("\"{text}\""), ops, env),
 
-            Expr::FunctionCall { name, args } => {
-                let args_len = args.len();
-                for arg in args {
-                    Self::compile_expr_rec(arg, ops, env);
+            // параметры подставляет bind_params; несвязанный останется неизвестной колонкой
+            Expr::Param(name) => Self::push_variable(format!("${name}"), ops, env),
+
+            Expr::FunctionCall { name, args } => match (name.as_str(), args.as_slice()) {
+                // bm25(col) читается коллектором как отдельная переменная
+                ("bm25", [Expr::Variable(column)]) => {
+                    Self::push_variable(format!("bm25({column})"), ops, env);
                 }
-                ops.push(OpCode::CallFunction {
-                    name,
-                    n_arg: args_len,
-                });
-            }
+
+                // фасетные предикаты тоже читаются коллектором из fast-поля колонки
+                ("has_facet", [Expr::Variable(column), Expr::Text(path)]) => {
+                    Self::push_variable(format!("has_facet({column},{path})"), ops, env);
+                }
+                ("facet_depth", [Expr::Variable(column)]) => {
+                    Self::push_variable(format!("facet_depth({column})"), ops, env);
+                }
+
+                // Специальная обработка now_ms()
+                ("now_ms", []) => {
+                    let now_ms = now.timestamp_millis() as f32;
+                    tracing::debug!(now_ms, "Replacing now_ms() with constant");
+                    ops.push(OpCode::PushNumber(now_ms));
+                }
+
+                _ => {
+                    let args_len = args.len();
+                    for arg in args {
+                        Self::compile_expr_rec(arg, now, ops, env);
+                    }
+                    ops.push(OpCode::CallFunction {
+                        name,
+                        n_arg: args_len,
+                    });
+                }
+            },
 
             Expr::UnaryOp { op, expr } => {
-                Self::compile_expr_rec(*expr, ops, env);
+                Self::compile_expr_rec(*expr, now, ops, env);
                 match op {
                     UnaryOp::Neg => {
                         ops.push(OpCode::PushNumber(-1.0));
@@ -65,17 +150,30 @@ impl Program {
                             n_arg: 2,
                         });
                     }
+                    UnaryOp::Not => ops.push(OpCode::CallFunction {
+                        name: "!".to_string(),
+                        n_arg: 1,
+                    }),
                 }
             }
 
             Expr::BinaryOp { op, lhs, rhs } => {
-                Self::compile_expr_rec(*lhs, ops, env);
-                Self::compile_expr_rec(*rhs, ops, env);
+                Self::compile_expr_rec(*lhs, now, ops, env);
+                Self::compile_expr_rec(*rhs, now, ops, env);
                 let name = match op {
                     BinaryOp::Add => "+",
                     BinaryOp::Sub => "-",
                     BinaryOp::Mul => "*",
                     BinaryOp::Div => "/",
+                    BinaryOp::Mod => "%",
+                    BinaryOp::Lt => "<",
+                    BinaryOp::Le => "<=",
+                    BinaryOp::Eq => "==",
+                    BinaryOp::Ne => "!=",
+                    BinaryOp::Gt => ">",
+                    BinaryOp::Ge => ">=",
+                    BinaryOp::And => "&&",
+                    BinaryOp::Or => "||",
                 };
                 ops.push(OpCode::CallFunction {
                     name: name.to_string(),
@@ -0,0 +1,248 @@
+mod common;
+
+use common::{add_documents, document, open_index};
+use searcher::api::SearchRequest;
+use searcher::domain::index::SearchIndex;
+use searcher::engine::response::build_search_response;
+use searcher::engine::search::execute_search;
+use serde_json::{Value, json};
+
+fn aggs(index: &SearchIndex, where_filter: Value, aggs: Value) -> anyhow::Result<Value> {
+    let req: SearchRequest = serde_json::from_value(json!({
+        "select": ["id"],
+        "from": "products",
+        "where": where_filter,
+        "limit": 1,
+        "aggs": aggs,
+    }))
+    .unwrap();
+    let hits = execute_search(index, &req)?;
+    let response = build_search_response(index, &hits, &req)?;
+    Ok(serde_json::to_value(response.aggs)?)
+}
+
+#[tokio::test]
+async fn test_numeric_aggregations_over_matched_docs() {
+    let dir = tempfile::tempdir().unwrap();
+    let index = open_index(dir.path()).await;
+
+    let result = aggs(
+        &index,
+        json!({"facet": {"field": "category", "path": "/laptops"}}),
+        json!({
+            "price_stats": {"stats": {"field": "price"}},
+            "price_hist": {"histogram": {"field": "price", "interval": 250}},
+            "price_ranges": {"range": {"field": "price", "ranges": [
+                {"to": 1500},
+                {"key": "premium", "from": 1500}
+            ]}}
+        }),
+    )
+    .unwrap();
+
+    // ноутбуки: 1999, 1499 и документ без цены, который в агрегации не попадает
+    assert_eq!(
+        result,
+        json!({
+            "price_stats": {"count": 2, "min": 1499.0, "max": 1999.0, "avg": 1749.0, "sum": 3498.0},
+            "price_hist": {"buckets": [
+                {"key": 1250.0, "from": 1250.0, "to": 1500.0, "doc_count": 1},
+                {"key": 1500.0, "from": 1500.0, "to": 1750.0, "doc_count": 0},
+                {"key": 1750.0, "from": 1750.0, "to": 2000.0, "doc_count": 1}
+            ]},
+            "price_ranges": {"buckets": [
+                {"key": "*-1500", "to": 1500.0, "doc_count": 1},
+                {"key": "premium", "from": 1500.0, "doc_count": 1}
+            ]}
+        })
+    );
+
+    let result = aggs(
+        &index,
+        json!({"term": {"field": "brand", "value": "nobody"}}),
+        json!({"price_stats": {"stats": {"field": "price"}}}),
+    )
+    .unwrap();
+    assert_eq!(
+        result,
+        json!({"price_stats": {"count": 0, "min": null, "max": null, "avg": null, "sum": 0.0}})
+    );
+}
+
+#[tokio::test]
+async fn test_numeric_aggregations_over_integer_column() {
+    let dir = tempfile::tempdir().unwrap();
+    let index = open_index(dir.path()).await;
+
+    let result = aggs(
+        &index,
+        Value::Null,
+        json!({
+            "rank_stats": {"stats": {"field": "rank_position"}},
+            "rank_hist": {"histogram": {"field": "rank_position", "interval": 2}},
+            "rank_ranges": {"range": {"field": "rank_position", "ranges": [{"to": 3}, {"from": 3}]}}
+        }),
+    )
+    .unwrap();
+
+    // позиции документов 1..3: 4, 3 и 2, у документа 4 позиции нет
+    assert_eq!(
+        result,
+        json!({
+            "rank_stats": {"count": 3, "min": 2.0, "max": 4.0, "avg": 3.0, "sum": 9.0},
+            "rank_hist": {"buckets": [
+                {"key": 2.0, "from": 2.0, "to": 4.0, "doc_count": 2},
+                {"key": 4.0, "from": 4.0, "to": 6.0, "doc_count": 1}
+            ]},
+            "rank_ranges": {"buckets": [
+                {"key": "*-3", "to": 3.0, "doc_count": 1},
+                {"key": "3-*", "from": 3.0, "doc_count": 2}
+            ]}
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_date_aggregations() {
+    let dir = tempfile::tempdir().unwrap();
+    let index = open_index(dir.path()).await;
+
+    let result = aggs(
+        &index,
+        json!({"terms": {"field": "id", "values": ["1", "3"]}}),
+        json!({
+            "by_month": {"date_histogram": {"field": "created_at", "interval": "month"}},
+            "posted": {"range": {"field": "created_at", "ranges": [
+                {"key": "q1", "from": "2025-01-01T00:00:00Z", "to": "2025-04-01T00:00:00Z"},
+                {"key": "recent", "from": "now-1w"}
+            ]}}
+        }),
+    )
+    .unwrap();
+
+    assert_eq!(
+        result["by_month"],
+        json!({"buckets": [
+            {"key": "2025-01-01T00:00:00.000Z", "from": 1735689600000.0, "to": 1738368000000.0, "doc_count": 1},
+            {"key": "2025-02-01T00:00:00.000Z", "from": 1738368000000.0, "to": 1740787200000.0, "doc_count": 0},
+            {"key": "2025-03-01T00:00:00.000Z", "from": 1740787200000.0, "to": 1743465600000.0, "doc_count": 1}
+        ]})
+    );
+    assert_eq!(result["posted"]["buckets"][0]["doc_count"], 2);
+    assert_eq!(result["posted"]["buckets"][1]["doc_count"], 0);
+}
+
+#[tokio::test]
+async fn test_terms_and_cardinality() {
+    let dir = tempfile::tempdir().unwrap();
+    let index = open_index(dir.path()).await;
+
+    let result = aggs(
+        &index,
+        Value::Null,
+        json!({
+            "sellers": {"terms": {"field": "seller"}},
+            "top_seller": {"terms": {"field": "seller", "size": 1}},
+            "repeat_sellers": {"terms": {"field": "seller", "min_doc_count": 2}},
+            "distinct_sellers": {"cardinality": {"field": "seller"}},
+            "distinct_prices": {"cardinality": {"field": "price"}}
+        }),
+    )
+    .unwrap();
+
+    assert_eq!(
+        result,
+        json!({
+            "sellers": {"buckets": [
+                {"key": "alpha", "doc_count": 2},
+                {"key": "beta", "doc_count": 1},
+                {"key": "gamma", "doc_count": 1}
+            ]},
+            "top_seller": {"buckets": [{"key": "alpha", "doc_count": 2}]},
+            "repeat_sellers": {"buckets": [{"key": "alpha", "doc_count": 2}]},
+            "distinct_sellers": {"value": 3},
+            "distinct_prices": {"value": 3}
+        })
+    );
+
+    let result = aggs(
+        &index,
+        json!({"term": {"field": "brand", "value": "lenovo"}}),
+        json!({"distinct_sellers": {"cardinality": {"field": "seller"}}}),
+    )
+    .unwrap();
+    assert_eq!(result, json!({"distinct_sellers": {"value": 2}}));
+}
+
+#[tokio::test]
+async fn test_aggregations_are_validated_against_schema() {
+    let dir = tempfile::tempdir().unwrap();
+    let index = open_index(dir.path()).await;
+
+    let cases = [
+        (
+            json!({"b": {"stats": {"field": "brand"}}}),
+            "aggs.b: column 'brand' is not fast_sortable",
+        ),
+        (
+            json!({"h": {"histogram": {"field": "price", "interval": 0}}}),
+            "aggs.h: interval must be a positive number",
+        ),
+        (
+            json!({"d": {"date_histogram": {"field": "price", "interval": "day"}}}),
+            "aggs.d: date_histogram requires a date_time column, 'price' is Double",
+        ),
+        (
+            json!({"t": {"terms": {"field": "price"}}}),
+            "aggs.t: terms requires a text column, 'price' is Double",
+        ),
+        (
+            json!({"s": {"stats": {"field": "seller"}}}),
+            "aggs.s: Text column 'seller' cannot be aggregated",
+        ),
+        (
+            json!({"r": {"range": {"field": "created_at", "ranges": [{"from": "yesterday"}]}}}),
+            "aggs.r.ranges[0]: invalid bound \"yesterday\"",
+        ),
+    ];
+
+    for (request, expected) in cases {
+        let err = aggs(&index, Value::Null, request).unwrap_err();
+        assert!(err.to_string().contains(expected), "{err}");
+    }
+}
+
+#[tokio::test]
+async fn test_histogram_with_out_of_range_buckets_is_rejected() {
+    let dir = tempfile::tempdir().unwrap();
+    let index = open_index(dir.path()).await;
+    add_documents(
+        dir.path(),
+        &index,
+        vec![
+            document("5", "apple", "huge", Some(1e300), "/phones"),
+            document("6", "apple", "tiny", Some(-1e300), "/phones"),
+        ],
+    )
+    .await;
+
+    let err = aggs(
+        &index,
+        Value::Null,
+        json!({"h": {"histogram": {"field": "price", "interval": 1e-300}}}),
+    )
+    .unwrap_err();
+    assert!(
+        format!("{err:#}").contains("aggs.h: more than 10000 buckets"),
+        "{err:#}"
+    );
+
+    // на обычных ценах то же поле по-прежнему агрегируется
+    let result = aggs(
+        &index,
+        json!({"range": {"field": "price", "gte": 0, "lte": 2000}}),
+        json!({"h": {"histogram": {"field": "price", "interval": 1000}}}),
+    )
+    .unwrap();
+    assert_eq!(result["h"]["buckets"].as_array().unwrap().len(), 2);
+}
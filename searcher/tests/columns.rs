@@ -0,0 +1,40 @@
+mod common;
+
+use common::open_index;
+use searcher::api::SearchRequest;
+use searcher::engine::response::build_matrix_response;
+use searcher::engine::search::execute_search;
+use serde_json::json;
+
+#[tokio::test]
+async fn test_matrix_response_has_typed_nullable_columns() {
+    let dir = tempfile::tempdir().unwrap();
+    let index = open_index(dir.path()).await;
+
+    let req: SearchRequest = serde_json::from_value(json!({
+        "select": ["id", "price", "category", "created_at", "id"],
+        "from": "products",
+        "where": {"term": {"field": "brand", "value": "lenovo"}},
+        "sort": "created_at",
+    }))
+    .unwrap();
+    let hits = execute_search(&index, &req).unwrap();
+    let response = build_matrix_response(&index, &hits, &req).unwrap();
+
+    assert_eq!(
+        serde_json::to_value(&response).unwrap(),
+        json!({
+            "row_count": 2,
+            "columns": [
+                {"name": "id", "values": {"type": "String", "values": ["3", "4"]}},
+                {"name": "price", "values": {"type": "NullableDouble", "values": [1499.0, null]}},
+                {"name": "category", "values": {"type": "Facet", "values": ["/laptops/lenovo", "/laptops/lenovo"]}},
+                {"name": "created_at", "values": {"type": "DateTime", "values": [
+                    "2025-03-10T12:00:00Z",
+                    "2025-04-10T12:00:00Z"
+                ]}}
+            ],
+            "invalid_sort_values": 0
+        })
+    );
+}
@@ -0,0 +1,194 @@
+#![allow(dead_code)]
+
+use std::collections::HashSet;
+
+use corelib::api::{MetaColumn, MetaColumnModifier, MetaColumnType, MetaSchema};
+use corelib::config::IndexerConfig;
+use indexer::api::{Document, FieldValue, IndexableField};
+use indexer::infra::index::IndexState;
+use indexer::infra::index_registry;
+use searcher::api::{SearchRequest, SearchValue};
+use searcher::domain::index::SearchIndex;
+use searcher::engine::response::build_search_response;
+use searcher::engine::search::execute_search;
+use serde_json::{Value, json};
+use tantivy::ReloadPolicy;
+
+use MetaColumnModifier::*;
+
+pub fn test_schema() -> MetaSchema {
+    let column = |name: &str, column_type, modifiers: &[MetaColumnModifier]| MetaColumn {
+        name: name.to_string(),
+        column_type,
+        modifiers: modifiers.iter().cloned().collect::<HashSet<_>>(),
+    };
+
+    MetaSchema {
+        name: "products".to_string(),
+        columns: vec![
+            column("id", MetaColumnType::Text, &[Id]),
+            column("brand", MetaColumnType::Text, &[Equals]),
+            column("title", MetaColumnType::Text, &[Equals, FullText]),
+            column("price", MetaColumnType::Double, &[FastSortable, Nullable]),
+            column("category", MetaColumnType::Tree, &[Equals]),
+            column("note", MetaColumnType::Text, &[Nullable]),
+            column("created_at", MetaColumnType::DateTime, &[FastSortable]),
+            column("seller", MetaColumnType::Text, &[Equals, FastSortable]),
+            column(
+                "rank_position",
+                MetaColumnType::Ulong,
+                &[FastSortable, Nullable],
+            ),
+        ],
+    }
+}
+
+pub fn document(
+    id: &str,
+    brand: &str,
+    title: &str,
+    price: Option<f64>,
+    category: &str,
+) -> Document {
+    let field = |name: &str, value| IndexableField {
+        name: name.to_string(),
+        value: Some(value),
+    };
+    let mut fields = vec![
+        field("id", FieldValue::Text(id.to_string())),
+        field("brand", FieldValue::Text(brand.to_string())),
+        field("title", FieldValue::Text(title.to_string())),
+        field("category", FieldValue::Tree(vec![category.to_string()])),
+        // документ N создан 10-го числа N-го месяца
+        field(
+            "created_at",
+            FieldValue::DateTime(format!("2025-{id:0>2}-10T12:00:00Z")),
+        ),
+        field("seller", FieldValue::Text(seller(id).to_string())),
+    ];
+    if let Some(price) = price {
+        fields.push(field("price", FieldValue::Double(price)));
+    }
+    // у документа 4 позиции нет
+    if let Some(position) = rank_position(id) {
+        fields.push(field("rank_position", FieldValue::Ulong(position)));
+    }
+
+    Document {
+        index_name: "products".to_string(),
+        index_version: 1,
+        fields,
+    }
+}
+
+/// Позиции в рейтинге документов 1..3: чем новее документ, тем выше
+pub fn rank_position(id: &str) -> Option<u64> {
+    match id {
+        "1" => Some(4),
+        "2" => Some(3),
+        "3" => Some(2),
+        _ => None,
+    }
+}
+
+/// Продавцы документов 1..4: у alpha два товара
+pub fn seller(id: &str) -> &'static str {
+    match id {
+        "1" | "3" => "alpha",
+        "2" => "beta",
+        _ => "gamma",
+    }
+}
+
+pub async fn open_index(root: &std::path::Path) -> SearchIndex {
+    build_index(root, false).await
+}
+
+/// Тот же индекс, но каждый документ — в своём сегменте
+pub async fn open_index_in_segments(root: &std::path::Path) -> SearchIndex {
+    build_index(root, true).await
+}
+
+async fn build_index(root: &std::path::Path, commit_each: bool) -> SearchIndex {
+    let config = IndexerConfig {
+        index_registry_dir: root.to_path_buf(),
+        ..Default::default()
+    };
+    let registry = index_registry::load_all_indexes(&config).await.unwrap();
+    let index_state = IndexState::init_index_state(&registry, &test_schema())
+        .await
+        .unwrap();
+
+    for doc in [
+        document(
+            "1",
+            "apple",
+            "macbook pro 14",
+            Some(1999.0),
+            "/laptops/apple",
+        ),
+        document("2", "apple", "iphone 12 mini", Some(699.0), "/phones"),
+        document(
+            "3",
+            "lenovo",
+            "thinkpad x1 carbon",
+            Some(1499.0),
+            "/laptops/lenovo",
+        ),
+        document("4", "lenovo", "legion pro", None, "/laptops/lenovo"),
+    ] {
+        index_state.add_document_safely(doc).await.unwrap();
+        if commit_each {
+            index_state.commit().await.unwrap();
+        }
+    }
+    index_state.commit().await.unwrap();
+
+    let index_dir = root.join("products").join("index");
+    SearchIndex::open_from_path(index_dir.to_str().unwrap(), ReloadPolicy::Manual).unwrap()
+}
+
+/// Дописывает документы в индекс из [`open_index`] и перечитывает его снимок
+pub async fn add_documents(root: &std::path::Path, index: &SearchIndex, docs: Vec<Document>) {
+    let config = IndexerConfig {
+        index_registry_dir: root.to_path_buf(),
+        ..Default::default()
+    };
+    let registry = index_registry::load_all_indexes(&config).await.unwrap();
+    let index_state = registry.lookup("products").unwrap();
+    for doc in docs {
+        index_state.add_document_safely(doc).await.unwrap();
+    }
+    index_state.shutdown().await.unwrap();
+    index.refresh().unwrap();
+}
+
+pub fn request(filter: &str, where_filter: Value) -> SearchRequest {
+    serde_json::from_value(json!({
+        "select": ["id"],
+        "from": "products",
+        "filter": filter,
+        "where": where_filter,
+    }))
+    .unwrap()
+}
+
+/// id найденных документов в порядке выдачи
+pub fn ordered_ids(index: &SearchIndex, req: &SearchRequest) -> anyhow::Result<Vec<String>> {
+    let hits = execute_search(index, req)?;
+    let response = build_search_response(index, &hits, req)?;
+    Ok(response
+        .rows
+        .into_iter()
+        .map(|row| match &row.fields[0].value {
+            SearchValue::Str(id) => id.clone(),
+            other => panic!("unexpected id value {other:?}"),
+        })
+        .collect())
+}
+
+pub fn search_ids(index: &SearchIndex, req: &SearchRequest) -> anyhow::Result<Vec<String>> {
+    let mut ids = ordered_ids(index, req)?;
+    ids.sort();
+    Ok(ids)
+}
@@ -0,0 +1,62 @@
+mod common;
+
+use common::open_index;
+use searcher::api::{CountRequest, SearchRequest};
+use searcher::engine::response::build_search_response;
+use searcher::engine::search::{execute_count, execute_search};
+use serde_json::json;
+
+#[tokio::test]
+async fn test_total_is_counted_alongside_page() {
+    let dir = tempfile::tempdir().unwrap();
+    let index = open_index(dir.path()).await;
+
+    for sort in [None, Some("price")] {
+        let req: SearchRequest = serde_json::from_value(json!({
+            "select": ["id"],
+            "from": "products",
+            "where": {"facet": {"field": "category", "path": "/laptops"}},
+            "sort": sort,
+            "limit": 1,
+            "track_total": true,
+        }))
+        .unwrap();
+        let hits = execute_search(&index, &req).unwrap();
+        let response = build_search_response(&index, &hits, &req).unwrap();
+
+        assert_eq!(response.rows.len(), 1);
+        assert_eq!(response.total, Some(3), "sort {sort:?}");
+    }
+
+    let req: SearchRequest =
+        serde_json::from_value(json!({"select": ["id"], "from": "products"})).unwrap();
+    assert_eq!(execute_search(&index, &req).unwrap().total, None);
+}
+
+#[tokio::test]
+async fn test_count_only() {
+    let dir = tempfile::tempdir().unwrap();
+    let index = open_index(dir.path()).await;
+
+    let count = |req| {
+        execute_count(
+            &index,
+            &serde_json::from_value::<CountRequest>(req).unwrap(),
+        )
+    };
+
+    assert_eq!(count(json!({"from": "products"})).unwrap(), 4);
+    assert_eq!(
+        count(json!({
+            "from": "products",
+            "filter": "pro",
+            "where": {"term": {"field": "brand", "value": "lenovo"}}
+        }))
+        .unwrap(),
+        1
+    );
+    assert!(
+        count(json!({"from": "products", "where": {"term": {"field": "missing", "value": 1}}}))
+            .is_err()
+    );
+}
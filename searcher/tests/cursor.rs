@@ -0,0 +1,126 @@
+mod common;
+
+use common::open_index;
+use searcher::api::{SearchRequest, SearchValue};
+use searcher::domain::index::SearchIndex;
+use searcher::engine::response::build_search_response;
+use searcher::engine::search::execute_search;
+use serde_json::{Value, json};
+
+fn page(index: &SearchIndex, req: Value) -> (Vec<String>, Option<String>) {
+    let req: SearchRequest = serde_json::from_value(req).unwrap();
+    let hits = execute_search(index, &req).unwrap();
+    let response = build_search_response(index, &hits, &req).unwrap();
+    let ids = response
+        .rows
+        .into_iter()
+        .map(|row| match &row.fields[0].value {
+            SearchValue::Str(id) => id.clone(),
+            other => panic!("unexpected id value {other:?}"),
+        })
+        .collect();
+    (ids, response.cursor)
+}
+
+#[tokio::test]
+async fn test_cursor_pages_match_single_page() {
+    let dir = tempfile::tempdir().unwrap();
+    let index = open_index(dir.path()).await;
+
+    // пустой filter даёт всем одинаковый score — порядок держится на адресе
+    for (filter, sort, sort_order) in [
+        ("", None, "asc"),
+        ("pro OR mini", None, "asc"),
+        ("", Some("price"), "asc"),
+        ("", Some("price"), "desc"),
+    ] {
+        let query = |limit: usize, cursor: Option<String>| {
+            json!({
+                "select": ["id"],
+                "from": "products",
+                "filter": filter,
+                "sort": sort,
+                "sort_order": sort_order,
+                "limit": limit,
+                "cursor": cursor,
+            })
+        };
+        let (expected, _) = page(&index, query(10, None));
+
+        let mut paged = Vec::new();
+        let mut cursor = None;
+        loop {
+            let (ids, next) = page(&index, query(2, cursor));
+            paged.extend(ids);
+            match next {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+            assert!(paged.len() <= expected.len(), "cursor loops: {paged:?}");
+        }
+        assert_eq!(
+            paged, expected,
+            "filter {filter:?}, sort {sort:?} {sort_order}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_cursor_of_other_order_is_rejected() {
+    let dir = tempfile::tempdir().unwrap();
+    let index = open_index(dir.path()).await;
+
+    let req = |sort: Option<&str>, cursor: Option<String>| json!({"select": ["id"], "from": "products", "sort": sort, "limit": 1, "cursor": cursor});
+    let (_, cursor) = page(&index, req(Some("price"), None));
+
+    let bm25: SearchRequest = serde_json::from_value(req(None, cursor.clone())).unwrap();
+    assert!(execute_search(&index, &bm25).is_err());
+
+    // тот же порядок, но другое выражение или другая политика для NaN
+    let other_expr: SearchRequest =
+        serde_json::from_value(req(Some("price * 2"), cursor.clone())).unwrap();
+    let err = execute_search(&index, &other_expr).unwrap_err();
+    assert!(
+        err.to_string().contains("different sort expression"),
+        "{err}"
+    );
+    let mut other_invalid = req(Some("price"), cursor);
+    other_invalid["sort_invalid"] = json!("first");
+    let other_invalid: SearchRequest = serde_json::from_value(other_invalid).unwrap();
+    assert!(execute_search(&index, &other_invalid).is_err());
+
+    let garbage: SearchRequest =
+        serde_json::from_value(req(None, Some("not-a-cursor".into()))).unwrap();
+    assert!(execute_search(&index, &garbage).is_err());
+}
+
+#[tokio::test]
+async fn test_cursor_keeps_now_of_first_page() {
+    let dir = tempfile::tempdir().unwrap();
+    let index = open_index(dir.path()).await;
+
+    let req = |now: Option<&str>, cursor: Option<String>| {
+        json!({
+            "select": ["id"],
+            "from": "products",
+            "sort": "-abs(created_at - now_ms())",
+            "sort_order": "desc",
+            "now": now,
+            "limit": 2,
+            "cursor": cursor,
+        })
+    };
+    let (first, cursor) = page(&index, req(Some("2025-03-01T00:00:00Z"), None));
+    assert_eq!(first, ["3", "2"]);
+
+    // без now следующая страница считается в now первой, а не в текущем времени
+    let (second, _) = page(&index, req(None, cursor.clone()));
+    assert_eq!(second, ["4", "1"]);
+    let (same, _) = page(&index, req(Some("2025-03-01T00:00:00Z"), cursor.clone()));
+    assert_eq!(same, second);
+
+    let other_now: SearchRequest =
+        serde_json::from_value(req(Some("2025-01-01T00:00:00Z"), cursor)).unwrap();
+    let err = execute_search(&index, &other_now).unwrap_err();
+    assert!(err.to_string().contains("different now"), "{err}");
+}
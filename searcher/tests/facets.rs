@@ -0,0 +1,81 @@
+mod common;
+
+use common::open_index;
+use searcher::api::SearchRequest;
+use searcher::engine::response::build_search_response;
+use searcher::engine::search::execute_search;
+use serde_json::{Value, json};
+
+fn facets(index: &searcher::domain::index::SearchIndex, req: Value) -> anyhow::Result<Value> {
+    let req: SearchRequest = serde_json::from_value(req).unwrap();
+    let hits = execute_search(index, &req)?;
+    let response = build_search_response(index, &hits, &req)?;
+    Ok(serde_json::to_value(response.facets)?)
+}
+
+#[tokio::test]
+async fn test_facet_counts_are_collected_with_hits() {
+    let dir = tempfile::tempdir().unwrap();
+    let index = open_index(dir.path()).await;
+
+    let result = facets(
+        &index,
+        json!({
+            "select": ["id"],
+            "from": "products",
+            "limit": 1,
+            "facets": [
+                {"field": "category"},
+                {"field": "category", "path": "/laptops", "top": 1}
+            ]
+        }),
+    )
+    .unwrap();
+    assert_eq!(
+        result,
+        json!([
+            {"field": "category", "path": "/", "counts": [
+                {"path": "/laptops", "count": 3},
+                {"path": "/phones", "count": 1}
+            ]},
+            {"field": "category", "path": "/laptops", "counts": [
+                {"path": "/laptops/lenovo", "count": 2}
+            ]}
+        ])
+    );
+
+    // счётчики считаются по найденным документам, а не по странице выдачи
+    let result = facets(
+        &index,
+        json!({
+            "select": ["id"],
+            "from": "products",
+            "where": {"term": {"field": "brand", "value": "apple"}},
+            "facets": [{"field": "category", "path": "/laptops"}]
+        }),
+    )
+    .unwrap();
+    assert_eq!(
+        result,
+        json!([{"field": "category", "path": "/laptops", "counts": [
+            {"path": "/laptops/apple", "count": 1}
+        ]}])
+    );
+}
+
+#[tokio::test]
+async fn test_facet_requires_tree_column() {
+    let dir = tempfile::tempdir().unwrap();
+    let index = open_index(dir.path()).await;
+
+    let err = facets(
+        &index,
+        json!({"select": ["id"], "from": "products", "facets": [{"field": "brand"}]}),
+    )
+    .unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("facets[0]: facet counts require a tree column, 'brand' is Text"),
+        "{err}"
+    );
+}
@@ -0,0 +1,206 @@
+mod common;
+
+use common::{open_index, request, search_ids};
+use searcher::api::SearchValue;
+use searcher::engine::query_sql::parse_sql;
+use searcher::engine::response::build_search_response;
+use searcher::engine::search::execute_search;
+use serde_json::{Value, json};
+
+#[tokio::test]
+async fn test_filter_ast_matches_expected_documents() {
+    let dir = tempfile::tempdir().unwrap();
+    let index = open_index(dir.path()).await;
+
+    let cases = [
+        (
+            json!({"term": {"field": "brand", "value": "apple"}}),
+            vec!["1", "2"],
+        ),
+        (
+            json!({"terms": {"field": "id", "values": ["2", "3"]}}),
+            vec!["2", "3"],
+        ),
+        (
+            json!({"range": {"field": "price", "gte": 699.0, "lt": 1999.0}}),
+            vec!["2", "3"],
+        ),
+        (
+            json!({"prefix": {"field": "title", "value": "i"}}),
+            vec!["2"],
+        ),
+        (
+            json!({"facet": {"field": "category", "path": "/laptops"}}),
+            vec!["1", "3", "4"],
+        ),
+        (
+            json!({"match": {"field": "title", "query": "Pro"}}),
+            vec!["1", "4"],
+        ),
+        (
+            json!({"match": {"field": "title", "query": "macbook pro", "operator": "and"}}),
+            vec!["1"],
+        ),
+        (json!({"not": {"exists": {"field": "price"}}}), vec!["4"]),
+        (
+            json!({"and": [
+                {"facet": {"field": "category", "path": "/laptops"}},
+                {"or": [
+                    {"term": {"field": "brand", "value": "apple"}},
+                    {"range": {"field": "price", "lt": 1500}}
+                ]}
+            ]}),
+            vec!["1", "3"],
+        ),
+    ];
+
+    for (where_filter, expected) in cases {
+        let ids = search_ids(&index, &request("", where_filter.clone())).unwrap();
+        assert_eq!(ids, expected, "{where_filter}");
+    }
+}
+
+#[tokio::test]
+async fn test_filter_ast_combines_with_query_string() {
+    let dir = tempfile::tempdir().unwrap();
+    let index = open_index(dir.path()).await;
+
+    let where_filter = json!({"term": {"field": "brand", "value": "lenovo"}});
+    let ids = search_ids(&index, &request("pro", where_filter)).unwrap();
+    assert_eq!(ids, vec!["4"]);
+
+    let ids = search_ids(&index, &request("", Value::Null)).unwrap();
+    assert_eq!(ids.len(), 4);
+}
+
+#[tokio::test]
+async fn test_filter_ast_is_validated_against_schema() {
+    let dir = tempfile::tempdir().unwrap();
+    let index = open_index(dir.path()).await;
+
+    let cases = [
+        (
+            json!({"and": [{"range": {"field": "note", "gte": "a"}}]}),
+            "where.and[0].range: column 'note' is neither indexed nor fast_sortable",
+        ),
+        (
+            json!({"term": {"field": "title_missing", "value": "x"}}),
+            "where.term: unknown column 'title_missing'",
+        ),
+        (
+            json!({"term": {"field": "brand", "value": 42}}),
+            "where.term: value 42 does not match Text column 'brand'",
+        ),
+        (
+            json!({"not": {"range": {"field": "price"}}}),
+            "where.not.range: at least one of gt, gte, lt, lte is required",
+        ),
+        (
+            json!({"match": {"field": "brand", "query": "apple"}}),
+            "where.match: column 'brand' is not full_text",
+        ),
+        (
+            json!({"exists": {"field": "brand"}}),
+            "where.exists: exists requires a fast_sortable column",
+        ),
+    ];
+
+    for (where_filter, expected) in cases {
+        let err = search_ids(&index, &request("", where_filter)).unwrap_err();
+        assert!(err.to_string().contains(expected), "{err}");
+    }
+}
+
+#[tokio::test]
+async fn test_sql_select_where_order_by() {
+    let dir = tempfile::tempdir().unwrap();
+    let index = open_index(dir.path()).await;
+
+    let ordered_ids = |sql: &str| {
+        let sql = parse_sql(sql).unwrap();
+        sql.validate(&index).unwrap();
+        let hits = execute_search(&index, &sql.request).unwrap();
+        build_search_response(&index, &hits, &sql.request)
+            .unwrap()
+            .rows
+            .into_iter()
+            .map(|row| match &row.fields[0].value {
+                SearchValue::Str(id) => id.clone(),
+                other => panic!("unexpected id value {other:?}"),
+            })
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        ordered_ids("SELECT id FROM products WHERE price IS NOT NULL ORDER BY price DESC"),
+        vec!["1", "3", "2"]
+    );
+    assert_eq!(
+        ordered_ids(
+            "SELECT id, brand FROM products WHERE facet(category, '/laptops') AND price < 1999 \
+             OR brand = 'apple' ORDER BY price LIMIT 2 OFFSET 1"
+        ),
+        vec!["3", "1"]
+    );
+    assert_eq!(
+        ordered_ids(
+            "SELECT id FROM products WHERE rank_position IS NOT NULL ORDER BY rank_position"
+        ),
+        vec!["3", "2", "1"]
+    );
+    assert_eq!(
+        ordered_ids("SELECT id FROM products ORDER BY seller DESC LIMIT 2"),
+        vec!["4", "2"]
+    );
+}
+
+#[tokio::test]
+async fn test_sql_schema_errors_point_at_span() {
+    let dir = tempfile::tempdir().unwrap();
+    let index = open_index(dir.path()).await;
+
+    let cases = [
+        (
+            "SELECT id FROM products WHERE brand = 'x' AND note >= 'a'",
+            "column 'note' is neither indexed nor fast_sortable at line 1, column 47",
+        ),
+        (
+            "SELECT id, missing FROM products",
+            "unknown column 'missing' at line 1, column 12",
+        ),
+        (
+            "SELECT id FROM products\nORDER BY brand",
+            "column 'brand' cannot be used in ORDER BY",
+        ),
+    ];
+
+    for (sql, expected) in cases {
+        let err = parse_sql(sql).unwrap().validate(&index).unwrap_err();
+        assert!(err.to_string().starts_with(expected), "{err}");
+    }
+}
+
+#[tokio::test]
+async fn test_sql_order_by_function_arguments() {
+    let dir = tempfile::tempdir().unwrap();
+    let index = open_index(dir.path()).await;
+
+    // аргументы функций не обязаны быть fast_sortable, их проверяет компилятор выражения
+    let sql =
+        parse_sql("SELECT id FROM products WHERE match(title, 'pro') ORDER BY bm25(title) DESC")
+            .unwrap();
+    sql.validate(&index).unwrap();
+    let mut ids = search_ids(&index, &sql.request).unwrap();
+    ids.sort();
+    assert_eq!(ids, ["1", "4"]);
+
+    let sql = parse_sql("SELECT id FROM products ORDER BY bm25(brand) DESC").unwrap();
+    sql.validate(&index).unwrap();
+    let err = search_ids(&index, &sql.request).unwrap_err();
+    assert!(err.to_string().contains("not full_text"), "{err}");
+
+    // голая колонка по-прежнему должна быть fast_sortable
+    let sql = parse_sql("SELECT id FROM products ORDER BY bm25(title) + brand").unwrap();
+    let err = sql.validate(&index).unwrap_err();
+    assert!(err.to_string().contains("column 'brand'"), "{err}");
+}
@@ -0,0 +1,87 @@
+mod common;
+
+use common::{open_index, ordered_ids};
+use searcher::api::SearchRequest;
+use serde_json::{Value, json};
+
+fn order_by(order_by: Value, offset: usize) -> SearchRequest {
+    serde_json::from_value(json!({
+        "select": ["id"],
+        "from": "products",
+        "order_by": order_by,
+        "offset": offset,
+    }))
+    .unwrap()
+}
+
+#[tokio::test]
+async fn test_order_by_several_columns() {
+    let dir = tempfile::tempdir().unwrap();
+    let index = open_index(dir.path()).await;
+
+    let by_seller_then_newest = json!([
+        {"field": "seller"},
+        {"field": "created_at", "dir": "desc"},
+    ]);
+    assert_eq!(
+        ordered_ids(&index, &order_by(by_seller_then_newest.clone(), 0)).unwrap(),
+        ["3", "1", "2", "4"]
+    );
+    assert_eq!(
+        ordered_ids(&index, &order_by(by_seller_then_newest, 1)).unwrap()[..2],
+        ["1", "2"]
+    );
+
+    // у документа 4 нет цены
+    assert_eq!(
+        ordered_ids(&index, &order_by(json!([{"field": "price"}]), 0)).unwrap(),
+        ["2", "3", "1", "4"]
+    );
+    assert_eq!(
+        ordered_ids(
+            &index,
+            &order_by(
+                json!([{"field": "price", "dir": "desc", "missing": "first"}]),
+                0
+            )
+        )
+        .unwrap(),
+        ["4", "1", "3", "2"]
+    );
+}
+
+#[tokio::test]
+async fn test_order_by_rejects_invalid_keys() {
+    let dir = tempfile::tempdir().unwrap();
+    let index = open_index(dir.path()).await;
+
+    let err = ordered_ids(&index, &order_by(json!([{"field": "brand"}]), 0)).unwrap_err();
+    assert!(err.to_string().contains("order_by[0]"), "{err}");
+    assert!(ordered_ids(&index, &order_by(json!([{"field": "missing"}]), 0)).is_err());
+
+    let mut req = order_by(json!([{"field": "price"}]), 0);
+    req.sort = Some("price".to_string());
+    assert!(ordered_ids(&index, &req).is_err());
+}
+
+#[tokio::test]
+async fn test_expression_sort_order() {
+    let dir = tempfile::tempdir().unwrap();
+    let index = open_index(dir.path()).await;
+
+    let sorted = |sort_order: &str| {
+        let req: SearchRequest = serde_json::from_value(json!({
+            "select": ["id"],
+            "from": "products",
+            "sort": "coalesce(price, 0) * 2",
+            "sort_order": sort_order,
+            "limit": 3,
+        }))
+        .unwrap();
+        ordered_ids(&index, &req).unwrap()
+    };
+
+    // у документа 4 нет цены, coalesce подставляет 0
+    assert_eq!(sorted("asc"), ["4", "2", "3"]);
+    assert_eq!(sorted("desc"), ["1", "3", "2"]);
+}
@@ -0,0 +1,118 @@
+mod common;
+
+use common::open_index;
+use searcher::api::{ColumnValues, SearchRequest, SearchValue};
+use searcher::engine::response::{build_matrix_response, build_search_response};
+use searcher::engine::search::execute_search;
+use serde_json::{Value, json};
+
+fn request(extra: Value) -> SearchRequest {
+    let mut req = json!({
+        "select": ["id", "_score", "_sort", "_segment", "_doc"],
+        "from": "products",
+    });
+    req.as_object_mut()
+        .unwrap()
+        .extend(extra.as_object().unwrap().clone());
+    serde_json::from_value(req).unwrap()
+}
+
+#[tokio::test]
+async fn test_pseudo_columns_in_rows() {
+    let dir = tempfile::tempdir().unwrap();
+    let index = open_index(dir.path()).await;
+
+    let req = request(json!({"filter": "pro"}));
+    let hits = execute_search(&index, &req).unwrap();
+    let response = build_search_response(&index, &hits, &req).unwrap();
+    assert_eq!(response.rows.len(), 2);
+    for (row, &(score, addr)) in response.rows.iter().zip(&hits.top_docs) {
+        let names: Vec<_> = row.fields.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["id", "_score", "_sort", "_segment", "_doc"]);
+        assert!(
+            matches!(row.fields[1].value, SearchValue::NullableDouble(Some(s)) if s == score as f64 && s > 0.0)
+        );
+        assert!(
+            matches!(row.fields[2].value, SearchValue::NullableDouble(Some(s)) if s == score as f64)
+        );
+        assert!(
+            matches!(row.fields[3].value, SearchValue::Ulong(s) if s == addr.segment_ord as u64)
+        );
+        assert!(matches!(row.fields[4].value, SearchValue::Ulong(d) if d == addr.doc_id as u64));
+    }
+
+    // order_by не считает BM25
+    let req = request(json!({"order_by": [{"field": "price"}], "limit": 1}));
+    let err = execute_search(&index, &req).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("_score cannot be selected with order_by")
+    );
+
+    let mut req = request(json!({"order_by": [{"field": "price"}], "limit": 1}));
+    req.select.retain(|f| f != "_score");
+    let hits = execute_search(&index, &req).unwrap();
+    let response = build_search_response(&index, &hits, &req).unwrap();
+    assert!(matches!(
+        response.rows[0].fields[1].value,
+        SearchValue::NullableDouble(None)
+    ));
+}
+
+#[tokio::test]
+async fn test_score_with_sort_expression() {
+    let dir = tempfile::tempdir().unwrap();
+    let index = open_index(dir.path()).await;
+
+    let relevance = execute_search(&index, &request(json!({"filter": "pro"}))).unwrap();
+    let bm25: Vec<_> = relevance
+        .top_docs
+        .iter()
+        .map(|&(score, addr)| (addr, score as f64))
+        .collect();
+
+    // выражение не использует _score, но BM25 запроса всё равно отдаётся
+    let req = request(json!({"filter": "pro", "sort": "price / 2", "sort_order": "desc"}));
+    let hits = execute_search(&index, &req).unwrap();
+    let response = build_search_response(&index, &hits, &req).unwrap();
+    assert_eq!(response.rows.len(), 2);
+    for (row, &(sort_value, addr)) in response.rows.iter().zip(&hits.top_docs) {
+        let expected = bm25.iter().find(|(a, _)| *a == addr).unwrap().1;
+        assert!(
+            matches!(row.fields[1].value, SearchValue::NullableDouble(Some(s)) if s == expected && s > 0.0)
+        );
+        // у документа 4 нет цены: _sort пустой, а _score есть
+        let sort_value = (!sort_value.is_nan()).then_some(sort_value as f64);
+        assert!(matches!(row.fields[2].value, SearchValue::NullableDouble(s) if s == sort_value));
+    }
+
+    let response = build_matrix_response(&index, &hits, &req).unwrap();
+    assert!(matches!(
+        &response.columns[1].values,
+        ColumnValues::NullableDouble(v) if v.iter().all(|s| s.is_some_and(|s| s > 0.0))
+    ));
+}
+
+#[tokio::test]
+async fn test_pseudo_columns_in_matrix() {
+    let dir = tempfile::tempdir().unwrap();
+    let index = open_index(dir.path()).await;
+
+    let req = request(json!({"sort": "price", "sort_invalid": "first", "limit": 2}));
+    let hits = execute_search(&index, &req).unwrap();
+    let response = build_matrix_response(&index, &hits, &req).unwrap();
+
+    let names: Vec<_> = response.columns.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(names, ["id", "_score", "_sort", "_segment", "_doc"]);
+    // без filter все документы получают одинаковый score
+    assert!(matches!(
+        &response.columns[1].values,
+        ColumnValues::NullableDouble(v) if v.len() == 2 && v[0].is_some() && v[0] == v[1]
+    ));
+    // у документа 4 нет цены: выражение пустое, и `_sort` тоже
+    assert!(matches!(
+        &response.columns[2].values,
+        ColumnValues::NullableDouble(v) if v == &[None, Some(699.0)]
+    ));
+    assert!(matches!(&response.columns[3].values, ColumnValues::UInt64(v) if v == &[0, 0]));
+}
@@ -0,0 +1,270 @@
+mod common;
+
+use common::{add_documents, document, open_index, open_index_in_segments, ordered_ids, seller};
+use searcher::api::{InvalidSortValue, SearchRequest, SortOrder};
+use searcher::engine::search::execute_search;
+use serde_json::json;
+
+fn ranked(filter: &str, sort: &str) -> SearchRequest {
+    serde_json::from_value(json!({
+        "select": ["id"],
+        "from": "products",
+        "filter": filter,
+        "sort": sort,
+        "sort_order": "desc",
+    }))
+    .unwrap()
+}
+
+#[tokio::test]
+async fn test_score_in_expression() {
+    let dir = tempfile::tempdir().unwrap();
+    let index = open_index(dir.path()).await;
+
+    let bm25: SearchRequest = serde_json::from_value(json!({
+        "select": ["id"],
+        "from": "products",
+        "filter": "pro OR carbon",
+    }))
+    .unwrap();
+    let by_relevance = ordered_ids(&index, &bm25).unwrap();
+    assert_eq!(by_relevance.len(), 3);
+    assert_eq!(
+        ordered_ids(&index, &ranked("pro OR carbon", "_score")).unwrap(),
+        by_relevance
+    );
+
+    // цена перевешивает релевантность; у документа 4 цены нет
+    assert_eq!(
+        ordered_ids(
+            &index,
+            &ranked("pro OR carbon", "_score - coalesce(price, 0) / 100")
+        )
+        .unwrap(),
+        ["4", "3", "1"]
+    );
+}
+
+#[tokio::test]
+async fn test_bm25_of_single_field() {
+    let dir = tempfile::tempdir().unwrap();
+    let index = open_index(dir.path()).await;
+
+    // короткий заголовок "legion pro" релевантнее "macbook pro 14"
+    assert_eq!(
+        ordered_ids(&index, &ranked("pro", "bm25(title)")).unwrap(),
+        ["4", "1"]
+    );
+    // без filter bm25 равен 0, порядок держится на адресе
+    assert_eq!(
+        ordered_ids(&index, &ranked("", "bm25(title) + 1")).unwrap(),
+        ["1", "2", "3", "4"]
+    );
+
+    assert!(ordered_ids(&index, &ranked("pro", "bm25(brand)")).is_err());
+    assert!(ordered_ids(&index, &ranked("pro", "bm25(1)")).is_err());
+}
+
+#[tokio::test]
+async fn test_user_functions_in_sort() {
+    let dir = tempfile::tempdir().unwrap();
+    let index = open_index(dir.path()).await;
+
+    let mut req = ranked("", "cheap(price, 1000)");
+    req.functions = vec!["cheap(p, limit) = if(p > 0 && p < limit, limit - p, 0)".to_string()];
+    assert_eq!(ordered_ids(&index, &req).unwrap()[0], "2");
+
+    req.functions = vec!["cheap(p) = -p".to_string()];
+    assert!(ordered_ids(&index, &req).is_err());
+}
+
+#[tokio::test]
+async fn test_params_and_fixed_now() {
+    let dir = tempfile::tempdir().unwrap();
+    let index = open_index(dir.path()).await;
+
+    // ближе всего к 2025-03-01 создан документ 3
+    let mut req = ranked("", "-abs(created_at - now_ms()) / $day");
+    req.now = Some("2025-03-01T00:00:00Z".to_string());
+    req.params.insert("day".to_string(), 86_400_000.0);
+    let first = ordered_ids(&index, &req).unwrap();
+    assert_eq!(first[0], "3");
+    assert_eq!(ordered_ids(&index, &req).unwrap(), first);
+
+    req.now = Some("2025-01-01T00:00:00Z".to_string());
+    assert_eq!(ordered_ids(&index, &req).unwrap()[0], "1");
+
+    req.now = Some("yesterday".to_string());
+    assert!(ordered_ids(&index, &req).is_err());
+
+    req.now = None;
+    req.params.clear();
+    assert!(ordered_ids(&index, &req).is_err());
+}
+
+#[tokio::test]
+async fn test_integer_and_text_columns_in_sort() {
+    let dir = tempfile::tempdir().unwrap();
+    let index = open_index(dir.path()).await;
+
+    let mut req = ranked("macbook OR iphone OR thinkpad", "rank_position");
+    req.sort_order = SortOrder::Asc;
+    assert_eq!(ordered_ids(&index, &req).unwrap(), ["3", "2", "1"]);
+
+    // текст сравнивается по строке: alpha < beta < gamma
+    let mut req = ranked("", "seller");
+    req.sort_order = SortOrder::Asc;
+    assert_eq!(ordered_ids(&index, &req).unwrap(), ["1", "3", "2", "4"]);
+}
+
+#[tokio::test]
+async fn test_text_column_sort_across_segments() {
+    let dir = tempfile::tempdir().unwrap();
+    let index = open_index_in_segments(dir.path()).await;
+    assert_eq!(index.searcher(None).unwrap().segment_readers().len(), 4);
+
+    // порядок сегментов в снимке не задан, поэтому проверяются сами строки
+    let sellers = |req: &SearchRequest| -> Vec<&str> {
+        ordered_ids(&index, req)
+            .unwrap()
+            .iter()
+            .map(|id| seller(id))
+            .collect()
+    };
+    let mut req = ranked("", "seller");
+    req.sort_order = SortOrder::Asc;
+    assert_eq!(sellers(&req), ["alpha", "alpha", "beta", "gamma"]);
+
+    req.sort_order = SortOrder::Desc;
+    assert_eq!(sellers(&req), ["gamma", "beta", "alpha", "alpha"]);
+
+    // курсор сравнивает ранги одного и того же снимка
+    req.sort_order = SortOrder::Asc;
+    req.limit = 2;
+    let first = execute_search(&index, &req).unwrap();
+    req.cursor = first.cursor;
+    assert_eq!(ordered_ids(&index, &req).unwrap(), ["2", "4"]);
+}
+
+#[tokio::test]
+async fn test_text_ranks_are_reused_until_segments_change() {
+    let dir = tempfile::tempdir().unwrap();
+    let index = open_index_in_segments(dir.path()).await;
+
+    let mut req = ranked("", "seller");
+    req.sort_order = SortOrder::Asc;
+    ordered_ids(&index, &req).unwrap();
+    let searcher = index.searcher(None).unwrap();
+    let cached = index
+        .text_ranks
+        .get_or_compute(&searcher, "seller", || panic!("ranks are not cached"))
+        .unwrap();
+    assert_eq!(cached.len(), 4);
+
+    // новый сегмент: ранги пересчитываются и включают его строки
+    add_documents(
+        dir.path(),
+        &index,
+        vec![document("5", "apple", "ipad", None, "/tablets")],
+    )
+    .await;
+    let sellers: Vec<_> = ordered_ids(&index, &req)
+        .unwrap()
+        .iter()
+        .map(|id| seller(id))
+        .collect();
+    assert_eq!(sellers, ["alpha", "alpha", "beta", "gamma", "gamma"]);
+    let searcher = index.searcher(None).unwrap();
+    let recomputed = index
+        .text_ranks
+        .get_or_compute(&searcher, "seller", || panic!("ranks are not cached"))
+        .unwrap();
+    assert_eq!(recomputed.len(), 5);
+}
+
+#[tokio::test]
+async fn test_facet_functions_in_sort() {
+    let dir = tempfile::tempdir().unwrap();
+    let index = open_index(dir.path()).await;
+
+    // путь совпадает и с самим фасетом, и с потомками
+    assert_eq!(
+        ordered_ids(
+            &index,
+            &ranked("", r#"has_facet(category, "/laptops/lenovo")"#)
+        )
+        .unwrap()[..2],
+        ["3", "4"]
+    );
+    assert_eq!(
+        ordered_ids(
+            &index,
+            &ranked(
+                "",
+                r#"10 * has_facet(category, "/laptops") - facet_depth(category)"#
+            )
+        )
+        .unwrap(),
+        ["1", "3", "4", "2"]
+    );
+    assert_eq!(
+        ordered_ids(
+            &index,
+            &ranked("", r#"has_facet(category, "/") - facet_depth(category)"#)
+        )
+        .unwrap()[0],
+        "2"
+    );
+
+    for sort in [
+        r#"has_facet(brand, "/apple")"#,
+        r#"has_facet(category, "laptops")"#,
+        "has_facet(category, 1)",
+        "facet_depth(category, 1)",
+        "category",
+        r#"price + "1""#,
+    ] {
+        assert!(ordered_ids(&index, &ranked("", sort)).is_err(), "{sort}");
+    }
+}
+
+#[tokio::test]
+async fn test_null_values_policy() {
+    let dir = tempfile::tempdir().unwrap();
+    let index = open_index(dir.path()).await;
+
+    // у документа 4 нет rank_position
+    let mut req = ranked("", "rank_position");
+    req.sort_order = SortOrder::Asc;
+    assert_eq!(ordered_ids(&index, &req).unwrap(), ["3", "2", "1", "4"]);
+    req.sort_invalid = InvalidSortValue::First;
+    assert_eq!(ordered_ids(&index, &req).unwrap(), ["4", "3", "2", "1"]);
+    req.sort_invalid = InvalidSortValue::Drop;
+    assert_eq!(ordered_ids(&index, &req).unwrap(), ["3", "2", "1"]);
+    let hits = execute_search(&index, &req).unwrap();
+    assert_eq!(hits.invalid_sort_values, Some(1));
+
+    // ошибка вычисления считается так же, как NaN
+    req.sort = Some("clamp(rank_position, 3, 2)".to_string());
+    assert_eq!(
+        execute_search(&index, &req).unwrap().invalid_sort_values,
+        Some(4)
+    );
+
+    req.sort = Some("coalesce(rank_position, 0) + is_null(rank_position)".to_string());
+    req.sort_invalid = InvalidSortValue::Last;
+    assert_eq!(ordered_ids(&index, &req).unwrap(), ["4", "3", "2", "1"]);
+    assert_eq!(
+        execute_search(&index, &req).unwrap().invalid_sort_values,
+        Some(0)
+    );
+
+    // документы без значения остаются в конце и при постраничном обходе
+    let mut req = ranked("", "rank_position");
+    req.sort_order = SortOrder::Desc;
+    req.limit = 2;
+    let first = execute_search(&index, &req).unwrap();
+    req.cursor = first.cursor;
+    req.limit = 3;
+    assert_eq!(ordered_ids(&index, &req).unwrap(), ["3", "4"]);
+}
@@ -0,0 +1,154 @@
+use std::collections::HashSet;
+use std::time::Duration;
+
+use corelib::api::{MetaColumn, MetaColumnModifier, MetaColumnType, MetaSchema};
+use corelib::config::IndexerConfig;
+use indexer::api::{Document, FieldValue, IndexableField};
+use indexer::infra::index::IndexState;
+use indexer::infra::index_registry;
+use searcher::api::SearchRequest;
+use searcher::domain::index::SearchIndex;
+use searcher::engine::search::execute_search;
+use tantivy::ReloadPolicy;
+
+fn test_schema() -> MetaSchema {
+    let column = |name: &str, column_type, modifiers: &[MetaColumnModifier]| MetaColumn {
+        name: name.to_string(),
+        column_type,
+        modifiers: modifiers.iter().cloned().collect::<HashSet<_>>(),
+    };
+
+    MetaSchema {
+        name: "products".to_string(),
+        columns: vec![
+            column("id", MetaColumnType::Text, &[MetaColumnModifier::Id]),
+            column(
+                "title",
+                MetaColumnType::Text,
+                &[MetaColumnModifier::Equals, MetaColumnModifier::FullText],
+            ),
+        ],
+    }
+}
+
+fn document(id: &str, title: &str) -> Document {
+    Document {
+        index_name: "products".to_string(),
+        index_version: 1,
+        fields: vec![
+            IndexableField {
+                name: "id".to_string(),
+                value: Some(FieldValue::Text(id.to_string())),
+            },
+            IndexableField {
+                name: "title".to_string(),
+                value: Some(FieldValue::Text(title.to_string())),
+            },
+        ],
+    }
+}
+
+async fn open_pair(root: &std::path::Path) -> (IndexState, SearchIndex) {
+    let config = IndexerConfig {
+        index_registry_dir: root.to_path_buf(),
+        ..Default::default()
+    };
+    let registry = index_registry::load_all_indexes(&config).await.unwrap();
+    let index_state = IndexState::init_index_state(&registry, &test_schema())
+        .await
+        .unwrap();
+    let index_dir = root.join("products").join("index");
+    let search_index =
+        SearchIndex::open_from_path(index_dir.to_str().unwrap(), ReloadPolicy::OnCommitWithDelay)
+            .unwrap();
+    (index_state, search_index)
+}
+
+fn select_all() -> SearchRequest {
+    serde_json::from_value(serde_json::json!({
+        "select": ["id"],
+        "from": "products",
+        "filter": "*",
+    }))
+    .unwrap()
+}
+
+#[tokio::test]
+async fn test_search_waits_for_committed_opstamp() {
+    let dir = tempfile::tempdir().unwrap();
+    let (index_state, search_index) = open_pair(dir.path()).await;
+
+    let doc_opstamp = index_state
+        .add_document_safely(document("1", "macbook pro"))
+        .await
+        .unwrap();
+
+    // без коммита документ не виден
+    let not_committed = search_index
+        .wait_for_opstamp(doc_opstamp, Duration::from_millis(50))
+        .await;
+    assert!(not_committed.is_err());
+
+    let commit_opstamp = index_state.commit().await.unwrap();
+    assert!(commit_opstamp > doc_opstamp);
+
+    search_index
+        .wait_for_opstamp(doc_opstamp, Duration::from_secs(5))
+        .await
+        .unwrap();
+
+    let hits = execute_search(&search_index, &select_all()).unwrap();
+    assert_eq!(hits.top_docs.len(), 1);
+}
+
+#[tokio::test]
+async fn test_refresh_returns_visible_opstamp() {
+    let dir = tempfile::tempdir().unwrap();
+    let (index_state, search_index) = open_pair(dir.path()).await;
+
+    index_state
+        .add_document_safely(document("1", "iphone 12"))
+        .await
+        .unwrap();
+    let commit_opstamp = index_state.commit().await.unwrap();
+
+    assert_eq!(search_index.refresh().unwrap(), commit_opstamp);
+    assert_eq!(
+        execute_search(&search_index, &select_all())
+            .unwrap()
+            .top_docs
+            .len(),
+        1
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_concurrent_waiters_see_commit() {
+    let dir = tempfile::tempdir().unwrap();
+    let (index_state, search_index) = open_pair(dir.path()).await;
+    let search_index = std::sync::Arc::new(search_index);
+
+    let doc_opstamp = index_state
+        .add_document_safely(document("1", "thinkpad x1"))
+        .await
+        .unwrap();
+
+    let waiters: Vec<_> = (0..32)
+        .map(|_| {
+            let search_index = search_index.clone();
+            tokio::spawn(async move {
+                search_index
+                    .wait_for_opstamp(doc_opstamp, Duration::from_secs(5))
+                    .await
+            })
+        })
+        .collect();
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let commit_opstamp = index_state.commit().await.unwrap();
+
+    for waiter in waiters {
+        waiter.await.unwrap().unwrap();
+    }
+    assert_eq!(search_index.loaded_opstamp(), commit_opstamp);
+}